@@ -0,0 +1,81 @@
+//! Measures pin throughput when several threads share one buffer pool.
+//!
+//! The threads pin pseudo-random pages of a working set that is larger than the
+//! pool, so the run mixes hits with evictions.
+//!
+//! Run with `cargo bench --bench buffer_concurrent`.
+
+use std::{
+    hint::black_box,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+use rimple::{
+    buffer::{manager::BufferManager, policy::ReplacementPolicyKind},
+    file::{FileManager, FileOptions, Page, PageId, backend::MemoryBackend},
+    log::manager::LogManager,
+};
+
+const PAGE_SIZE: usize = 64;
+const FRAMES: usize = 512;
+const WORKING_SET: u64 = 768;
+const PINS_PER_THREAD: usize = 200_000;
+
+fn buffer_manager() -> Arc<BufferManager> {
+    let file_manager = Arc::new(FileManager::with_backend(
+        Arc::new(MemoryBackend::new()),
+        PAGE_SIZE,
+        FileOptions::default(),
+    ));
+    let pages: Vec<_> = (0..WORKING_SET)
+        .map(|_| Page::with_size(PAGE_SIZE))
+        .collect();
+    file_manager
+        .write_many(Path::new("data"), 0, &pages)
+        .unwrap();
+    let log_manager = Arc::new(Mutex::new(
+        LogManager::new(file_manager.clone(), "log").unwrap(),
+    ));
+    let buffer_manager = Arc::new(BufferManager::new(file_manager, log_manager, FRAMES));
+    buffer_manager.set_read_ahead(0);
+    buffer_manager.set_replacement_policy(ReplacementPolicyKind::Clock);
+    buffer_manager
+}
+
+fn main() {
+    for threads in [1, 2, 4, 8] {
+        let buffer_manager = buffer_manager();
+        let start = Instant::now();
+        thread::scope(|scope| {
+            for thread in 0..threads {
+                let buffer_manager = &buffer_manager;
+                scope.spawn(move || {
+                    let mut state = thread as u64 + 1;
+                    for _ in 0..PINS_PER_THREAD {
+                        // xorshift, cheap enough not to dominate the pins
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        let page_id = PageId::new(PathBuf::from("data"), state % WORKING_SET);
+                        drop(buffer_manager.pin(black_box(&page_id)).unwrap());
+                    }
+                });
+            }
+        });
+        let elapsed = start.elapsed();
+
+        let pins = threads * PINS_PER_THREAD;
+        let stats = buffer_manager.stats();
+        println!(
+            "{} threads: {:>6.2} M pins/s, {:.1}% hits ({} pins in {:?})",
+            threads,
+            pins as f64 / elapsed.as_secs_f64() / 1e6,
+            100.0 * stats.hits as f64 / stats.pins as f64,
+            pins,
+            elapsed
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Measures how long pinning a cached page takes as the buffer pool grows.
+//!
+//! Run with `cargo bench --bench buffer_pin`.
+
+use std::{
+    hint::black_box,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use rimple::{
+    buffer::manager::BufferManager,
+    file::{FileManager, FileOptions, Page, PageId, backend::MemoryBackend},
+    log::manager::LogManager,
+};
+
+const PAGE_SIZE: usize = 64;
+/// Number of distinct pages pinned in each round, capped by the pool size.
+const WORKING_SET: usize = 1_000;
+const PINS: usize = 1_000_000;
+
+fn buffer_manager(frames: usize) -> Arc<BufferManager> {
+    let file_manager = Arc::new(FileManager::with_backend(
+        Arc::new(MemoryBackend::new()),
+        PAGE_SIZE,
+        FileOptions::default(),
+    ));
+    let pages: Vec<_> = (0..WORKING_SET)
+        .map(|_| Page::with_size(PAGE_SIZE))
+        .collect();
+    file_manager
+        .write_many(Path::new("data"), 0, &pages)
+        .unwrap();
+    let log_manager = Arc::new(Mutex::new(
+        LogManager::new(file_manager.clone(), "log").unwrap(),
+    ));
+    let buffer_manager = Arc::new(BufferManager::new(file_manager, log_manager, frames));
+    buffer_manager.set_read_ahead(0);
+    buffer_manager
+}
+
+fn main() {
+    for frames in [8, 1_000, 100_000] {
+        let buffer_manager = buffer_manager(frames);
+        let page_ids: Vec<_> = (0..WORKING_SET.min(frames) as u64)
+            .map(|block_no| PageId::new(PathBuf::from("data"), block_no))
+            .collect();
+        // Load the working set, so the measured pins are all hits. Pins are released
+        // when the returned guard is dropped.
+        for page_id in &page_ids {
+            buffer_manager.pin(page_id).unwrap();
+        }
+
+        let start = Instant::now();
+        for page_id in page_ids.iter().cycle().take(PINS) {
+            drop(buffer_manager.pin(black_box(page_id)).unwrap());
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "{:>7} frames: {:>8.1} ns per pin ({} pins in {:?})",
+            frames,
+            elapsed.as_nanos() as f64 / PINS as f64,
+            PINS,
+            elapsed
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! Online backups of a live database.
+//!
+//! [`SimpleDB::backup_to`](crate::db::SimpleDB::backup_to) copies a database into
+//! another directory while transactions keep running. The copy is consistent in
+//! the sense that opening it runs the normal recovery, which rolls back every
+//! transaction that had not committed when the backup finished. A
+//! [`BackupManifest`] written last describes what was copied and marks the
+//! backup as complete.
+//!
+//! [`SimpleDB::backup_incremental_to`](crate::db::SimpleDB::backup_incremental_to)
+//! only copies the pages written since an earlier backup, recognized by the
+//! [LSN](crate::file::Page::lsn) in their header. [`restore`] applies a full
+//! backup and its incremental backups in order.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::info;
+
+use crate::{
+    db::SimpleDB,
+    file::{
+        DurabilityMode, FileManager, FileOptions, Lsn, Page, PageId,
+        backend::{DiskBackend, StorageBackend},
+        dir_lock::DirLock,
+        superblock::{self, Superblock, SuperblockError},
+    },
+};
+
+/// Errors raised when a backup cannot be read or restored.
+#[derive(thiserror::Error, Debug)]
+pub enum BackupError {
+    /// The directory holds no finished backup.
+    #[error("No backup found: manifest {0:?} does not exist")]
+    MissingManifest(PathBuf),
+
+    /// The manifest cannot be parsed.
+    #[error("Backup manifest {path:?} is invalid: {reason}")]
+    InvalidManifest { path: PathBuf, reason: String },
+
+    /// A backup does not continue the chain of backups it is used with.
+    #[error("Backup {path:?} does not fit the backup chain: {reason}")]
+    BrokenChain { path: PathBuf, reason: String },
+}
+
+/// Describes a finished backup. Stored as [`BackupManifest::FILE`] in the backup directory.
+///
+/// The manifest is a plain text file of `key=value` lines, so it can be inspected
+/// without any tooling. A full backup has no `base`; an incremental backup names
+/// the backup it builds on by that backup's creation time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupManifest {
+    /// The directory of the database that was backed up.
+    pub source: PathBuf,
+    /// When the backup started.
+    pub created_at: SystemTime,
+    /// The creation time of the backup this one builds on, or `None` for a full backup.
+    pub base: Option<SystemTime>,
+    /// The page size of the database.
+    pub page_size: usize,
+    /// The latest LSN of the log when the backup started. Pages written since
+    /// carry at least this LSN and are copied by the next incremental backup.
+    /// Transactions that had not committed by then are rolled back when the
+    /// backup is opened.
+    pub start_lsn: Lsn,
+    /// Number of log pages when the backup started.
+    pub log_start: u64,
+    /// Number of log pages copied, i.e. the length of the log when the backup finished.
+    pub log_end: u64,
+    /// Number of pages per data file when the backup was taken, keyed by file name.
+    pub files: BTreeMap<String, u64>,
+    /// The pages an incremental backup copied per data file, keyed by file name.
+    /// Empty for a full backup, which copies every page.
+    pub changed_pages: BTreeMap<String, Vec<u64>>,
+    /// The data files of the base backup that were deleted since, which a restore
+    /// deletes as well. Empty for a full backup.
+    pub deleted_files: BTreeSet<String>,
+}
+
+impl BackupManifest {
+    pub const FILE: &'static str = "backup.manifest";
+
+    /// Version of the manifest format.
+    const VERSION: u64 = 3;
+
+    /// The oldest version of the manifest format that can still be read. Version 2
+    /// did not record deleted files.
+    const MIN_VERSION: u64 = 2;
+
+    /// Returns the LSNs of the log records the backup copied although they were
+    /// written after it started, see [`start_lsn`](Self::start_lsn).
+    pub fn late_lsns(&self) -> Range<Lsn> {
+        let log_end_lsn = self.log_end * self.page_size as Lsn;
+        self.start_lsn + 1..log_end_lsn + 1
+    }
+
+    /// Writes the manifest into `dir` on `backend` and makes it durable.
+    pub fn write(&self, backend: &dyn StorageBackend, dir: &Path) -> anyhow::Result<()> {
+        let mut content = format!(
+            "version={}\nsource={}\ncreated_at={}\n",
+            Self::VERSION,
+            self.source.display(),
+            micros(self.created_at)
+        );
+        if let Some(base) = self.base {
+            content.push_str(&format!("base={}\n", micros(base)));
+        }
+        content.push_str(&format!(
+            "page_size={}\nstart_lsn={}\nlog_start={}\nlog_end={}\n",
+            self.page_size, self.start_lsn, self.log_start, self.log_end
+        ));
+        for (name, pages) in &self.files {
+            content.push_str(&format!("file.{}={}\n", name, pages));
+        }
+        for (name, blocks) in &self.changed_pages {
+            let blocks: Vec<_> = blocks.iter().map(u64::to_string).collect();
+            content.push_str(&format!("changed.{}={}\n", name, blocks.join(",")));
+        }
+        for name in &self.deleted_files {
+            content.push_str(&format!("deleted={}\n", name));
+        }
+
+        let path = dir.join(Self::FILE);
+        backend.delete(&path)?;
+        backend.write(&path, 0, content.as_bytes())?;
+        backend.sync(&path)?;
+        Ok(())
+    }
+
+    /// Reads the manifest of the backup in `dir` on `backend`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`BackupError::MissingManifest`] if `dir` holds no finished
+    /// backup, and with [`BackupError::InvalidManifest`] if it cannot be parsed.
+    pub fn read(backend: &dyn StorageBackend, dir: &Path) -> anyhow::Result<Self> {
+        let path = dir.join(Self::FILE);
+        let size = backend.size(&path)?;
+        if size == 0 {
+            return Err(BackupError::MissingManifest(path).into());
+        }
+        let mut bytes = vec![0; size as usize];
+        backend.read(&path, 0, &mut bytes)?;
+        let invalid = |reason: String| BackupError::InvalidManifest {
+            path: path.clone(),
+            reason,
+        };
+        let content = String::from_utf8(bytes).map_err(|_| invalid("not UTF-8".to_string()))?;
+        let number = |key: &str, value: &str| -> Result<u64, BackupError> {
+            value
+                .parse()
+                .map_err(|_| invalid(format!("malformed {} {:?}", key, value)))
+        };
+
+        let mut values = BTreeMap::new();
+        let mut files = BTreeMap::new();
+        let mut changed_pages = BTreeMap::new();
+        let mut deleted_files = BTreeSet::new();
+        for line in content.lines().filter(|line| !line.is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("malformed line {:?}", line)))?;
+            if let Some(name) = key.strip_prefix("file.") {
+                files.insert(name.to_string(), number(key, value)?);
+            } else if let Some(name) = key.strip_prefix("changed.") {
+                let blocks = value
+                    .split(',')
+                    .filter(|block| !block.is_empty())
+                    .map(|block| number(key, block))
+                    .collect::<Result<_, _>>()?;
+                changed_pages.insert(name.to_string(), blocks);
+            } else if key == "deleted" {
+                deleted_files.insert(value.to_string());
+            } else {
+                values.insert(key.to_string(), value.to_string());
+            }
+        }
+        let mut text = |key: &str| {
+            values
+                .remove(key)
+                .ok_or_else(|| invalid(format!("missing {}", key)))
+        };
+
+        let version = number("version", &text("version")?)?;
+        if !(Self::MIN_VERSION..=Self::VERSION).contains(&version) {
+            return Err(invalid(format!("unsupported version {}", version)).into());
+        }
+        let base = match text("base") {
+            Ok(base) => Some(UNIX_EPOCH + Duration::from_micros(number("base", &base)?)),
+            Err(_) => None,
+        };
+        Ok(Self {
+            source: PathBuf::from(text("source")?),
+            created_at: UNIX_EPOCH
+                + Duration::from_micros(number("created_at", &text("created_at")?)?),
+            base,
+            page_size: number("page_size", &text("page_size")?)? as usize,
+            start_lsn: number("start_lsn", &text("start_lsn")?)?,
+            log_start: number("log_start", &text("log_start")?)?,
+            log_end: number("log_end", &text("log_end")?)?,
+            files,
+            changed_pages,
+            deleted_files,
+        })
+    }
+}
+
+/// Restores a database into `dest` from a full backup and the incremental backups on top of it.
+///
+/// `backups` lists the backup directories oldest first: a full backup made by
+/// [`SimpleDB::backup_to`], followed by backups made by
+/// [`SimpleDB::backup_incremental_to`], each based on the one before it. The
+/// backups are applied in that order, so later pages and log pages overwrite
+/// earlier ones. Like any backup, the result is recovered when it is opened for
+/// the first time.
+///
+/// # Errors
+///
+/// Fails with [`BackupError::BrokenChain`] if the backups do not form a chain,
+/// and with [`SuperblockError::AlreadyExists`] if `dest` holds a database.
+pub fn restore<P: AsRef<Path>>(backups: &[P], dest: impl AsRef<Path>) -> anyhow::Result<()> {
+    let dest = dest.as_ref();
+    let backups: Vec<&Path> = backups.iter().map(AsRef::as_ref).collect();
+    let manifests = backups
+        .iter()
+        .map(|dir| BackupManifest::read(&DiskBackend::read_only(), dir))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    check_chain(&backups, &manifests)?;
+    info!("Restoring {} backup(s) into {:?}", backups.len(), dest);
+
+    let last = backups.last().expect("chain is not empty");
+    let superblock_path = last.join(SimpleDB::SUPERBLOCK_FILE);
+    let mut superblock = Superblock::read(&DiskBackend::read_only(), &superblock_path)?
+        .ok_or(SuperblockError::Missing(superblock_path))?;
+    std::fs::create_dir_all(dest)?;
+    let dest_superblock = dest.join(SimpleDB::SUPERBLOCK_FILE);
+    if Superblock::read(&DiskBackend::new(), &dest_superblock)?.is_some() {
+        return Err(SuperblockError::AlreadyExists(dest_superblock).into());
+    }
+    let options = FileOptions {
+        checksums: superblock.checksums,
+        segment_size: superblock.segment_size,
+        durability: DurabilityMode::FsyncOnFlush,
+        read_only: false,
+    };
+    let target = FileManager::with_options(dest, superblock.page_size, options.clone())?;
+
+    let mut restored = BTreeSet::from([SimpleDB::LOG_FILE.to_string()]);
+    let mut log_from = 0;
+    for (dir, manifest) in backups.iter().zip(&manifests) {
+        let source = FileManager::with_options(
+            dir,
+            superblock.page_size,
+            FileOptions {
+                read_only: true,
+                ..options.clone()
+            },
+        )?;
+        for (name, &pages) in &manifest.files {
+            let blocks = match manifest.base {
+                None => (0..pages).collect(),
+                Some(_) => manifest
+                    .changed_pages
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            copy_pages(&source, &target, &dir.join(name), &dest.join(name), blocks)?;
+            // Appended pages that were never written are not copied, but still exist
+            while target.size(&dest.join(name))? < pages {
+                target.append_page(&dest.join(name))?;
+            }
+            restored.insert(name.clone());
+        }
+        for name in &manifest.deleted_files {
+            target.delete(&dest.join(name))?;
+            restored.remove(name);
+        }
+        copy_pages(
+            &source,
+            &target,
+            &dir.join(SimpleDB::LOG_FILE),
+            &dest.join(SimpleDB::LOG_FILE),
+            (log_from..manifest.log_end).collect(),
+        )?;
+        log_from = manifest.log_end.saturating_sub(1);
+    }
+
+    for name in &restored {
+        target.sync(&dest.join(name))?;
+    }
+    superblock.log_file = SimpleDB::LOG_FILE.to_string();
+    superblock.clean_shutdown = false;
+    superblock.write(target.backend(), &dest_superblock)?;
+    // Tells recovery where the files named in the log live now
+    manifests
+        .last()
+        .expect("chain is not empty")
+        .write(target.backend(), dest)?;
+    info!("Restore into {:?} done", dest);
+    Ok(())
+}
+
+/// Checks that the backups start with a full backup and each builds on the one before.
+fn check_chain(backups: &[&Path], manifests: &[BackupManifest]) -> Result<(), BackupError> {
+    let broken = |i: usize, reason: &str| BackupError::BrokenChain {
+        path: backups
+            .get(i)
+            .map_or_else(PathBuf::new, |dir| dir.to_path_buf()),
+        reason: reason.to_string(),
+    };
+    match manifests.first() {
+        None => return Err(broken(0, "no backups given")),
+        Some(first) if first.base.is_some() => {
+            return Err(broken(0, "the first backup is not a full backup"));
+        }
+        Some(_) => {}
+    }
+    for (i, pair) in manifests.windows(2).enumerate() {
+        if pair[1].base != Some(pair[0].created_at) || pair[1].source != pair[0].source {
+            return Err(broken(i + 1, "it is not based on the backup before it"));
+        }
+    }
+    Ok(())
+}
+
+/// Copies the given pages of a file between two file managers.
+fn copy_pages(
+    source: &FileManager,
+    target: &FileManager,
+    from: &Path,
+    to: &Path,
+    blocks: Vec<u64>,
+) -> anyhow::Result<()> {
+    let mut page = Page::with_size(source.page_size());
+    for block_no in blocks {
+        source.read(&PageId::new(from.to_path_buf(), block_no), &mut page)?;
+        target.write(&PageId::new(to.to_path_buf(), block_no), &page)?;
+    }
+    Ok(())
+}
+
+/// Returns the current time, truncated to the precision manifests store it with.
+pub(crate) fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_micros(micros(SystemTime::now()))
+}
+
+/// Returns a point in time as microseconds since the Unix epoch.
+fn micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// Returns the names of the files that hold metadata of a database or a backup.
+///
+/// The default log name is among them, as backups store the log under it.
+pub(crate) fn reserved_file_names() -> [String; 5] {
+    let superblock_temp = superblock::temp_path(Path::new(SimpleDB::SUPERBLOCK_FILE));
+    [
+        SimpleDB::LOG_FILE.to_string(),
+        SimpleDB::SUPERBLOCK_FILE.to_string(),
+        superblock_temp.to_string_lossy().into_owned(),
+        DirLock::LOCK_FILE.to_string(),
+        BackupManifest::FILE.to_string(),
+    ]
+}
+
+/// Returns the logical data files in `dir`.
+///
+/// Everything but the log, the metadata files and temporary files counts as data.
+/// The segments of a segmented file are reported once, under the file's name.
+pub(crate) fn data_files(
+    dir: &Path,
+    file_manager: &FileManager,
+    log_file: &str,
+) -> io::Result<Vec<PathBuf>> {
+    let reserved = reserved_file_names();
+    let segmented = file_manager.options().segment_size.is_some();
+
+    let mut files = BTreeSet::new();
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if !entry.file_type()?.is_file() || file_manager.is_temp_file(&path) {
+            continue;
+        }
+        let name = match name.rsplit_once('.') {
+            Some((base, segment)) if segmented && segment.parse::<u64>().is_ok() => base,
+            _ => name.as_str(),
+        };
+        if name != log_file && !reserved.iter().any(|reserved| reserved == name) {
+            files.insert(dir.join(name));
+        }
+    }
+    Ok(files.into_iter().collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn manifest_round_trips() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let manifest = BackupManifest {
+            source: PathBuf::from("/var/lib/db"),
+            created_at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            base: Some(UNIX_EPOCH + Duration::from_micros(1_600_000_000_000_000)),
+            page_size: 400,
+            start_lsn: 12_345,
+            log_start: 3,
+            log_end: 5,
+            files: [("data".to_string(), 10), ("index.db".to_string(), 2)].into(),
+            changed_pages: [
+                ("data".to_string(), vec![1, 7]),
+                ("index.db".to_string(), vec![]),
+            ]
+            .into(),
+            deleted_files: ["old".to_string()].into(),
+        };
+
+        // test
+        manifest.write(&DiskBackend::new(), tmp.path()).unwrap();
+        let read = BackupManifest::read(&DiskBackend::new(), tmp.path()).unwrap();
+
+        // verify
+        assert_eq!(read, manifest);
+    }
+
+    #[test]
+    fn reading_without_a_manifest_fails() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let err = BackupManifest::read(&DiskBackend::new(), tmp.path()).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<BackupError>(),
+            Some(BackupError::MissingManifest(_))
+        ));
+    }
+}
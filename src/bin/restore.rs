@@ -0,0 +1,24 @@
+//! Restores a database from a full backup and the incremental backups on top of it.
+//!
+//! Usage: `restore <dest> <full backup> [<incremental backup>...]`
+
+use ::log::info;
+use anyhow::bail;
+
+use rimple::backup;
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((dest, backups)) = args.split_first() else {
+        bail!("Usage: restore <dest> <full backup> [<incremental backup>...]");
+    };
+    if backups.is_empty() {
+        bail!("Usage: restore <dest> <full backup> [<incremental backup>...]");
+    }
+
+    backup::restore(backups, dest)?;
+    info!("Restored {} backup(s) into {}", backups.len(), dest);
+    Ok(())
+}
@@ -4,11 +4,14 @@ use std::{
 };
 
 use crate::{
-    file::{PageId, FileManager, Page},
+    file::{FileManager, Lsn, Page, PageId},
     log::manager::LogManager,
 };
 
 /// A buffer is a page of main memory that can hold the contents of a disk page.
+///
+/// How often a buffer is pinned is tracked by the
+/// [`BufferManager`](crate::buffer::manager::BufferManager), next to the buffer.
 pub struct Buffer {
     file_manager: Arc<FileManager>,
 
@@ -20,14 +23,15 @@ pub struct Buffer {
     /// The identifier of the disk page currently stored in this buffer, if any.
     page_id: Option<PageId>,
 
-    /// Number of times this buffer has been pinned (i.e., how many clients are currently using it).
-    pins: usize,
-
     /// The transaction number that last modified this buffer, if any.
     txnum: i32,
 
     /// The log sequence number (LSN) of the most recent log record that modified this buffer, if any.
-    lsn: i32,
+    lsn: Option<Lsn>,
+
+    /// Whether the file of the page was deleted while the page was pinned, so its
+    /// changes are dropped instead of written back.
+    discarded: bool,
 }
 
 impl Buffer {
@@ -37,9 +41,9 @@ impl Buffer {
             log_manager: log_manager.clone(),
             page: Page::with_size(file_manager.page_size()),
             page_id: None,
-            pins: 0,
             txnum: -1,
-            lsn: -1,
+            lsn: None,
+            discarded: false,
         }
     }
 
@@ -56,49 +60,106 @@ impl Buffer {
         self.page_id.as_ref()
     }
 
-    pub fn set_modified(&mut self, txnum: i32, lsn: i32) {
+    /// Marks the page as modified by `txnum` and stamps its header with the LSN
+    /// of the log record describing the change.
+    ///
+    /// Changes that were not logged, such as undoing a record during rollback, are
+    /// stamped with the latest LSN of the log instead. Every page changed after some
+    /// point in the log therefore carries a larger LSN, which is what incremental
+    /// backups rely on.
+    pub(crate) fn set_modified(&mut self, txnum: i32, lsn: Option<Lsn>) -> io::Result<()> {
         self.txnum = txnum;
-        if lsn >= 0 {
-            self.lsn = lsn;
-        }
-    }
-
-    pub fn is_pinned(&self) -> bool {
-        self.pins > 0
+        let stamp = match lsn {
+            Some(lsn) => {
+                self.lsn = Some(lsn);
+                lsn
+            }
+            None => self
+                .log_manager
+                .lock()
+                .map_err(|_| io::Error::other("Failed to acquire log manager lock"))?
+                .latest_lsn(),
+        };
+        self.page.set_lsn(stamp);
+        Ok(())
     }
 
     pub fn modifying_txn(&self) -> i32 {
         self.txnum
     }
 
-    pub fn pin(&mut self) {
-        self.pins += 1;
+    /// Returns whether the page has changes that are not written back yet.
+    pub fn is_modified(&self) -> bool {
+        self.txnum >= 0
     }
 
-    pub fn unpin(&mut self) {
-        if self.pins > 0 {
-            self.pins -= 1;
-        }
+    /// Returns the LSN of the latest logged change to the page, if there is one.
+    pub fn lsn(&self) -> Option<Lsn> {
+        self.lsn
     }
 
     pub(crate) fn assign_to_page(&mut self, page_id: &PageId) -> anyhow::Result<()> {
         self.flush()?;
-        self.page_id = Some(page_id.clone());
+        // Only claim the page once its contents were read and verified, so a failed
+        // read never leaves the buffer posing as a valid copy of the page.
+        self.page_id = None;
+        self.discarded = false;
         self.file_manager.read(page_id, &mut self.page)?;
-        self.pins = 0;
+        self.page_id = Some(page_id.clone());
+
+        Ok(())
+    }
+
+    /// Assigns the buffer to a page whose contents were already read, e.g. by read-ahead.
+    pub(crate) fn assign_prefetched(&mut self, page_id: &PageId, page: Page) -> anyhow::Result<()> {
+        self.flush()?;
+        self.page = page;
+        self.page_id = Some(page_id.clone());
+        self.discarded = false;
 
         Ok(())
     }
 
+    /// Detaches the buffer from its page, which must have been written back.
+    pub(crate) fn unassign(&mut self) {
+        debug_assert!(!self.is_modified());
+        self.page_id = None;
+        self.discarded = false;
+    }
+
+    /// Detaches the buffer from its page and drops its changes, e.g. because the
+    /// file of the page was deleted.
+    pub(crate) fn discard(&mut self) {
+        self.txnum = -1;
+        self.lsn = None;
+        self.page_id = None;
+        self.discarded = false;
+    }
+
+    /// Drops the changes of a page that is still pinned, now and whenever it is
+    /// changed again, because its file was deleted.
+    pub(crate) fn discard_changes(&mut self) {
+        self.txnum = -1;
+        self.lsn = None;
+        self.discarded = true;
+    }
+
+    /// Writes the page back if it was modified, after the log records describing the change.
     pub(crate) fn flush(&mut self) -> anyhow::Result<()> {
+        if self.discarded {
+            self.txnum = -1;
+            self.lsn = None;
+        }
         if self.txnum >= 0 {
-            let mut log_manager = self
-                .log_manager
-                .lock()
-                .map_err(|_| io::Error::other("Failed to acquire log manager lock"))?;
-            log_manager.flush(self.lsn as usize)?;
+            if let Some(lsn) = self.lsn {
+                self.log_manager
+                    .lock()
+                    .map_err(|_| io::Error::other("Failed to acquire log manager lock"))?
+                    .flush(lsn)?;
+            }
             self.file_manager
                 .write(self.page_id.as_ref().unwrap(), &self.page)?;
+            self.txnum = -1;
         }
 
         Ok(())
@@ -1,30 +1,305 @@
 use std::{
+    borrow::Borrow,
+    collections::{BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
     io,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    thread,
     time::{Duration, Instant},
 };
 
-use anyhow::bail;
 use log::{debug, trace};
 
 use crate::{
-    buffer::buffer::Buffer,
-    file::{PageId, FileManager},
+    buffer::{
+        buffer::Buffer,
+        pinned::PinnedBuffer,
+        policy::{ReplacementPolicy, ReplacementPolicyKind},
+        stats::{BufferStats, FrameInfo},
+    },
+    file::{FileManager, Lsn, Page, PageId, temp_file::PageCache},
     log::manager::LogManager,
 };
 
 #[derive(thiserror::Error, Debug)]
 pub enum BufferError {
-    #[error("Buffer pinning failed: {0}")]
-    Timeout(String),
+    /// No buffer became available for the page before the pin timeout expired.
+    #[error("Timed out pinning page {page_id}: {pinned} of {frames} buffers stayed pinned")]
+    Timeout {
+        /// The page that was to be pinned.
+        page_id: PageId,
+        /// Number of buffers pinned when the wait was given up.
+        pinned: usize,
+        /// Number of buffers in the pool.
+        frames: usize,
+    },
+
+    /// A replacement policy was named that does not exist.
+    #[error("Unknown replacement policy {0:?}, expected one of naive, lru, clock or lru-2")]
+    UnknownPolicy(String),
+}
+
+/// How often pins found their page in the pool while a replacement policy was in use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HitStats {
+    /// Pins of pages that were in the pool already.
+    pub hits: u64,
+    /// Pins that had to read the page, evicting another one.
+    pub misses: u64,
 }
 
+impl HitStats {
+    /// Returns the fraction of pins that were hits, or 0 if nothing was pinned.
+    pub fn hit_ratio(&self) -> f64 {
+        let pins = self.hits + self.misses;
+        if pins == 0 {
+            0.0
+        } else {
+            self.hits as f64 / pins as f64
+        }
+    }
+}
+
+/// A modified page that was not written back yet, see [`BufferManager::dirty_pages`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirtyPage {
+    /// The frame holding the page.
+    pub frame: usize,
+    /// The LSN of the first logged change since the page was last written, if any
+    /// change was logged. Log records before it are not needed to restore the page.
+    pub first_lsn: Option<Lsn>,
+    /// The transactions that changed the page since it was last written.
+    pub txns: BTreeSet<i32>,
+}
+
+/// Number of pages prefetched when a sequential scan is detected, unless configured otherwise.
+pub const DEFAULT_READ_AHEAD: usize = 4;
+
+/// How long a pin waits for a buffer to become available, unless configured otherwise.
+pub const DEFAULT_PIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of independently locked parts of the page table and the dirty page table.
+const SHARDS: usize = 64;
+
+/// Manages the pool of buffers that hold pages in memory.
+///
+/// All methods take `&self`, so the buffer manager is shared between threads as
+/// an `Arc<BufferManager>`. There is no lock around the whole pool: pins of
+/// different pages only meet in the shards of the page table, and only for as
+/// long as a lookup takes. Pins share the frames with the buffer manager through
+/// an inner `Arc`, so they do not hold on to the buffer manager itself.
+///
+/// - The page table maps every cached page to the frame holding it. It is split
+///   into shards by the hash of the page.
+/// - Every frame counts its pins in an atomic next to its buffer. A frame is only
+///   given another page after its pin count was swapped from 0 to 1 while holding
+///   the shard of its old page, so a concurrent pin either finds the old page
+///   still pinned or not at all.
+/// - The [`ReplacementPolicy`] keeps its state in atomics.
+/// - A pin that finds every buffer pinned waits on a condition variable, which is
+///   only signalled while some pin is waiting.
+///
+/// A page is loaded while holding the lock of its buffer, after entering it into
+/// the page table. A concurrent pin of the same page therefore finds the frame and
+/// waits for the buffer lock until the page is read. A page is written back before
+/// it leaves the page table, so it can always be found in the pool or in storage.
+///
+/// Modified pages are tracked in a dirty page table, so that flushing the pages
+/// of a transaction only touches the buffers it changed. Locks are taken in the
+/// order buffer, page table shard, dirty page table shard. Several buffers are
+/// only ever locked at once with `try_lock`.
 pub struct BufferManager {
     file_manager: Arc<FileManager>,
-    log_manager: Arc<Mutex<LogManager>>,
-    pool: Vec<Arc<Mutex<Buffer>>>,
-    available: usize,
-    max_time: u64,
+    pool: Arc<Pool>,
+    /// The frame each cached page is held by.
+    page_table: Sharded<PageId, usize>,
+    policy: RwLock<ActivePolicy>,
+    /// Hits and misses of the policies used before the active one, kept so policies
+    /// can be compared.
+    hit_stats: Mutex<HashMap<ReplacementPolicyKind, HitStats>>,
+    counters: Counters,
+    /// How long a pin waits for a buffer to become available, in nanoseconds.
+    pin_timeout: AtomicU64,
+    /// How many pages to prefetch after a sequential pin, 0 disables read-ahead.
+    read_ahead: AtomicUsize,
+    /// The block pinned last in each file, used to detect sequential scans.
+    last_pins: Sharded<PathBuf, u64>,
+    /// The frame [`BufferManager::flush_unpinned`] looks at next.
+    write_hand: AtomicUsize,
+}
+
+/// The frames of the pool and the state their pins update.
+///
+/// Shared with every [`PinnedBuffer`], so a pin can release itself and record
+/// changes to its page without holding on to the buffer manager.
+pub(crate) struct Pool {
+    frames: Vec<Frame>,
+    /// Number of frames that are not pinned.
+    available: AtomicUsize,
+    /// Number of pins waiting for a buffer to become available.
+    waiters: AtomicUsize,
+    wait_lock: Mutex<()>,
+    /// Signalled whenever a buffer becomes unpinned while pins are waiting.
+    unpinned: Condvar,
+    /// The pages changed since they were last written. Updated through
+    /// [`PageGuard::set_modified`](crate::buffer::pinned::PageGuard::set_modified)
+    /// while holding the buffer's lock, so it always agrees with the buffers.
+    dirty_pages: Sharded<PageId, DirtyPage>,
+}
+
+impl Pool {
+    /// Unpins the buffer in `frame`, waking up pins waiting for a buffer once
+    /// nobody uses it anymore.
+    ///
+    /// Called when a [`PinnedBuffer`] is dropped.
+    pub(crate) fn unpin(&self, frame: usize) {
+        trace!("Unpinning frame {}", frame);
+        if self.frames[frame].pins.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.available.fetch_add(1, Ordering::SeqCst);
+            // A waiter registers before it checks for available frames, so either it
+            // sees this frame or it is told about it
+            if self.waiters.load(Ordering::SeqCst) > 0 {
+                let _waiting = self.wait_lock.lock();
+                self.unpinned.notify_all();
+            }
+        }
+    }
+
+    /// Adds a change of the page held by `frame` to the dirty page table.
+    ///
+    /// Must be called while holding the lock of the buffer in `frame`.
+    pub(crate) fn record_modification(
+        &self,
+        page_id: &PageId,
+        frame: usize,
+        txnum: i32,
+        lsn: Option<Lsn>,
+    ) -> anyhow::Result<()> {
+        let mut dirty_pages = self.dirty_pages.lock(page_id)?;
+        let page = dirty_pages
+            .entry(page_id.clone())
+            .or_insert_with(|| DirtyPage {
+                frame,
+                first_lsn: None,
+                txns: BTreeSet::new(),
+            });
+        if page.first_lsn.is_none() {
+            page.first_lsn = lsn;
+        }
+        page.txns.insert(txnum);
+        Ok(())
+    }
+}
+
+/// A buffer of the pool and the number of pins held on it.
+struct Frame {
+    buffer: Arc<Mutex<Buffer>>,
+    pins: AtomicUsize,
+}
+
+/// The replacement policy in use and how it fared so far.
+struct ActivePolicy {
+    kind: ReplacementPolicyKind,
+    policy: Box<dyn ReplacementPolicy>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ActivePolicy {
+    fn new(kind: ReplacementPolicyKind, frames: usize) -> Self {
+        Self {
+            kind,
+            policy: kind.build(frames),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn hit_stats(&self) -> HitStats {
+        HitStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The counters behind [`BufferStats`].
+#[derive(Default)]
+struct Counters {
+    pins: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    write_backs: AtomicU64,
+    background_writes: AtomicU64,
+    waits: AtomicU64,
+    timeouts: AtomicU64,
+    pin_wait_nanos: AtomicU64,
+}
+
+impl Counters {
+    fn snapshot(&self) -> BufferStats {
+        let get = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        BufferStats {
+            pins: get(&self.pins),
+            hits: get(&self.hits),
+            misses: get(&self.misses),
+            evictions: get(&self.evictions),
+            write_backs: get(&self.write_backs),
+            background_writes: get(&self.background_writes),
+            waits: get(&self.waits),
+            timeouts: get(&self.timeouts),
+            pin_wait_time: Duration::from_nanos(get(&self.pin_wait_nanos)),
+        }
+    }
+
+    fn reset(&self) {
+        for counter in [
+            &self.pins,
+            &self.hits,
+            &self.misses,
+            &self.evictions,
+            &self.write_backs,
+            &self.background_writes,
+            &self.waits,
+            &self.timeouts,
+            &self.pin_wait_nanos,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn add_wait_time(&self, waited: Duration) {
+        self.pin_wait_nanos
+            .fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Increments one of the [`Counters`].
+fn count(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The outcome of an attempt to read a page into the pool.
+enum Load {
+    /// The page was read into the frame, which is pinned once.
+    Pinned(usize),
+    /// Another thread cached the page in the meantime.
+    Cached,
+    /// Every frame is pinned.
+    NoFrame,
+}
+
+/// A frame taken over to hold another page, see [`BufferManager::claim_frame`].
+struct Claim<'a> {
+    frame: usize,
+    buffer: MutexGuard<'a, Buffer>,
+    /// Whether the frame held a page before.
+    evicted: bool,
 }
 
 impl BufferManager {
@@ -37,111 +312,1158 @@ impl BufferManager {
             "Start to initialize buffer manager with {} buffers",
             num_buffers
         );
-        let buffers = (0..num_buffers)
-            .map(|_| {
-                Arc::new(Mutex::new(Buffer::new(
+        let frames = (0..num_buffers)
+            .map(|_| Frame {
+                buffer: Arc::new(Mutex::new(Buffer::new(
                     file_manager.clone(),
                     log_manager.clone(),
-                )))
+                ))),
+                pins: AtomicUsize::new(0),
             })
             .collect();
 
         debug!("Buffer manager initialization done");
         Self {
             file_manager,
-            log_manager,
-            pool: buffers,
-            available: num_buffers,
-            max_time: 1000, // Default max time to wait for a buffer (in milliseconds)
+            pool: Arc::new(Pool {
+                frames,
+                available: AtomicUsize::new(num_buffers),
+                waiters: AtomicUsize::new(0),
+                wait_lock: Mutex::new(()),
+                unpinned: Condvar::new(),
+                dirty_pages: Sharded::new(SHARDS),
+            }),
+            page_table: Sharded::new(SHARDS),
+            policy: RwLock::new(ActivePolicy::new(
+                ReplacementPolicyKind::default(),
+                num_buffers,
+            )),
+            hit_stats: Mutex::new(HashMap::new()),
+            counters: Counters::default(),
+            pin_timeout: AtomicU64::new(DEFAULT_PIN_TIMEOUT.as_nanos() as u64),
+            read_ahead: AtomicUsize::new(DEFAULT_READ_AHEAD),
+            last_pins: Sharded::new(SHARDS),
+            write_hand: AtomicUsize::new(0),
         }
     }
 
-    pub fn pin(&mut self, page_id: &PageId) -> anyhow::Result<Arc<Mutex<Buffer>>, BufferError> {
+    /// Pins the page, reading it into an unpinned buffer if it is not cached yet.
+    ///
+    /// If every buffer is pinned, waits until another thread unpins one and fails
+    /// with [`BufferError::Timeout`] if none does within the pin timeout, see
+    /// [`set_pin_timeout`](Self::set_pin_timeout). I/O errors while reading the
+    /// page are returned right away.
+    ///
+    /// Pinning the page right after the previous one of the same file counts as a
+    /// sequential scan: the following pages are then read ahead into unpinned buffers
+    /// with a single batched read, see [`set_read_ahead`](Self::set_read_ahead).
+    ///
+    /// The page stays pinned until the returned [`PinnedBuffer`] is dropped.
+    pub fn pin(&self, page_id: &PageId) -> anyhow::Result<PinnedBuffer> {
         debug!("Trying to pin page: {}", page_id);
-        let deadline = Instant::now() + Duration::from_millis(self.max_time);
+        let deadline =
+            Instant::now() + Duration::from_nanos(self.pin_timeout.load(Ordering::Relaxed));
+        let mut waiting_since: Option<Instant> = None;
 
-        while Instant::now() < deadline {
-            if let Ok(buffer) = self.try_to_pin(page_id.clone()) {
+        loop {
+            if let Some(frame) = self.try_to_pin(page_id)? {
                 trace!("Pinned page: {}", page_id);
-                return Ok(buffer);
+                count(&self.counters.pins);
+                if let Some(since) = waiting_since {
+                    self.counters.add_wait_time(since.elapsed());
+                }
+                self.read_ahead_after(page_id);
+                return Ok(PinnedBuffer::new(
+                    self.pool.clone(),
+                    self.pool.frames[frame].buffer.clone(),
+                    frame,
+                    page_id.clone(),
+                ));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                count(&self.counters.timeouts);
+                if let Some(since) = waiting_since {
+                    self.counters.add_wait_time(now - since);
+                }
+                return Err(BufferError::Timeout {
+                    page_id: page_id.clone(),
+                    pinned: self.pool.frames.len() - self.available(),
+                    frames: self.pool.frames.len(),
+                }
+                .into());
+            }
+            trace!("All buffers pinned, waiting to pin page: {}", page_id);
+            if waiting_since.is_none() {
+                count(&self.counters.waits);
+                waiting_since = Some(now);
             }
-            std::thread::sleep(Duration::from_millis(10));
+            self.wait_for_unpin(deadline - now)?;
         }
+    }
 
-        Err(BufferError::Timeout("Could not pin buffer: timeout".into()))
+    pub(crate) fn file_manager(&self) -> &FileManager {
+        &self.file_manager
     }
 
-    pub fn unpin(&mut self, buffer: Arc<Mutex<Buffer>>) -> anyhow::Result<()> {
-        let mut buffer = buffer.lock().unwrap();
-        debug!("Trying to unpin page: {:?}", buffer.page_id());
+    /// Sets how many pages are prefetched during sequential scans, 0 disables read-ahead.
+    pub fn set_read_ahead(&self, pages: usize) {
+        self.read_ahead.store(pages, Ordering::Relaxed);
+    }
 
-        buffer.unpin();
-        if !buffer.is_pinned() {
-            self.available += 1;
-        }
+    /// Sets how long a pin waits for a buffer before failing with [`BufferError::Timeout`].
+    ///
+    /// Defaults to [`DEFAULT_PIN_TIMEOUT`].
+    pub fn set_pin_timeout(&self, timeout: Duration) {
+        let nanos = u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX);
+        self.pin_timeout.store(nanos, Ordering::Relaxed);
+    }
 
-        Ok(())
+    /// Switches to another replacement policy, which starts without any history.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::buffer::policy::ReplacementPolicyKind;
+    /// # use rimple::db::SimpleDB;
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let db = SimpleDB::new(tmp.path(), 400).unwrap();
+    /// db.buffer_manager().set_replacement_policy(ReplacementPolicyKind::Lru);
+    /// assert_eq!(db.buffer_manager().replacement_policy(), ReplacementPolicyKind::Lru);
+    /// ```
+    pub fn set_replacement_policy(&self, kind: ReplacementPolicyKind) {
+        debug!("Switching buffer replacement policy to {}", kind);
+        let mut policy = self.policy.write().unwrap();
+        let mut hit_stats = self.hit_stats.lock().unwrap();
+        let previous = hit_stats.entry(policy.kind).or_default();
+        let stats = policy.hit_stats();
+        previous.hits += stats.hits;
+        previous.misses += stats.misses;
+        *policy = ActivePolicy::new(kind, self.pool.frames.len());
+    }
+
+    /// Returns the replacement policy in use.
+    pub fn replacement_policy(&self) -> ReplacementPolicyKind {
+        self.policy.read().unwrap().kind
+    }
+
+    /// Returns the hits and misses of every policy used so far.
+    pub fn hit_stats(&self) -> HashMap<ReplacementPolicyKind, HitStats> {
+        let policy = self.policy.read().unwrap();
+        let mut hit_stats = self.hit_stats.lock().unwrap().clone();
+        let active = hit_stats.entry(policy.kind).or_default();
+        let stats = policy.hit_stats();
+        active.hits += stats.hits;
+        active.misses += stats.misses;
+        hit_stats
     }
 
     pub fn available(&self) -> usize {
-        self.available
+        self.pool.available.load(Ordering::SeqCst)
+    }
+
+    /// Returns a snapshot of the counters collected since the pool was created or
+    /// the counters were last reset.
+    pub fn stats(&self) -> BufferStats {
+        self.counters.snapshot()
+    }
+
+    /// Sets all counters reported by [`stats`](Self::stats) back to zero.
+    pub fn reset_stats(&self) {
+        self.counters.reset();
+    }
+
+    /// Returns the state of every buffer in the pool, in pool order.
+    ///
+    /// The buffers are looked at one after the other while pins go on, so the
+    /// snapshot need not show the pool at a single point in time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::db::SimpleDB;
+    /// # use rimple::file::PageId;
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let db = SimpleDB::new(tmp.path(), 400).unwrap();
+    /// let page_id = PageId::new(tmp.path().join("data"), 0);
+    /// db.file_manager().append_page(page_id.path()).unwrap();
+    ///
+    /// let _pinned = db.buffer_manager().pin(&page_id).unwrap();
+    /// let frames = db.buffer_manager().frames().unwrap();
+    /// let frame = frames.iter().find(|f| f.page_id.as_ref() == Some(&page_id)).unwrap();
+    /// assert_eq!(frame.pins, 1);
+    /// assert_eq!(frame.modifying_txn, None);
+    /// ```
+    pub fn frames(&self) -> anyhow::Result<Vec<FrameInfo>> {
+        (0..self.pool.frames.len())
+            .map(|frame| {
+                let buffer = self.lock_buffer(frame)?;
+                Ok(FrameInfo {
+                    frame,
+                    page_id: buffer.page_id().cloned(),
+                    pins: self.pool.frames[frame].pins.load(Ordering::SeqCst),
+                    modifying_txn: buffer.is_modified().then(|| buffer.modifying_txn()),
+                    lsn: buffer.lsn(),
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a snapshot of the dirty page table.
+    ///
+    /// Every page in it has changes that only exist in the buffer pool, so a
+    /// checkpoint can tell from it which pages still need to be written and how far
+    /// back in the log their changes go.
+    pub fn dirty_pages(&self) -> HashMap<PageId, DirtyPage> {
+        let mut dirty_pages = HashMap::new();
+        for shard in self.pool.dirty_pages.shards() {
+            dirty_pages.extend(
+                shard
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|(page_id, page)| (page_id.clone(), page.clone())),
+            );
+        }
+        dirty_pages
+    }
+
+    /// Writes back every page changed by transaction `txn` and syncs them if
+    /// the durability mode asks for it.
+    ///
+    /// Only the buffers listed for `txn` in the dirty page table are touched.
+    pub fn flush_all(&self, txn: i32) -> anyhow::Result<()> {
+        self.flush_dirty_pages(|page| page.txns.contains(&txn))
     }
 
-    pub fn flush_all(&mut self, txn: i32) -> anyhow::Result<()> {
-        for buffer in &mut self.pool {
-            let mut buffer = buffer
-                .lock()
-                .map_err(|_| io::Error::other("Failed to acquire buffer lock"))?;
-            if buffer.modifying_txn() == txn {
+    /// Writes back every modified page, whichever transaction changed it, and
+    /// syncs them if the durability mode asks for it.
+    ///
+    /// Used by checkpoints, after which no change may be left only in memory.
+    pub fn flush_every_dirty_page(&self) -> anyhow::Result<()> {
+        self.flush_dirty_pages(|_| true)
+    }
+
+    fn flush_dirty_pages(&self, selected: impl Fn(&DirtyPage) -> bool) -> anyhow::Result<()> {
+        let mut pages = vec![];
+        for shard in self.pool.dirty_pages.shards() {
+            let shard = lock(shard, "dirty page table")?;
+            pages.extend(
+                shard
+                    .iter()
+                    .filter(|(_, page)| selected(page))
+                    .map(|(page_id, page)| (page_id.clone(), page.frame)),
+            );
+        }
+        for (page_id, frame) in pages {
+            let mut buffer = self.lock_buffer(frame)?;
+            // Otherwise the page was evicted, and so written back, in the meantime
+            if buffer.page_id() == Some(&page_id) && buffer.is_modified() {
                 buffer.flush()?;
+                count(&self.counters.write_backs);
+                self.written_back(&buffer, Some(&page_id))?;
             }
         }
 
-        Ok(())
+        self.file_manager.sync_on_flush()
     }
 
-    fn try_to_pin(&mut self, page_id: PageId) -> anyhow::Result<Arc<Mutex<Buffer>>> {
-        if let Some(buffer) = self.find_existing_buffer(&page_id) {
-            let mut locked_buffer = buffer
-                .lock()
-                .map_err(|_| io::Error::other("Failed to acquire buffer lock"))?;
-            if !locked_buffer.is_pinned() {
-                self.available -= 1;
-            }
-            locked_buffer.pin();
-            Ok(buffer.clone())
-        } else if let Some(buffer) = self.choose_unpinned_buffer() {
-            let mut locked_buffer = buffer
-                .lock()
-                .map_err(|_| io::Error::other("Failed to acquire buffer lock"))?;
-            locked_buffer.assign_to_page(&page_id)?;
-            self.available -= 1;
-            locked_buffer.pin();
-            Ok(buffer.clone())
-        } else {
-            bail!("No available buffers to pin")
+    /// Writes back up to `max_pages` modified buffers that nobody has pinned and
+    /// returns how many were written.
+    ///
+    /// Buffers are visited in pool order, continuing where the previous call
+    /// stopped, so that repeated calls reach every buffer. As with any write-back,
+    /// the log is flushed up to the page's latest change first. Buffers that are
+    /// locked by someone else are skipped, so pins are not held up.
+    ///
+    /// Meant to be called periodically by the
+    /// [`BackgroundWriter`](crate::buffer::writer::BackgroundWriter), so that
+    /// evictions find clean buffers and need not wait for a write.
+    pub fn flush_unpinned(&self, max_pages: usize) -> anyhow::Result<usize> {
+        let mut written = 0;
+        for _ in 0..self.pool.frames.len() {
+            if written == max_pages {
+                break;
+            }
+            let frame = self.write_hand.fetch_add(1, Ordering::Relaxed) % self.pool.frames.len();
+            if self.pool.frames[frame].pins.load(Ordering::SeqCst) > 0 {
+                continue;
+            }
+            let Ok(mut buffer) = self.pool.frames[frame].buffer.try_lock() else {
+                continue;
+            };
+            if buffer.is_modified() {
+                buffer.flush()?;
+                count(&self.counters.write_backs);
+                count(&self.counters.background_writes);
+                self.written_back(&buffer, buffer.page_id())?;
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Reads a page straight from storage while no buffer is written back to it.
+    ///
+    /// Used to copy pages that may be written by the buffer pool at the same time,
+    /// which could otherwise be read halfway through a write.
+    pub(crate) fn read_from_storage(
+        &self,
+        page_id: &PageId,
+        page: &mut Page,
+    ) -> anyhow::Result<()> {
+        loop {
+            let page_table = self.page_table.lock(page_id)?;
+            let Some(&frame) = page_table.get(page_id) else {
+                // Holding the shard keeps the page from being loaded, and so written
+                return self.file_manager.read(page_id, page);
+            };
+            drop(page_table);
+            // The page is only written by the buffer holding it, under its lock
+            let buffer = self.lock_buffer(frame)?;
+            if buffer.page_id() == Some(page_id) {
+                return self.file_manager.read(page_id, page);
+            }
         }
     }
 
-    fn find_existing_buffer(&self, page_id: &PageId) -> Option<Arc<Mutex<Buffer>>> {
-        for buffer in &self.pool {
-            if let Ok(locked_buffer) = buffer.lock()
-                && locked_buffer.page_id() == Some(page_id)
+    /// Pins the page if a buffer is available, returning its frame, or `None` otherwise.
+    fn try_to_pin(&self, page_id: &PageId) -> anyhow::Result<Option<usize>> {
+        loop {
+            if let Some(frame) = self.pin_cached(page_id)? {
+                count(&self.counters.hits);
+                let policy = self.policy.read().unwrap();
+                policy.policy.record_access(frame);
+                count(&policy.hits);
+                return Ok(Some(frame));
+            }
+            match self.load(page_id)? {
+                Load::Pinned(frame) => {
+                    count(&self.counters.misses);
+                    let policy = self.policy.read().unwrap();
+                    policy.policy.record_load(frame);
+                    count(&policy.misses);
+                    return Ok(Some(frame));
+                }
+                Load::Cached => continue,
+                Load::NoFrame => return Ok(None),
+            }
+        }
+    }
+
+    /// Pins the page if it is cached and returns its frame.
+    fn pin_cached(&self, page_id: &PageId) -> anyhow::Result<Option<usize>> {
+        loop {
+            let frame = {
+                let page_table = self.page_table.lock(page_id)?;
+                let Some(&frame) = page_table.get(page_id) else {
+                    return Ok(None);
+                };
+                // Under the shard lock, so the frame cannot be claimed for another page
+                self.acquire(frame);
+                frame
+            };
+            // Waits for the page to be read if it is still loading
+            let loaded = self.lock_buffer(frame)?.page_id() == Some(page_id);
+            if loaded {
+                return Ok(Some(frame));
+            }
+            // The page failed to load and left the page table again
+            self.pool.unpin(frame);
+        }
+    }
+
+    /// Reads the page into a frame that is not pinned, and pins it.
+    fn load(&self, page_id: &PageId) -> anyhow::Result<Load> {
+        let Some(mut claim) = self.claim_frame(&[])? else {
+            return Ok(Load::NoFrame);
+        };
+        {
+            let mut page_table = self.page_table.lock(page_id)?;
+            if page_table.contains_key(page_id) {
+                drop(page_table);
+                drop(claim.buffer);
+                self.pool.unpin(claim.frame);
+                return Ok(Load::Cached);
+            }
+            page_table.insert(page_id.clone(), claim.frame);
+        }
+        if let Err(e) = claim.buffer.assign_to_page(page_id) {
+            self.page_table.lock(page_id)?.remove(page_id);
+            drop(claim.buffer);
+            self.pool.unpin(claim.frame);
+            return Err(e);
+        }
+        if claim.evicted {
+            count(&self.counters.evictions);
+        }
+        Ok(Load::Pinned(claim.frame))
+    }
+
+    /// Takes over a frame that nobody has pinned and that is not in `taken`, and
+    /// detaches it from its page.
+    ///
+    /// The frame comes back pinned once and with its buffer locked. Its old page is
+    /// written back if needed, and removed from the page table.
+    fn claim_frame(&self, taken: &[usize]) -> anyhow::Result<Option<Claim<'_>>> {
+        let mut tried = taken.to_vec();
+        loop {
+            // Released before writing the victim back, so switching policies never
+            // waits for I/O
+            let victim = self.policy.read().unwrap().policy.victim(&|frame| {
+                !tried.contains(&frame) && self.pool.frames[frame].pins.load(Ordering::SeqCst) == 0
+            });
+            let Some(frame) = victim else {
+                return Ok(None);
+            };
+            tried.push(frame);
+            // A locked buffer is in use, e.g. by a pin checking it or a write-back
+            let Ok(mut buffer) = self.pool.frames[frame].buffer.try_lock() else {
+                continue;
+            };
+            // Written before the page leaves the page table, so it can always be
+            // found in the pool or in storage
+            if buffer.is_modified() {
+                buffer.flush()?;
+                count(&self.counters.write_backs);
+                self.written_back(&buffer, buffer.page_id())?;
+            }
+            let pins = &self.pool.frames[frame].pins;
+            let claimed = match buffer.page_id() {
+                Some(old_page_id) => {
+                    let mut page_table = self.page_table.lock(old_page_id)?;
+                    let claimed = pins
+                        .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                        .is_ok();
+                    if claimed {
+                        page_table.remove(old_page_id);
+                    }
+                    claimed
+                }
+                None => pins
+                    .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                    .is_ok(),
+            };
+            if !claimed {
+                continue;
+            }
+            self.pool.available.fetch_sub(1, Ordering::SeqCst);
+            let evicted = buffer.page_id().is_some();
+            buffer.unassign();
+            return Ok(Some(Claim {
+                frame,
+                buffer,
+                evicted,
+            }));
+        }
+    }
+
+    /// Adds a pin to `frame`.
+    fn acquire(&self, frame: usize) {
+        if self.pool.frames[frame].pins.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.pool.available.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Waits until a frame is unpinned, for at most `timeout`.
+    ///
+    /// Returns right away if a frame is unpinned already but was in use by someone
+    /// else, e.g. by a pin that is about to claim it.
+    fn wait_for_unpin(&self, timeout: Duration) -> io::Result<()> {
+        self.pool.waiters.fetch_add(1, Ordering::SeqCst);
+        let result = lock(&self.pool.wait_lock, "buffer manager").and_then(|waiting| {
+            if self.available() > 0 {
+                drop(waiting);
+                thread::yield_now();
+                return Ok(());
+            }
+            self.pool
+                .unpinned
+                .wait_timeout(waiting, timeout)
+                .map(|_| ())
+                .map_err(|_| io::Error::other("Failed to acquire buffer manager lock"))
+        });
+        self.pool.waiters.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+
+    /// Prefetches the pages behind `page_id` if pinning it continues a sequential scan.
+    ///
+    /// Read-ahead is only an optimization, so failures are logged and otherwise ignored.
+    fn read_ahead_after(&self, page_id: &PageId) {
+        let read_ahead = self.read_ahead.load(Ordering::Relaxed);
+        // Every pin of a file goes through the same shard, so skip it when unused
+        if read_ahead == 0 {
+            return;
+        }
+        let previous = match self.last_pins.lock(page_id.path()) {
+            Ok(mut last_pins) => last_pins.insert(page_id.path().to_path_buf(), page_id.block_no()),
+            Err(_) => return,
+        };
+        let sequential = previous.is_some_and(|block_no| block_no + 1 == page_id.block_no());
+        if !sequential {
+            return;
+        }
+        if let Err(e) = self.prefetch(page_id.path(), page_id.block_no() + 1, read_ahead) {
+            debug!("Read-ahead after page {} failed: {}", page_id, e);
+        }
+    }
+
+    /// Reads up to `read_ahead` pages starting at `first_block` into unpinned buffers.
+    ///
+    /// Stops at the end of the file and at the first page that is cached already.
+    fn prefetch(&self, path: &Path, first_block: u64, read_ahead: usize) -> anyhow::Result<()> {
+        let mut claims = vec![];
+        let result = self
+            .claim_frames_for(path, first_block, read_ahead, &mut claims)
+            .and_then(|()| self.read_into(path, first_block, &mut claims));
+
+        for (page_id, claim) in claims {
+            if claim.buffer.page_id().is_none()
+                && let Ok(mut page_table) = self.page_table.lock(&page_id)
             {
-                return Some(buffer.clone());
+                page_table.remove(&page_id);
+            }
+            drop(claim.buffer);
+            self.pool.unpin(claim.frame);
+        }
+        result
+    }
+
+    /// Claims a frame for each page to prefetch and enters the pages into the page
+    /// table, so that pins of them wait until they are read.
+    fn claim_frames_for<'a>(
+        &'a self,
+        path: &Path,
+        first_block: u64,
+        read_ahead: usize,
+        claims: &mut Vec<(PageId, Claim<'a>)>,
+    ) -> anyhow::Result<()> {
+        let size = self.file_manager.size(path)?;
+        for block_no in first_block..size.min(first_block + read_ahead as u64) {
+            let page_id = PageId::new(path.to_path_buf(), block_no);
+            if self.page_table.lock(&page_id)?.contains_key(&page_id) {
+                break;
+            }
+            let taken: Vec<usize> = claims.iter().map(|(_, claim)| claim.frame).collect();
+            let Some(claim) = self.claim_frame(&taken)? else {
+                break;
+            };
+            let mut page_table = self.page_table.lock(&page_id)?;
+            if page_table.contains_key(&page_id) {
+                drop(page_table);
+                drop(claim.buffer);
+                self.pool.unpin(claim.frame);
+                break;
+            }
+            page_table.insert(page_id.clone(), claim.frame);
+            drop(page_table);
+            claims.push((page_id, claim));
+        }
+        Ok(())
+    }
+
+    /// Reads the claimed pages with a single batched read.
+    fn read_into(
+        &self,
+        path: &Path,
+        first_block: u64,
+        claims: &mut [(PageId, Claim<'_>)],
+    ) -> anyhow::Result<()> {
+        if claims.is_empty() {
+            return Ok(());
+        }
+        let mut pages: Vec<_> = claims
+            .iter()
+            .map(|_| Page::with_size(self.file_manager.page_size()))
+            .collect();
+        self.file_manager.read_many(path, first_block, &mut pages)?;
+        trace!("Read ahead {} pages of {:?}", pages.len(), path);
+
+        let policy = self.policy.read().unwrap();
+        for ((page_id, claim), page) in claims.iter_mut().zip(pages) {
+            claim.buffer.assign_prefetched(page_id, page)?;
+            policy.policy.record_load(claim.frame);
+            if claim.evicted {
+                count(&self.counters.evictions);
             }
         }
-        None
+        Ok(())
     }
 
-    fn choose_unpinned_buffer(&mut self) -> Option<Arc<Mutex<Buffer>>> {
-        for buffer in &self.pool {
-            if let Ok(locked_buffer) = buffer.lock()
-                && !locked_buffer.is_pinned()
+    /// Removes `page_id` from the dirty page table if `buffer` was written back.
+    ///
+    /// Called with the lock of `buffer` held after anything that may have flushed it.
+    fn written_back(&self, buffer: &Buffer, page_id: Option<&PageId>) -> anyhow::Result<()> {
+        if let Some(page_id) = page_id
+            && !buffer.is_modified()
+        {
+            self.pool.dirty_pages.lock(page_id)?.remove(page_id);
+        }
+        Ok(())
+    }
+
+    fn lock_buffer(&self, frame: usize) -> io::Result<MutexGuard<'_, Buffer>> {
+        lock(&self.pool.frames[frame].buffer, "buffer")
+    }
+}
+
+impl PageCache for BufferManager {
+    /// Drops every unpinned page of `path` from the pool without writing it back,
+    /// together with its entries in the page table and the dirty page table.
+    ///
+    /// Pages that are still pinned stay in the pool until they are evicted, but
+    /// their changes are dropped instead of written back, so the file is not
+    /// created again.
+    fn discard_file(&self, path: &Path) -> anyhow::Result<()> {
+        for frame in 0..self.pool.frames.len() {
+            let mut buffer = self.lock_buffer(frame)?;
+            let Some(page_id) = buffer.page_id().filter(|p| p.path() == path).cloned() else {
+                continue;
+            };
+            let mut page_table = self.page_table.lock(&page_id)?;
+            // Claimed like a frame to evict, so no pin can find the page anymore
+            if self.pool.frames[frame]
+                .pins
+                .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+                .is_err()
             {
-                return Some(buffer.clone());
+                drop(page_table);
+                debug!(
+                    "Page {} of deleted file {:?} is still pinned",
+                    page_id, path
+                );
+                self.pool.dirty_pages.lock(&page_id)?.remove(&page_id);
+                buffer.discard_changes();
+                continue;
+            }
+            self.pool.available.fetch_sub(1, Ordering::SeqCst);
+            page_table.remove(&page_id);
+            drop(page_table);
+            self.pool.dirty_pages.lock(&page_id)?.remove(&page_id);
+            buffer.discard();
+            drop(buffer);
+            self.pool.unpin(frame);
+        }
+        Ok(())
+    }
+}
+
+fn lock<'a, T>(mutex: &'a Mutex<T>, what: &str) -> io::Result<MutexGuard<'a, T>> {
+    mutex
+        .lock()
+        .map_err(|_| io::Error::other(format!("Failed to acquire {} lock", what)))
+}
+
+/// A hash map split into independently locked shards, so that threads working on
+/// different keys rarely wait for each other.
+struct Sharded<K, V> {
+    shards: Vec<Mutex<HashMap<K, V>>>,
+}
+
+impl<K: Hash + Eq, V> Sharded<K, V> {
+    fn new(shards: usize) -> Self {
+        Self {
+            shards: (0..shards).map(|_| Mutex::new(HashMap::new())).collect(),
+        }
+    }
+
+    /// Locks the shard holding `key`.
+    fn lock<Q>(&self, key: &Q) -> io::Result<MutexGuard<'_, HashMap<K, V>>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let shard = hasher.finish() as usize % self.shards.len();
+        lock(&self.shards[shard], "page table")
+    }
+
+    fn shards(&self) -> impl Iterator<Item = &Mutex<HashMap<K, V>>> {
+        self.shards.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicBool;
+
+    use crate::file::{
+        FileOptions,
+        backend::{MemoryBackend, StorageBackend},
+        manager::test::memory_file_manager,
+    };
+
+    use super::*;
+
+    fn buffer_manager_with_pages(pages: u64) -> (Arc<BufferManager>, Arc<FileManager>) {
+        let (fm, _) = memory_file_manager(64);
+        let fm = Arc::new(fm);
+        let data: Vec<_> = (0..pages)
+            .map(|i| Page::with_bytes(&[i as u8; 64]))
+            .collect();
+        fm.write_many(Path::new("data"), 0, &data).unwrap();
+        let lm = Arc::new(Mutex::new(LogManager::new(fm.clone(), "log").unwrap()));
+        fm.reset_io_stats();
+        (Arc::new(BufferManager::new(fm.clone(), lm, 8)), fm)
+    }
+
+    fn pin_and_unpin(bm: &BufferManager, block_no: u64) -> u8 {
+        let buffer = bm
+            .pin(&PageId::new(PathBuf::from("data"), block_no))
+            .unwrap();
+        buffer.lock().unwrap().content()[0]
+    }
+
+    #[test]
+    fn sequential_pins_read_the_following_pages_ahead() {
+        // setup
+        let (bm, fm) = buffer_manager_with_pages(6);
+
+        // test
+        pin_and_unpin(&bm, 0);
+        pin_and_unpin(&bm, 1);
+        let reads_after_scan_start = fm.io_stats().file(Path::new("data")).reads;
+        let contents: Vec<_> = (2..6).map(|i| pin_and_unpin(&bm, i)).collect();
+
+        // verify
+        assert_eq!(reads_after_scan_start, 6);
+        assert_eq!(contents, vec![2, 3, 4, 5]);
+        assert_eq!(fm.io_stats().file(Path::new("data")).reads, 6);
+    }
+
+    #[test]
+    fn random_pins_do_not_read_ahead() {
+        // setup
+        let (bm, fm) = buffer_manager_with_pages(6);
+
+        // test
+        pin_and_unpin(&bm, 3);
+        pin_and_unpin(&bm, 0);
+        pin_and_unpin(&bm, 5);
+
+        // verify
+        assert_eq!(fm.io_stats().file(Path::new("data")).reads, 3);
+    }
+
+    /// Pins page 0 between pins of a run of other pages and returns the reads of page 0.
+    fn reads_of_hot_page(kind: ReplacementPolicyKind) -> (u64, HitStats) {
+        let (bm, fm) = buffer_manager_with_pages(20);
+        bm.set_read_ahead(0);
+        bm.set_replacement_policy(kind);
+        for block_no in 1..20 {
+            pin_and_unpin(&bm, 0);
+            pin_and_unpin(&bm, block_no);
+        }
+        let reads = fm.io_stats().file(Path::new("data")).reads;
+        (reads, bm.hit_stats()[&kind])
+    }
+
+    #[test]
+    fn lru_keeps_a_hot_page_that_naive_evicts() {
+        // test
+        let (naive_reads, naive_stats) = reads_of_hot_page(ReplacementPolicyKind::Naive);
+        let (lru_reads, lru_stats) = reads_of_hot_page(ReplacementPolicyKind::Lru);
+
+        // verify
+        assert_eq!(lru_reads, 20);
+        assert!(naive_reads > lru_reads);
+        assert_eq!(
+            lru_stats,
+            HitStats {
+                hits: 18,
+                misses: 20
+            }
+        );
+        assert!(lru_stats.hit_ratio() > naive_stats.hit_ratio());
+    }
+
+    #[test]
+    fn hit_stats_are_kept_per_policy() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(4);
+        bm.set_read_ahead(0);
+
+        // test
+        pin_and_unpin(&bm, 0);
+        pin_and_unpin(&bm, 0);
+        bm.set_replacement_policy(ReplacementPolicyKind::Clock);
+        pin_and_unpin(&bm, 1);
+
+        // verify
+        let stats = bm.hit_stats();
+        assert_eq!(
+            stats[&ReplacementPolicyKind::Naive],
+            HitStats { hits: 1, misses: 1 }
+        );
+        assert_eq!(
+            stats[&ReplacementPolicyKind::Clock],
+            HitStats { hits: 0, misses: 1 }
+        );
+        assert_eq!(stats[&ReplacementPolicyKind::Naive].hit_ratio(), 0.5);
+    }
+
+    #[test]
+    fn stats_count_pins_evictions_and_write_backs() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(10);
+        bm.set_read_ahead(0);
+        bm.set_replacement_policy(ReplacementPolicyKind::Lru);
+        {
+            let buffer = bm.pin(&PageId::new(PathBuf::from("data"), 0)).unwrap();
+            buffer.lock().unwrap().set_modified(1, None).unwrap();
+        }
+
+        // test
+        for block_no in [0, 1, 2, 3, 4, 5, 6, 7, 8, 9] {
+            pin_and_unpin(&bm, block_no);
+        }
+
+        // verify
+        let stats = bm.stats();
+        assert_eq!(stats.pins, 11);
+        assert_eq!((stats.hits, stats.misses), (1, 10));
+        assert_eq!(stats.evictions, 2);
+        assert_eq!(stats.write_backs, 1);
+        assert_eq!((stats.waits, stats.timeouts), (0, 0));
+        bm.reset_stats();
+        assert_eq!(bm.stats(), BufferStats::default());
+    }
+
+    #[test]
+    fn flush_unpinned_skips_pinned_buffers_and_resumes_where_it_stopped() {
+        // setup
+        let (bm, fm) = buffer_manager_with_pages(4);
+        bm.set_read_ahead(0);
+        let mut pinned: Vec<_> = (0..4)
+            .map(|block_no| {
+                let buffer = bm
+                    .pin(&PageId::new(PathBuf::from("data"), block_no))
+                    .unwrap();
+                buffer.lock().unwrap().set_modified(1, None).unwrap();
+                buffer
+            })
+            .collect();
+        let _still_pinned = pinned.split_off(3);
+        drop(pinned);
+        fm.reset_io_stats();
+
+        // test
+        let first_round = bm.flush_unpinned(2).unwrap();
+        let second_round = bm.flush_unpinned(2).unwrap();
+        let third_round = bm.flush_unpinned(2).unwrap();
+
+        // verify
+        assert_eq!((first_round, second_round, third_round), (2, 1, 0));
+        assert_eq!(fm.io_stats().file(Path::new("data")).writes, 3);
+        let stats = bm.stats();
+        assert_eq!((stats.write_backs, stats.background_writes), (3, 3));
+        let frames = bm.frames().unwrap();
+        assert_eq!(frames[3].modifying_txn, Some(1));
+    }
+
+    /// Pins the page and marks it as modified by `txnum` with `lsn`.
+    /// Takes a while to write the file `slow` once armed.
+    #[derive(Default)]
+    struct SlowWriteBackend {
+        inner: MemoryBackend,
+        armed: AtomicBool,
+    }
+
+    impl StorageBackend for SlowWriteBackend {
+        fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+            self.inner.read(path, offset, buf)
+        }
+
+        fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+            if path == Path::new("slow") && self.armed.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(500));
             }
+            self.inner.write(path, offset, buf)
+        }
+
+        fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+            self.inner.append(path, buf)
+        }
+
+        fn size(&self, path: &Path) -> io::Result<u64> {
+            self.inner.size(path)
+        }
+
+        fn sync(&self, path: &Path) -> io::Result<()> {
+            self.inner.sync(path)
+        }
+
+        fn delete(&self, path: &Path) -> io::Result<()> {
+            self.inner.delete(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.inner.rename(from, to)
+        }
+    }
+
+    #[test]
+    fn switching_policies_does_not_wait_for_a_write_back() {
+        // setup
+        let backend = Arc::new(SlowWriteBackend::default());
+        let fm = Arc::new(FileManager::with_backend(
+            backend.clone(),
+            64,
+            FileOptions::default(),
+        ));
+        let slow = PageId::new(PathBuf::from("slow"), 0);
+        let data = PageId::new(PathBuf::from("data"), 0);
+        fm.write(&slow, &Page::with_size(64)).unwrap();
+        fm.write(&data, &Page::with_size(64)).unwrap();
+        let lm = Arc::new(Mutex::new(LogManager::new(fm.clone(), "log").unwrap()));
+        let bm = BufferManager::new(fm, lm, 1);
+        bm.set_read_ahead(0);
+        let pinned = bm.pin(&slow).unwrap();
+        pinned.lock().unwrap().set_modified(1, None).unwrap();
+        drop(pinned);
+        backend.armed.store(true, Ordering::SeqCst);
+
+        // test
+        let switched_after = thread::scope(|scope| {
+            let evicting = scope.spawn(|| bm.pin(&data).map(drop));
+            thread::sleep(Duration::from_millis(50));
+            let start = Instant::now();
+            bm.set_replacement_policy(ReplacementPolicyKind::Lru);
+            let switched_after = start.elapsed();
+            evicting.join().unwrap().unwrap();
+            switched_after
+        });
+
+        // verify
+        assert!(switched_after < Duration::from_millis(250));
+        assert_eq!(bm.stats().write_backs, 1);
+    }
+
+    fn modify(bm: &BufferManager, block_no: u64, txnum: i32, lsn: Option<Lsn>) {
+        let buffer = bm
+            .pin(&PageId::new(PathBuf::from("data"), block_no))
+            .unwrap();
+        buffer.lock().unwrap().set_modified(txnum, lsn).unwrap();
+    }
+
+    #[test]
+    fn dirty_page_table_keeps_the_first_lsn_and_every_transaction() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(4);
+        bm.set_read_ahead(0);
+        bm.set_replacement_policy(ReplacementPolicyKind::Lru);
+
+        // test
+        modify(&bm, 0, 1, None);
+        modify(&bm, 0, 1, Some(10));
+        modify(&bm, 0, 2, Some(20));
+        modify(&bm, 1, 2, Some(30));
+
+        // verify
+        let dirty_pages = bm.dirty_pages();
+        assert_eq!(dirty_pages.len(), 2);
+        let page = &dirty_pages[&PageId::new(PathBuf::from("data"), 0)];
+        assert_eq!(page.first_lsn, Some(10));
+        assert_eq!(page.txns, BTreeSet::from([1, 2]));
+        assert_eq!(
+            bm.pool.frames[page.frame].buffer.lock().unwrap().page_id(),
+            Some(&PageId::new(PathBuf::from("data"), 0))
+        );
+    }
+
+    #[test]
+    fn flush_all_writes_only_the_pages_of_the_transaction() {
+        // setup
+        let (bm, fm) = buffer_manager_with_pages(4);
+        bm.set_read_ahead(0);
+        bm.set_replacement_policy(ReplacementPolicyKind::Lru);
+        modify(&bm, 0, 1, None);
+        modify(&bm, 1, 2, None);
+        modify(&bm, 2, 1, None);
+        fm.reset_io_stats();
+
+        // test
+        bm.flush_all(1).unwrap();
+
+        // verify
+        assert_eq!(fm.io_stats().file(Path::new("data")).writes, 2);
+        let dirty_pages = bm.dirty_pages();
+        assert_eq!(
+            dirty_pages.keys().collect::<Vec<_>>(),
+            [&PageId::new(PathBuf::from("data"), 1)]
+        );
+        bm.flush_every_dirty_page().unwrap();
+        assert!(bm.dirty_pages().is_empty());
+    }
+
+    #[test]
+    fn evicted_pages_leave_the_dirty_page_table() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(10);
+        bm.set_read_ahead(0);
+        modify(&bm, 0, 1, None);
+
+        // test
+        for block_no in 1..10 {
+            pin_and_unpin(&bm, block_no);
+        }
+
+        // verify
+        assert!(bm.dirty_pages().is_empty());
+        assert_eq!(bm.stats().write_backs, 1);
+    }
+
+    #[test]
+    fn page_table_follows_evictions() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(12);
+        bm.set_read_ahead(0);
+        bm.set_replacement_policy(ReplacementPolicyKind::Lru);
+
+        // test
+        for block_no in [0, 5, 10, 11, 1, 2, 3, 4, 6, 7, 0, 5] {
+            pin_and_unpin(&bm, block_no);
+        }
+
+        // verify
+        let page_table = page_table(&bm);
+        assert_eq!(page_table.len(), 8);
+        for (page_id, &frame) in &page_table {
+            assert_eq!(
+                bm.pool.frames[frame].buffer.lock().unwrap().page_id(),
+                Some(page_id)
+            );
+        }
+        let cached =
+            |block_no| page_table.contains_key(&PageId::new(PathBuf::from("data"), block_no));
+        assert!([0, 5, 7].into_iter().all(cached));
+        assert!(![10, 11].into_iter().any(cached));
+    }
+
+    fn page_table(bm: &BufferManager) -> HashMap<PageId, usize> {
+        bm.page_table
+            .shards()
+            .flat_map(|shard| shard.lock().unwrap().clone())
+            .collect()
+    }
+
+    fn pin_all_frames(bm: &BufferManager) -> Vec<PinnedBuffer> {
+        (0..8)
+            .map(|block_no| {
+                bm.pin(&PageId::new(PathBuf::from("data"), block_no))
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn waiting_pin_is_woken_by_unpin() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(9);
+        bm.set_read_ahead(0);
+        bm.set_pin_timeout(Duration::from_secs(10));
+        let mut pinned = pin_all_frames(&bm);
+
+        // test
+        let (waited, content) = std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                let start = Instant::now();
+                let buffer = bm.pin(&PageId::new(PathBuf::from("data"), 8)).unwrap();
+                let content = buffer.lock().unwrap().content()[0];
+                (start.elapsed(), content)
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            drop(pinned.pop());
+            waiter.join().unwrap()
+        });
+
+        // verify
+        assert_eq!(content, 8);
+        assert!(waited >= Duration::from_millis(50));
+        assert!(waited < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn pin_times_out_with_the_page_and_the_pinned_frames() {
+        // setup
+        let (bm, _) = buffer_manager_with_pages(9);
+        bm.set_read_ahead(0);
+        bm.set_pin_timeout(Duration::from_millis(20));
+        let _pinned = pin_all_frames(&bm);
+
+        // test
+        let Err(err) = bm.pin(&PageId::new(PathBuf::from("data"), 8)) else {
+            panic!("pinning a page into a fully pinned pool succeeded");
+        };
+
+        // verify
+        match err.downcast_ref::<BufferError>() {
+            Some(BufferError::Timeout {
+                page_id,
+                pinned,
+                frames,
+            }) => {
+                assert_eq!(page_id, &PageId::new(PathBuf::from("data"), 8));
+                assert_eq!((*pinned, *frames), (8, 8));
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        let stats = bm.stats();
+        assert_eq!((stats.waits, stats.timeouts), (1, 1));
+        assert!(stats.pin_wait_time >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn concurrent_pins_keep_pages_and_pin_counts_consistent() {
+        // setup
+        const THREADS: u64 = 4;
+        const ROUNDS: i32 = 500;
+        let (bm, fm) = buffer_manager_with_pages(20);
+        let counters: Vec<_> = (0..THREADS).map(|_| Page::with_size(64)).collect();
+        fm.write_many(Path::new("counters"), 0, &counters).unwrap();
+        bm.set_pin_timeout(Duration::from_secs(10));
+        bm.set_replacement_policy(ReplacementPolicyKind::Clock);
+
+        // test
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let bm = &bm;
+                scope.spawn(move || {
+                    let counter = PageId::new(PathBuf::from("counters"), thread);
+                    for round in 0..ROUNDS as u64 {
+                        // Every thread holds up to two pins, so the 8 frames never run out
+                        let block_no = (round * 7 + thread) % 20;
+                        let shared = bm
+                            .pin(&PageId::new(PathBuf::from("data"), block_no))
+                            .unwrap();
+                        assert_eq!(shared.lock().unwrap().content()[0], block_no as u8);
+
+                        let buffer = bm.pin(&counter).unwrap();
+                        let mut page = buffer.lock().unwrap();
+                        let value = page.get_integer(0).unwrap();
+                        page.set_integer(0, value + 1).unwrap();
+                        page.set_modified(thread as i32, None).unwrap();
+                    }
+                });
+            }
+        });
+
+        // verify
+        assert_eq!(bm.available(), 8);
+        for (page_id, frame) in page_table(&bm) {
+            let buffer = bm.pool.frames[frame].buffer.lock().unwrap();
+            assert_eq!(buffer.page_id(), Some(&page_id));
+        }
+        bm.flush_every_dirty_page().unwrap();
+        assert!(bm.dirty_pages().is_empty());
+        for thread in 0..THREADS {
+            let mut page = Page::with_size(64);
+            fm.read(&PageId::new(PathBuf::from("counters"), thread), &mut page)
+                .unwrap();
+            assert_eq!(page.get_integer(0).unwrap(), ROUNDS);
         }
-        None
+        assert_eq!(bm.stats().pins, 2 * THREADS * ROUNDS as u64);
     }
 }
@@ -1,2 +1,7 @@
+#[allow(clippy::module_inception)]
 pub mod buffer;
 pub mod manager;
+pub mod pinned;
+pub mod policy;
+pub mod stats;
+pub mod writer;
@@ -0,0 +1,122 @@
+use std::{
+    io,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    buffer::{buffer::Buffer, manager::Pool},
+    file::{Lsn, Page, PageId},
+};
+
+/// A pin on a buffer, released when dropped.
+///
+/// Returned by [`BufferManager::pin`](crate::buffer::manager::BufferManager::pin).
+/// Pinning the same page twice yields two handles, and the buffer only becomes
+/// available for other pages once both are dropped. Since unpinning happens on
+/// drop, a pin cannot be leaked by an early return or a `?`.
+///
+/// # Examples
+///
+/// ```
+/// # use rimple::db::SimpleDB;
+/// # use rimple::file::PageId;
+/// let tmp = tempfile::tempdir().unwrap();
+/// let db = SimpleDB::new(tmp.path(), 400).unwrap();
+/// let page_id = PageId::new(tmp.path().join("data"), 0);
+/// db.file_manager().append_page(page_id.path()).unwrap();
+///
+/// let available = db.buffer_manager().available();
+/// {
+///     let pinned = db.buffer_manager().pin(&page_id).unwrap();
+///     assert_eq!(pinned.lock().unwrap().get_integer(0).unwrap(), 0);
+///     assert_eq!(db.buffer_manager().available(), available - 1);
+/// }
+/// assert_eq!(db.buffer_manager().available(), available);
+/// ```
+pub struct PinnedBuffer {
+    pool: Arc<Pool>,
+    buffer: Arc<Mutex<Buffer>>,
+    /// The position of the buffer in the pool.
+    frame: usize,
+    page_id: PageId,
+}
+
+impl PinnedBuffer {
+    pub(crate) fn new(
+        pool: Arc<Pool>,
+        buffer: Arc<Mutex<Buffer>>,
+        frame: usize,
+        page_id: PageId,
+    ) -> Self {
+        Self {
+            pool,
+            buffer,
+            frame,
+            page_id,
+        }
+    }
+
+    /// Returns the page the buffer is pinned to.
+    pub fn page_id(&self) -> &PageId {
+        &self.page_id
+    }
+
+    /// Locks the buffer for exclusive access to its page.
+    pub fn lock(&self) -> anyhow::Result<PageGuard<'_>> {
+        let buffer = self
+            .buffer
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire buffer lock"))?;
+        Ok(PageGuard {
+            pinned: self,
+            buffer,
+        })
+    }
+}
+
+impl Drop for PinnedBuffer {
+    fn drop(&mut self) {
+        self.pool.unpin(self.frame);
+    }
+}
+
+/// Exclusive access to a pinned buffer, dereferencing to the page it holds.
+pub struct PageGuard<'a> {
+    pinned: &'a PinnedBuffer,
+    buffer: MutexGuard<'a, Buffer>,
+}
+
+impl PageGuard<'_> {
+    /// Returns the buffer itself, e.g. to log its current contents.
+    ///
+    /// Changes to the page must be marked through [`set_modified`](Self::set_modified)
+    /// rather than the buffer, to keep the dirty page table up to date.
+    pub fn buffer(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+
+    /// Marks the page as modified by `txnum` with the LSN of the log record
+    /// describing the change, or `None` if the change was not logged, and adds it
+    /// to the dirty page table.
+    pub fn set_modified(&mut self, txnum: i32, lsn: Option<Lsn>) -> anyhow::Result<()> {
+        self.buffer.set_modified(txnum, lsn)?;
+        self.pinned
+            .pool
+            .record_modification(&self.pinned.page_id, self.pinned.frame, txnum, lsn)
+    }
+}
+
+impl Deref for PageGuard<'_> {
+    type Target = Page;
+
+    fn deref(&self) -> &Page {
+        self.buffer.contents()
+    }
+}
+
+impl DerefMut for PageGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Page {
+        self.buffer.contents_mut()
+    }
+}
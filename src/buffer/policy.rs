@@ -0,0 +1,417 @@
+//! Strategies for choosing the buffer to evict when a page has to be read.
+//!
+//! The [`BufferManager`](crate::buffer::manager::BufferManager) identifies buffers
+//! by their index in the pool, called a frame. It tells its [`ReplacementPolicy`]
+//! about every pin and every page it loads, and asks it for a victim among the
+//! unpinned frames whenever a page is missing from the pool.
+//!
+//! A policy is shared by all threads pinning pages. Policies therefore keep their
+//! state in atomics, so that recording an access never waits for a lock. Without
+//! a lock there is no ordered structure to take the oldest frame from, so LRU and
+//! LRU-2 compare a sample of [`SAMPLES`] frames instead of the whole pool, which
+//! keeps choosing a victim cheap in large pools.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+use crate::buffer::manager::BufferError;
+
+/// Number of evictable frames [`LruPolicy`] and [`Lru2Policy`] compare to choose a
+/// victim. Pools of at most this many frames get an exact LRU or LRU-2.
+pub const SAMPLES: usize = 16;
+
+/// Decides which unpinned frame gives up its page when another page is needed.
+///
+/// Accesses may be recorded and victims chosen by several threads at once.
+/// Concurrent updates may make a policy pick a slightly worse victim, but it must
+/// only ever pick frames for which `evictable` holds.
+pub trait ReplacementPolicy: Send + Sync {
+    /// Notes that the page held by `frame` was pinned.
+    fn record_access(&self, frame: usize);
+
+    /// Notes that `frame` was assigned a new page.
+    ///
+    /// Counts as an access by default. Policies that remember more than the latest
+    /// access should forget what they know about the page that was evicted.
+    fn record_load(&self, frame: usize) {
+        self.record_access(frame);
+    }
+
+    /// Chooses the frame to evict among those for which `evictable` holds, or
+    /// returns `None` if there is none.
+    fn victim(&self, evictable: &dyn Fn(usize) -> bool) -> Option<usize>;
+}
+
+/// The replacement policies that come with the buffer manager.
+///
+/// Policies can be named in configuration by the strings `naive`, `lru`, `clock`
+/// and `lru-2`.
+///
+/// # Examples
+///
+/// ```
+/// # use rimple::buffer::policy::ReplacementPolicyKind;
+/// let kind: ReplacementPolicyKind = "clock".parse().unwrap();
+/// assert_eq!(kind, ReplacementPolicyKind::Clock);
+/// assert_eq!(kind.to_string(), "clock");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ReplacementPolicyKind {
+    /// Evicts the first unpinned frame of the pool, see [`NaivePolicy`].
+    #[default]
+    Naive,
+    /// Evicts the least recently used frame, see [`LruPolicy`].
+    Lru,
+    /// Gives every recently used frame a second chance, see [`ClockPolicy`].
+    Clock,
+    /// Evicts by the second most recent access, see [`Lru2Policy`].
+    Lru2,
+}
+
+impl ReplacementPolicyKind {
+    /// Creates a policy of this kind for a pool of `frames` buffers.
+    pub fn build(self, frames: usize) -> Box<dyn ReplacementPolicy> {
+        match self {
+            Self::Naive => Box::new(NaivePolicy::new(frames)),
+            Self::Lru => Box::new(LruPolicy::new(frames)),
+            Self::Clock => Box::new(ClockPolicy::new(frames)),
+            Self::Lru2 => Box::new(Lru2Policy::new(frames)),
+        }
+    }
+}
+
+impl fmt::Display for ReplacementPolicyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Naive => "naive",
+            Self::Lru => "lru",
+            Self::Clock => "clock",
+            Self::Lru2 => "lru-2",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ReplacementPolicyKind {
+    type Err = BufferError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "naive" => Ok(Self::Naive),
+            "lru" => Ok(Self::Lru),
+            "clock" => Ok(Self::Clock),
+            "lru-2" | "lru2" => Ok(Self::Lru2),
+            _ => Err(BufferError::UnknownPolicy(s.to_string())),
+        }
+    }
+}
+
+/// Evicts the first unpinned frame of the pool.
+///
+/// Cheap, but keeps evicting the frames at the start of the pool, however hot
+/// their pages are.
+pub struct NaivePolicy {
+    frames: usize,
+}
+
+impl NaivePolicy {
+    pub fn new(frames: usize) -> Self {
+        Self { frames }
+    }
+}
+
+impl ReplacementPolicy for NaivePolicy {
+    fn record_access(&self, _frame: usize) {}
+
+    fn victim(&self, evictable: &dyn Fn(usize) -> bool) -> Option<usize> {
+        (0..self.frames).find(|&frame| evictable(frame))
+    }
+}
+
+/// Evicts the frame whose page was pinned least recently, among [`SAMPLES`]
+/// evictable frames.
+///
+/// The sample starts where the previous one ended, so that every frame is looked
+/// at in turn.
+pub struct LruPolicy {
+    /// The logical time of the latest access per frame, 0 if never used.
+    last_access: Vec<AtomicU64>,
+    now: AtomicU64,
+    /// The frame the next sample starts at, taken modulo the number of frames.
+    hand: AtomicUsize,
+}
+
+impl LruPolicy {
+    pub fn new(frames: usize) -> Self {
+        Self {
+            last_access: (0..frames).map(|_| AtomicU64::new(0)).collect(),
+            now: AtomicU64::new(0),
+            hand: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ReplacementPolicy for LruPolicy {
+    fn record_access(&self, frame: usize) {
+        let now = self.now.fetch_add(1, Ordering::Relaxed) + 1;
+        self.last_access[frame].store(now, Ordering::Relaxed);
+    }
+
+    fn victim(&self, evictable: &dyn Fn(usize) -> bool) -> Option<usize> {
+        sample(self.last_access.len(), &self.hand, evictable)
+            .min_by_key(|&frame| self.last_access[frame].load(Ordering::Relaxed))
+    }
+}
+
+/// Approximates LRU with one reference bit per frame.
+///
+/// A hand sweeps over the frames. A frame that was used since the hand last
+/// passed gets a second chance: its bit is cleared and the hand moves on. The
+/// first evictable frame without the bit is the victim.
+pub struct ClockPolicy {
+    referenced: Vec<AtomicBool>,
+    /// Advanced by every thread looking for a victim, taken modulo the number of frames.
+    hand: AtomicUsize,
+}
+
+impl ClockPolicy {
+    pub fn new(frames: usize) -> Self {
+        Self {
+            referenced: (0..frames).map(|_| AtomicBool::new(false)).collect(),
+            hand: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ReplacementPolicy for ClockPolicy {
+    fn record_access(&self, frame: usize) {
+        self.referenced[frame].store(true, Ordering::Relaxed);
+    }
+
+    fn victim(&self, evictable: &dyn Fn(usize) -> bool) -> Option<usize> {
+        let frames = self.referenced.len();
+        // The first sweep may clear every bit, the second then finds a victim
+        for _ in 0..2 * frames {
+            let frame = self.hand.fetch_add(1, Ordering::Relaxed) % frames;
+            if !evictable(frame) {
+                continue;
+            }
+            if !self.referenced[frame].swap(false, Ordering::Relaxed) {
+                return Some(frame);
+            }
+        }
+        None
+    }
+}
+
+/// Evicts the frame whose second most recent access lies furthest back (LRU-K with K = 2),
+/// among [`SAMPLES`] evictable frames.
+///
+/// Pages that were only used once so far count as infinitely old and go first,
+/// oldest single access first. A page that is scanned once therefore cannot push
+/// out a page that is used over and over.
+pub struct Lru2Policy {
+    /// The logical times of the latest and the second latest access per frame.
+    history: Vec<[AtomicU64; 2]>,
+    now: AtomicU64,
+    /// The frame the next sample starts at, taken modulo the number of frames.
+    hand: AtomicUsize,
+}
+
+impl Lru2Policy {
+    pub fn new(frames: usize) -> Self {
+        Self {
+            history: (0..frames)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            now: AtomicU64::new(0),
+            hand: AtomicUsize::new(0),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.now.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+impl ReplacementPolicy for Lru2Policy {
+    fn record_access(&self, frame: usize) {
+        let [latest, previous] = &self.history[frame];
+        previous.store(
+            latest.swap(self.tick(), Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn record_load(&self, frame: usize) {
+        let [latest, previous] = &self.history[frame];
+        latest.store(self.tick(), Ordering::Relaxed);
+        previous.store(0, Ordering::Relaxed);
+    }
+
+    fn victim(&self, evictable: &dyn Fn(usize) -> bool) -> Option<usize> {
+        sample(self.history.len(), &self.hand, evictable).min_by_key(|&frame| {
+            let [latest, previous] = &self.history[frame];
+            (
+                previous.load(Ordering::Relaxed),
+                latest.load(Ordering::Relaxed),
+            )
+        })
+    }
+}
+
+/// Returns up to [`SAMPLES`] evictable frames, starting at `hand` and advancing it.
+///
+/// Only looks at more than [`SAMPLES`] frames if some of them are not evictable.
+fn sample<'a>(
+    frames: usize,
+    hand: &AtomicUsize,
+    evictable: &'a dyn Fn(usize) -> bool,
+) -> impl Iterator<Item = usize> + 'a {
+    let start = hand.fetch_add(SAMPLES, Ordering::Relaxed);
+    (0..frames)
+        .map(move |i| start.wrapping_add(i) % frames)
+        .filter(move |&frame| evictable(frame))
+        .take(SAMPLES)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Loads frames 0..4 in order, replays `accesses`, then evicts every frame
+    /// in turn and returns the order they went in.
+    fn eviction_order(kind: ReplacementPolicyKind, accesses: &[usize]) -> Vec<usize> {
+        let policy = kind.build(4);
+        for frame in 0..4 {
+            policy.record_load(frame);
+        }
+        for &frame in accesses {
+            policy.record_access(frame);
+        }
+
+        let mut evicted: Vec<usize> = vec![];
+        while let Some(frame) = policy.victim(&|frame| !evicted.contains(&frame)) {
+            evicted.push(frame);
+        }
+        evicted
+    }
+
+    #[test]
+    fn naive_evicts_in_pool_order() {
+        let order = eviction_order(ReplacementPolicyKind::Naive, &[0, 0, 1]);
+
+        assert_eq!(order, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn lru_evicts_the_least_recently_used_first() {
+        let order = eviction_order(ReplacementPolicyKind::Lru, &[0, 2, 1]);
+
+        assert_eq!(order, [3, 0, 2, 1]);
+    }
+
+    #[test]
+    fn clock_gives_referenced_frames_a_second_chance() {
+        // setup
+        let policy = ClockPolicy::new(4);
+        policy.record_access(1);
+        policy.record_access(2);
+
+        // test
+        let first = policy.victim(&|_| true);
+        let second = policy.victim(&|_| true);
+        policy.record_access(3);
+        let third = policy.victim(&|frame| frame != 0);
+
+        // verify
+        assert_eq!(first, Some(0));
+        assert_eq!(second, Some(3));
+        assert_eq!(third, Some(1));
+    }
+
+    #[test]
+    fn clock_evicts_when_every_frame_was_referenced() {
+        let order = eviction_order(ReplacementPolicyKind::Clock, &[]);
+
+        assert_eq!(order, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn lru_2_evicts_pages_used_once_before_pages_used_repeatedly() {
+        // Frame 0 is hot, frame 3 was used twice long ago, 1 and 2 only once
+        let order = eviction_order(ReplacementPolicyKind::Lru2, &[3, 0, 0, 0]);
+
+        assert_eq!(order, [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn lru_2_forgets_the_history_of_evicted_pages() {
+        // setup
+        let policy = Lru2Policy::new(2);
+        policy.record_load(0);
+        policy.record_access(0);
+        policy.record_load(1);
+        policy.record_access(1);
+
+        // test
+        policy.record_load(0);
+        let victim = policy.victim(&|_| true);
+
+        // verify
+        assert_eq!(victim, Some(0));
+    }
+
+    #[test]
+    fn lru_policies_compare_a_sample_of_large_pools() {
+        for kind in [ReplacementPolicyKind::Lru, ReplacementPolicyKind::Lru2] {
+            // setup
+            let policy = kind.build(10 * SAMPLES);
+            for frame in 0..10 * SAMPLES {
+                policy.record_load(frame);
+            }
+            let looked_at = AtomicUsize::new(0);
+
+            // test
+            let victim = policy.victim(&|_| {
+                looked_at.fetch_add(1, Ordering::Relaxed);
+                true
+            });
+
+            // verify
+            assert_eq!(victim, Some(0), "{}", kind);
+            assert_eq!(looked_at.load(Ordering::Relaxed), SAMPLES, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn policies_report_no_victim_without_evictable_frames() {
+        for kind in [
+            ReplacementPolicyKind::Naive,
+            ReplacementPolicyKind::Lru,
+            ReplacementPolicyKind::Clock,
+            ReplacementPolicyKind::Lru2,
+        ] {
+            assert_eq!(kind.build(3).victim(&|_| false), None, "{}", kind);
+        }
+    }
+
+    #[test]
+    fn policy_names_round_trip() {
+        for kind in [
+            ReplacementPolicyKind::Naive,
+            ReplacementPolicyKind::Lru,
+            ReplacementPolicyKind::Clock,
+            ReplacementPolicyKind::Lru2,
+        ] {
+            assert_eq!(
+                kind.to_string().parse::<ReplacementPolicyKind>().unwrap(),
+                kind
+            );
+        }
+        assert!("mru".parse::<ReplacementPolicyKind>().is_err());
+    }
+}
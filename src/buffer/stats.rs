@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use crate::file::{Lsn, PageId};
+
+/// Counters of the work done by a [`BufferManager`](crate::buffer::manager::BufferManager).
+///
+/// Pins of a page that is already pinned count as hits. Pages loaded by read-ahead
+/// count as neither hits nor misses, but the pages they evict count as evictions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    /// Number of successful pins.
+    pub pins: u64,
+    /// Pins of pages that were in the pool already.
+    pub hits: u64,
+    /// Pins that had to read the page from storage.
+    pub misses: u64,
+    /// Number of times a buffer gave up its page for another one.
+    pub evictions: u64,
+    /// Number of modified pages written back, whether to evict them, on commit or
+    /// in the background.
+    pub write_backs: u64,
+    /// Number of the write-backs done ahead of time by the background writer.
+    pub background_writes: u64,
+    /// Number of pins that found every buffer pinned and had to wait.
+    pub waits: u64,
+    /// Number of pins that gave up waiting, see [`BufferError::Timeout`](crate::buffer::manager::BufferError::Timeout).
+    pub timeouts: u64,
+    /// Cumulative time pins spent waiting for a buffer.
+    pub pin_wait_time: Duration,
+}
+
+/// The state of a single buffer of the pool at the time of a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    /// The position of the buffer in the pool.
+    pub frame: usize,
+    /// The page held by the buffer, if any.
+    pub page_id: Option<PageId>,
+    /// Number of pins currently held on the buffer.
+    pub pins: usize,
+    /// The transaction whose changes to the page are not written back yet, if any.
+    pub modifying_txn: Option<i32>,
+    /// The LSN of the latest logged change to the page, if it was ever changed.
+    pub lsn: Option<Lsn>,
+}
@@ -0,0 +1,154 @@
+//! A thread that writes modified buffers back ahead of their eviction.
+//!
+//! Without it, a modified page is only written when its buffer is needed for
+//! another page or when its transaction commits, so a pin that evicts it has to
+//! wait for the write. The background writer trickles such pages to storage
+//! while they are not in use.
+
+use std::{
+    io,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::{debug, trace, warn};
+
+use crate::buffer::manager::BufferManager;
+
+/// How often and how much the [`BackgroundWriter`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackgroundWriterOptions {
+    /// The pause between two rounds of writing.
+    pub interval: Duration,
+    /// The maximum number of pages written per round, limiting the I/O spent on
+    /// writing ahead.
+    pub pages_per_round: usize,
+}
+
+impl Default for BackgroundWriterOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(200),
+            pages_per_round: 100,
+        }
+    }
+}
+
+/// Periodically writes modified, unpinned buffers back, see
+/// [`BufferManager::flush_unpinned`].
+///
+/// The thread stops and is joined when the writer is dropped.
+pub struct BackgroundWriter {
+    /// Set to ask the thread to stop, signalled to wake it up early.
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundWriter {
+    /// Starts writing back buffers of `buffer_manager` in a new thread.
+    pub fn start(
+        buffer_manager: Arc<BufferManager>,
+        options: BackgroundWriterOptions,
+    ) -> io::Result<Self> {
+        debug!("Starting background writer with {:?}", options);
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread = {
+            let stop = stop.clone();
+            thread::Builder::new()
+                .name("rimple-bgwriter".to_string())
+                .spawn(move || run(&buffer_manager, options, &stop))?
+        };
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        let (stopped, wake_up) = &*self.stop;
+        *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+        wake_up.notify_all();
+        if let Some(thread) = self.thread.take()
+            && thread.join().is_err()
+        {
+            warn!("Background writer panicked");
+        }
+        debug!("Background writer stopped");
+    }
+}
+
+fn run(
+    buffer_manager: &BufferManager,
+    options: BackgroundWriterOptions,
+    stop: &(Mutex<bool>, Condvar),
+) {
+    let (stopped, wake_up) = stop;
+    loop {
+        {
+            let Ok(stopped) = stopped.lock() else {
+                return;
+            };
+            let Ok((stopped, _)) =
+                wake_up.wait_timeout_while(stopped, options.interval, |stopped| !*stopped)
+            else {
+                return;
+            };
+            if *stopped {
+                return;
+            }
+        }
+        match buffer_manager.flush_unpinned(options.pages_per_round) {
+            Ok(0) => {}
+            Ok(written) => trace!("Background writer wrote {} pages", written),
+            Err(e) => warn!("Background writer failed to write back buffers: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use crate::{db::SimpleDB, file::Page};
+
+    use super::*;
+
+    #[test]
+    fn modified_pages_are_written_in_the_background() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path(), 400).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(&tmp.path().join("data")).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+        tx.unpin(&page_id).unwrap();
+        let options = BackgroundWriterOptions {
+            interval: Duration::from_millis(1),
+            pages_per_round: 1,
+        };
+
+        // test
+        let writer = BackgroundWriter::start(db.buffer_manager().clone(), options).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while db.buffer_stats().background_writes == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+        drop(writer);
+
+        // verify
+        assert_eq!(db.buffer_stats().background_writes, 1);
+        assert!(
+            db.buffer_frames()
+                .unwrap()
+                .iter()
+                .all(|frame| frame.modifying_txn.is_none())
+        );
+        let mut page = Page::with_size(400);
+        db.file_manager().read(&page_id, &mut page).unwrap();
+        assert_eq!(page.get_integer(0).unwrap(), 42);
+    }
+}
@@ -0,0 +1,498 @@
+//! Settings for opening a [`SimpleDB`], set in code or loaded from a file and
+//! the environment.
+//!
+//! A config file holds one `key = value` setting per line. Blank lines and
+//! lines starting with `#` are ignored. The same settings can be given as
+//! environment variables named after the key in upper case with a `RIMPLE_`
+//! prefix, e.g. `RIMPLE_BUFFERS=64`.
+//!
+//! | Key                  | Value                                   |
+//! |----------------------|-----------------------------------------|
+//! | `page_size`          | bytes per page                          |
+//! | `buffers`            | number of buffers in the pool           |
+//! | `log_file`           | name of the log file in the directory   |
+//! | `pin_timeout_ms`     | how long a pin waits for a buffer       |
+//! | `lock_timeout_ms`    | how long a transaction waits for a lock |
+//! | `durability`         | `sync`, `fsync-on-flush` or `none`      |
+//! | `replacement_policy` | `naive`, `lru`, `clock` or `lru-2`      |
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use log::debug;
+
+use crate::{
+    backup,
+    buffer::{manager::DEFAULT_PIN_TIMEOUT, policy::ReplacementPolicyKind},
+    db::SimpleDB,
+    file::{DurabilityMode, FileOptions, temp_file::TEMP_FILE_PREFIX},
+    tx::concurrency::lock_table::DEFAULT_LOCK_TIMEOUT,
+};
+
+/// Page size used unless configured otherwise.
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+
+/// Number of buffers in the pool unless configured otherwise.
+pub const DEFAULT_BUFFERS: usize = 8;
+
+/// Prefix of the environment variables read by [`SimpleDBConfig::load_env`].
+pub const ENV_PREFIX: &str = "RIMPLE_";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    /// A line of a config file is not a `key = value` setting.
+    #[error("{path:?}, line {line}: expected `key = value`, found {content:?}")]
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        content: String,
+    },
+
+    /// A setting was named that does not exist.
+    #[error("Unknown setting {0:?}")]
+    UnknownKey(String),
+
+    /// A setting was given a value it cannot take.
+    #[error("Invalid value {value:?} for {key}: {reason}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+}
+
+/// Everything that can be tuned when opening a [`SimpleDB`].
+///
+/// Settings not given keep their defaults, which match [`SimpleDB::new`].
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Duration;
+/// # use rimple::buffer::policy::ReplacementPolicyKind;
+/// # use rimple::config::SimpleDBConfig;
+/// let tmp = tempfile::tempdir().unwrap();
+/// let db = SimpleDBConfig::new(tmp.path())
+///     .page_size(400)
+///     .buffers(64)
+///     .pin_timeout(Duration::from_millis(100))
+///     .replacement_policy(ReplacementPolicyKind::Clock)
+///     .open()
+///     .unwrap();
+/// assert_eq!(db.buffer_manager().available(), 64);
+/// ```
+#[derive(Debug, Clone)]
+pub struct SimpleDBConfig {
+    pub(crate) dirname: PathBuf,
+    pub(crate) page_size: usize,
+    pub(crate) buffers: usize,
+    pub(crate) log_file: String,
+    pub(crate) pin_timeout: Duration,
+    pub(crate) lock_timeout: Duration,
+    pub(crate) file_options: FileOptions,
+    pub(crate) replacement_policy: ReplacementPolicyKind,
+}
+
+impl SimpleDBConfig {
+    /// Starts a configuration for the database in `dirname` with default settings.
+    pub fn new(dirname: impl AsRef<Path>) -> Self {
+        Self {
+            dirname: dirname.as_ref().to_path_buf(),
+            page_size: DEFAULT_PAGE_SIZE,
+            buffers: DEFAULT_BUFFERS,
+            log_file: SimpleDB::LOG_FILE.to_string(),
+            pin_timeout: DEFAULT_PIN_TIMEOUT,
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+            file_options: FileOptions::default(),
+            replacement_policy: ReplacementPolicyKind::default(),
+        }
+    }
+
+    /// Sets the page size, which must match the one an existing database was created with.
+    pub fn page_size(mut self, page_size: usize) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    /// Sets the number of buffers in the pool.
+    pub fn buffers(mut self, buffers: usize) -> Self {
+        self.buffers = buffers;
+        self
+    }
+
+    /// Sets the name of the log file inside the database directory.
+    ///
+    /// The name is recorded when the database is created, and opening it with
+    /// another one fails with
+    /// [`SuperblockError::LogFileMismatch`](crate::file::superblock::SuperblockError::LogFileMismatch).
+    ///
+    /// Backups always store the log as [`SimpleDB::LOG_FILE`], so a backup is
+    /// opened with the default name. No other log may take that name, nor the
+    /// name of a temporary or metadata file.
+    pub fn log_file(mut self, log_file: impl Into<String>) -> Self {
+        self.log_file = log_file.into();
+        self
+    }
+
+    /// Sets how long a pin waits for a buffer, see [`BufferManager::set_pin_timeout`](crate::buffer::manager::BufferManager::set_pin_timeout).
+    pub fn pin_timeout(mut self, timeout: Duration) -> Self {
+        self.pin_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a transaction waits for a lock held by another one before it
+    /// gives up.
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Sets when writes are forced to stable storage.
+    pub fn durability(mut self, durability: DurabilityMode) -> Self {
+        self.file_options.durability = durability;
+        self
+    }
+
+    /// Sets all storage options at once, including the durability mode.
+    pub fn file_options(mut self, options: FileOptions) -> Self {
+        self.file_options = options;
+        self
+    }
+
+    /// Sets the buffer replacement policy.
+    pub fn replacement_policy(mut self, policy: ReplacementPolicyKind) -> Self {
+        self.replacement_policy = policy;
+        self
+    }
+
+    /// Applies a single setting given by its key, as used in config files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::config::SimpleDBConfig;
+    /// assert!(SimpleDBConfig::new("db").set("buffers", "64").is_ok());
+    /// assert!(SimpleDBConfig::new("db").set("buffers", "many").is_err());
+    /// ```
+    pub fn set(mut self, key: &str, value: &str) -> Result<Self, ConfigError> {
+        match key {
+            "page_size" => self.page_size = parse(key, value)?,
+            "buffers" => self.buffers = parse(key, value)?,
+            "log_file" => self.log_file = value.to_string(),
+            "pin_timeout_ms" => self.pin_timeout = Duration::from_millis(parse(key, value)?),
+            "lock_timeout_ms" => self.lock_timeout = Duration::from_millis(parse(key, value)?),
+            "durability" => self.file_options.durability = parse_durability(value)?,
+            "replacement_policy" => self.replacement_policy = parse(key, value)?,
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(self)
+    }
+
+    /// Applies the settings of the config file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ConfigError`] if a line is malformed or a setting is unknown
+    /// or invalid, and with an I/O error if the file cannot be read.
+    pub fn load_file(self, path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        debug!("Loading database settings from {:?}", path);
+        let content = std::fs::read_to_string(path)?;
+        Ok(self.apply_file(path, &content)?)
+    }
+
+    /// Applies the settings given as `RIMPLE_*` environment variables.
+    ///
+    /// Variables with the prefix that name no setting are ignored, so other tools
+    /// can use the prefix too.
+    pub fn load_env(self) -> Result<Self, ConfigError> {
+        self.apply_env(std::env::vars())
+    }
+
+    /// Opens the database, creating it if it does not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ConfigError::InvalidValue`] if the settings cannot work
+    /// together, and otherwise like [`SimpleDB::new`].
+    pub fn open(self) -> anyhow::Result<SimpleDB> {
+        self.validate()?;
+        SimpleDB::with_config(self)
+    }
+
+    fn apply_file(mut self, path: &Path, content: &str) -> Result<Self, ConfigError> {
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(ConfigError::Syntax {
+                    path: path.to_path_buf(),
+                    line: i + 1,
+                    content: line.to_string(),
+                });
+            };
+            self = self.set(key.trim(), value.trim())?;
+        }
+        Ok(self)
+    }
+
+    fn apply_env(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            match self.clone().set(&key.to_ascii_lowercase(), &value) {
+                Ok(config) => self = config,
+                Err(ConfigError::UnknownKey(_)) => {
+                    debug!("Ignoring environment variable {}", name)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |key: &str, value: String, reason: &str| ConfigError::InvalidValue {
+            key: key.to_string(),
+            value,
+            reason: reason.to_string(),
+        };
+        if self.buffers == 0 {
+            return Err(invalid(
+                "buffers",
+                "0".to_string(),
+                "the pool needs a buffer",
+            ));
+        }
+        // The default log name is reserved for the log
+        let reserved = backup::reserved_file_names();
+        if self.log_file.is_empty()
+            || self.log_file.contains(['/', '\\'])
+            || self.log_file.starts_with(TEMP_FILE_PREFIX)
+            || (self.log_file != SimpleDB::LOG_FILE && reserved.contains(&self.log_file))
+        {
+            return Err(invalid(
+                "log_file",
+                self.log_file.clone(),
+                "expected the name of a file of its own in the database directory",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn parse<T>(key: &str, value: &str) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e: T::Err| ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+fn parse_durability(value: &str) -> Result<DurabilityMode, ConfigError> {
+    match value.to_ascii_lowercase().as_str() {
+        "sync" => Ok(DurabilityMode::Sync),
+        "fsync-on-flush" => Ok(DurabilityMode::FsyncOnFlush),
+        "none" => Ok(DurabilityMode::None),
+        _ => Err(ConfigError::InvalidValue {
+            key: "durability".to_string(),
+            value: value.to_string(),
+            reason: "expected one of sync, fsync-on-flush or none".to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::file::superblock::SuperblockError;
+
+    use super::*;
+
+    #[test]
+    fn config_file_settings_override_the_defaults() {
+        // setup
+        let content = "\
+# tuned for a bigger machine
+page_size = 8192
+buffers=256
+
+log_file = wal.log
+pin_timeout_ms = 250
+durability = fsync-on-flush
+replacement_policy = LRU-2
+";
+
+        // test
+        let config = SimpleDBConfig::new("db")
+            .apply_file(Path::new("rimple.conf"), content)
+            .unwrap();
+
+        // verify
+        assert_eq!(config.page_size, 8192);
+        assert_eq!(config.buffers, 256);
+        assert_eq!(config.log_file, "wal.log");
+        assert_eq!(config.pin_timeout, Duration::from_millis(250));
+        assert_eq!(config.lock_timeout, DEFAULT_LOCK_TIMEOUT);
+        assert_eq!(config.file_options.durability, DurabilityMode::FsyncOnFlush);
+        assert_eq!(config.replacement_policy, ReplacementPolicyKind::Lru2);
+    }
+
+    #[test]
+    fn malformed_config_files_are_rejected_with_their_line() {
+        // setup
+        let config = SimpleDBConfig::new("db");
+
+        // test
+        let syntax = config
+            .clone()
+            .apply_file(Path::new("rimple.conf"), "buffers = 8\nbuffers 16\n");
+        let unknown = config
+            .clone()
+            .apply_file(Path::new("rimple.conf"), "frames = 8\n");
+        let invalid = config.apply_file(Path::new("rimple.conf"), "durability = maybe\n");
+
+        // verify
+        assert!(matches!(syntax, Err(ConfigError::Syntax { line: 2, .. })));
+        assert!(matches!(unknown, Err(ConfigError::UnknownKey(key)) if key == "frames"));
+        assert!(
+            matches!(invalid, Err(ConfigError::InvalidValue { key, .. }) if key == "durability")
+        );
+    }
+
+    #[test]
+    fn environment_variables_apply_prefixed_settings() {
+        // setup
+        let vars = [
+            ("RIMPLE_BUFFERS", "32"),
+            ("RIMPLE_LOCK_TIMEOUT_MS", "50"),
+            ("RIMPLE_UNRELATED", "ignored"),
+            ("BUFFERS", "1"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        // test
+        let config = SimpleDBConfig::new("db").apply_env(vars).unwrap();
+
+        // verify
+        assert_eq!(config.buffers, 32);
+        assert_eq!(config.lock_timeout, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn configured_database_uses_its_settings() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let config = SimpleDBConfig::new(tmp.path())
+            .page_size(400)
+            .buffers(3)
+            .log_file("wal.log")
+            .replacement_policy(ReplacementPolicyKind::Clock);
+
+        // test
+        let db = config.open().unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        tx.commit().unwrap();
+
+        // verify
+        assert_eq!(db.file_manager().page_size(), 400);
+        assert_eq!(db.buffer_manager().available(), 3);
+        assert_eq!(
+            db.buffer_manager().replacement_policy(),
+            ReplacementPolicyKind::Clock
+        );
+        assert!(tmp.path().join("wal.log").exists());
+        assert!(!tmp.path().join(SimpleDB::LOG_FILE).exists());
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected_before_opening() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+
+        // test
+        let no_buffers = SimpleDBConfig::new(tmp.path()).buffers(0).open();
+        let log_elsewhere = SimpleDBConfig::new(tmp.path()).log_file("../log").open();
+
+        // verify
+        for result in [no_buffers, log_elsewhere] {
+            let Err(err) = result else {
+                panic!("opening with invalid settings succeeded");
+            };
+            assert!(matches!(
+                err.downcast_ref::<ConfigError>(),
+                Some(ConfigError::InvalidValue { .. })
+            ));
+        }
+        assert!(!tmp.path().join(SimpleDB::SUPERBLOCK_FILE).exists());
+    }
+
+    #[test]
+    fn log_file_names_of_other_files_are_rejected() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let mut names = backup::reserved_file_names().to_vec();
+        names.retain(|name| name != SimpleDB::LOG_FILE);
+        names.push(format!("{TEMP_FILE_PREFIX}wal"));
+
+        for name in names {
+            // test
+            let result = SimpleDBConfig::new(tmp.path()).log_file(&name).open();
+
+            // verify
+            let Err(err) = result else {
+                panic!("opening with log file {name:?} succeeded");
+            };
+            assert!(
+                matches!(
+                    err.downcast_ref::<ConfigError>(),
+                    Some(ConfigError::InvalidValue { .. })
+                ),
+                "{name}: {err}"
+            );
+        }
+        assert!(!tmp.path().join(SimpleDB::SUPERBLOCK_FILE).exists());
+    }
+
+    #[test]
+    fn database_is_opened_with_the_log_file_it_was_created_with() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        drop(
+            SimpleDBConfig::new(tmp.path())
+                .log_file("production-write-ahead.log")
+                .open()
+                .unwrap(),
+        );
+
+        // test
+        let default_log = SimpleDBConfig::new(tmp.path()).open();
+        let reopened = SimpleDB::open(tmp.path()).unwrap();
+
+        // verify
+        let Err(err) = default_log else {
+            panic!("opening with another log file succeeded");
+        };
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::LogFileMismatch { stored, requested })
+                if stored == "production-write-ahead.log" && requested == SimpleDB::LOG_FILE
+        ));
+        drop(reopened);
+        assert!(!tmp.path().join(SimpleDB::LOG_FILE).exists());
+    }
+}
@@ -1,44 +1,326 @@
-use log::info;
+use log::{info, warn};
 
-use crate::{buffer::manager::BufferManager, file::FileManager, log::manager::LogManager};
+use crate::{
+    backup::{self, BackupError, BackupManifest},
+    buffer::{
+        manager::BufferManager,
+        stats::{BufferStats, FrameInfo},
+        writer::{BackgroundWriter, BackgroundWriterOptions},
+    },
+    config::SimpleDBConfig,
+    file::{
+        DurabilityMode, FileManager, FileOptions, IoStats, Lsn, Page, PageId,
+        backend::{DiskBackend, StorageBackend},
+        dir_lock::{DirLock, LockMode},
+        superblock::{Superblock, SuperblockError},
+        temp_file::PageCache,
+    },
+    log::manager::LogManager,
+    tx::{concurrency::lock_table::LockTable, transaction::Transaction},
+};
 use std::{
-    path::Path,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock, Weak},
 };
 
+/// Whether opening a database may or must create it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OpenMode {
+    /// Fail if the database already exists.
+    Create,
+    /// Fail if the database does not exist yet.
+    Open,
+    /// Open the database, creating it if it does not exist yet.
+    OpenOrCreate,
+}
+
 pub struct SimpleDB {
+    dirname: PathBuf,
+    /// The name of the log file inside `dirname`.
+    log_file: String,
+    superblock: Superblock,
     file_manager: Arc<FileManager>,
     log_manager: Arc<Mutex<LogManager>>,
-    buffer_manager: Arc<Mutex<BufferManager>>,
+    buffer_manager: Arc<BufferManager>,
+    tx_num: Arc<Mutex<i32>>,
+    lock_table: Arc<LockTable>,
+    /// Taken exclusively by a backup to keep transactions from committing or rolling back.
+    commit_latch: Arc<RwLock<()>>,
+    /// Writes modified buffers back ahead of their eviction, if started.
+    background_writer: Option<BackgroundWriter>,
+    /// Keeps other processes out of the directory. Declared last so it is released
+    /// only after everything else, including the clean-shutdown marker, is written.
+    _dir_lock: Option<DirLock>,
 }
 
 impl SimpleDB {
+    /// The name of the log file, unless configured otherwise. Backups always store
+    /// the log under this name.
     pub const LOG_FILE: &'static str = "simpledb.log";
+    pub const SUPERBLOCK_FILE: &'static str = "simpledb.meta";
 
+    /// Opens the database in `dirname`, creating it if it does not exist yet.
+    ///
+    /// The directory stays locked against other processes until the database is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`DirLockError`](crate::file::dir_lock::DirLockError) if another
+    /// process has the database open.
+    ///
+    /// Fails with [`SuperblockError::PageSizeMismatch`] if an existing database
+    /// was created with a different page size.
+    ///
+    /// Every other setting keeps its default, see [`SimpleDBConfig`] to change them.
     pub fn new(dirname: impl AsRef<Path>, page_size: usize) -> anyhow::Result<Self> {
+        Self::with_options(dirname, page_size, FileOptions::default())
+    }
+
+    /// Opens the database in `dirname` with explicit storage options, creating it if needed.
+    pub fn with_options(
+        dirname: impl AsRef<Path>,
+        page_size: usize,
+        options: FileOptions,
+    ) -> anyhow::Result<Self> {
+        let config = SimpleDBConfig::new(dirname)
+            .page_size(page_size)
+            .file_options(options);
+        Self::start(&config, OpenMode::OpenOrCreate)
+    }
+
+    /// Opens the database with the given settings, creating it if needed.
+    ///
+    /// Called by [`SimpleDBConfig::open`], which checks the settings first.
+    pub(crate) fn with_config(config: SimpleDBConfig) -> anyhow::Result<Self> {
+        Self::start(&config, OpenMode::OpenOrCreate)
+    }
+
+    /// Creates a new database in `dirname`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`SuperblockError::AlreadyExists`] if `dirname` already holds a database.
+    pub fn create(dirname: impl AsRef<Path>, page_size: usize) -> anyhow::Result<Self> {
+        let config = SimpleDBConfig::new(dirname).page_size(page_size);
+        Self::start(&config, OpenMode::Create)
+    }
+
+    /// Opens the existing database in `dirname` with the settings it was created with.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`SuperblockError::Missing`] if `dirname` does not hold a database.
+    pub fn open(dirname: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let superblock_path = dirname.as_ref().join(Self::SUPERBLOCK_FILE);
+        let superblock = Superblock::read(&DiskBackend::new(), &superblock_path)?
+            .ok_or(SuperblockError::Missing(superblock_path))?;
+        let config = with_log_file(SimpleDBConfig::new(dirname), &superblock)
+            .page_size(superblock.page_size)
+            .file_options(FileOptions {
+                checksums: superblock.checksums,
+                segment_size: superblock.segment_size,
+                ..FileOptions::default()
+            });
+        Self::start(&config, OpenMode::Open)
+    }
+
+    /// Opens the existing database in `dirname` without ever modifying it.
+    ///
+    /// The directory is locked in shared mode, so any number of read-only openers
+    /// can coexist but no process can open it for writing at the same time. Files
+    /// are opened without write permission, nothing is logged, and recovery does
+    /// not run. A database that was not shut down cleanly may therefore show
+    /// changes of transactions that never committed.
+    ///
+    /// Transactions can read as usual, but every attempt to change or append a
+    /// page fails with [`TransactionError::ReadOnly`](crate::tx::transaction::TransactionError::ReadOnly).
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`SuperblockError::Missing`] if `dirname` does not hold a database,
+    /// and with [`DirLockError`](crate::file::dir_lock::DirLockError) if another
+    /// process has it open for writing.
+    pub fn open_read_only(dirname: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let dirname = dirname.as_ref();
+        let superblock_path = dirname.join(Self::SUPERBLOCK_FILE);
+        let superblock = Superblock::read(&DiskBackend::read_only(), &superblock_path)?
+            .ok_or(SuperblockError::Missing(superblock_path))?;
+        let dir_lock = DirLock::acquire(dirname, LockMode::Shared)?;
+        let options = FileOptions {
+            checksums: superblock.checksums,
+            segment_size: superblock.segment_size,
+            read_only: true,
+            ..FileOptions::default()
+        };
+        let file_manager = Arc::new(FileManager::with_options(
+            dirname,
+            superblock.page_size,
+            options,
+        )?);
+        Self::with_file_manager(
+            &with_log_file(SimpleDBConfig::new(dirname), &superblock),
+            file_manager,
+            OpenMode::Open,
+            Some(dir_lock),
+        )
+    }
+
+    /// Opens the database stored in `backend`, creating it if needed.
+    ///
+    /// `dirname` only serves as the prefix of the file names inside the backend,
+    /// so a [`MemoryBackend`](crate::file::backend::MemoryBackend) gives a
+    /// database that never touches the disk.
+    pub fn with_backend(
+        dirname: impl AsRef<Path>,
+        page_size: usize,
+        options: FileOptions,
+        backend: Arc<dyn StorageBackend>,
+    ) -> anyhow::Result<Self> {
         info!(
-            "Start to initialize the database in folder {:?} with page size {}",
+            "Start to initialize the database in backend folder {:?} with page size {}",
             dirname.as_ref(),
             page_size
         );
-        let file_manager = Arc::new(FileManager::new(&dirname, page_size)?);
+        let file_manager = Arc::new(FileManager::with_backend(backend, page_size, options));
+        Self::with_file_manager(
+            &SimpleDBConfig::new(dirname),
+            file_manager,
+            OpenMode::OpenOrCreate,
+            None,
+        )
+    }
+
+    fn start(config: &SimpleDBConfig, mode: OpenMode) -> anyhow::Result<Self> {
+        let dirname = &config.dirname;
+        info!(
+            "Start to initialize the database in folder {:?} with page size {}",
+            dirname, config.page_size
+        );
+        std::fs::create_dir_all(dirname)?;
+        let dir_lock = DirLock::acquire(dirname, LockMode::Exclusive)?;
+        let file_manager = Arc::new(FileManager::with_options(
+            dirname,
+            config.page_size,
+            config.file_options.clone(),
+        )?);
+        let superblock_path = dirname.join(Self::SUPERBLOCK_FILE);
+        if file_manager.backend().size(&superblock_path)? == 0
+            && let Some(file) = backup::data_files(dirname, &file_manager, &config.log_file)?
+                .into_iter()
+                .next()
+        {
+            return Err(SuperblockError::FilesWithoutSuperblock(file).into());
+        }
+        Self::with_file_manager(config, file_manager, mode, Some(dir_lock))
+    }
+
+    /// Opens the database on top of `file_manager`, which determines the page size
+    /// and storage options, with the remaining settings taken from `config`.
+    fn with_file_manager(
+        config: &SimpleDBConfig,
+        file_manager: Arc<FileManager>,
+        mode: OpenMode,
+        dir_lock: Option<DirLock>,
+    ) -> anyhow::Result<Self> {
+        let dirname = config.dirname.clone();
+        let superblock_path = dirname.join(Self::SUPERBLOCK_FILE);
+        let existing = Superblock::read(file_manager.backend(), &superblock_path)?;
+        let is_new = existing.is_none();
+        let mut superblock = match (existing, mode) {
+            (Some(_), OpenMode::Create) => {
+                return Err(SuperblockError::AlreadyExists(superblock_path).into());
+            }
+            (None, OpenMode::Open) => return Err(SuperblockError::Missing(superblock_path).into()),
+            (Some(mut superblock), _) => {
+                validate(&superblock, &file_manager, &config.log_file)?;
+                if !superblock.clean_shutdown {
+                    warn!("Database in {:?} was not shut down cleanly", dirname);
+                }
+                if superblock.log_file.is_empty() {
+                    superblock.log_file = config.log_file.clone();
+                }
+                superblock
+            }
+            (None, _) => {
+                // Without a superblock the page size of existing files is unknown
+                let log_path = dirname.join(&config.log_file);
+                if file_manager.backend().size(&log_path)? > 0 {
+                    return Err(SuperblockError::FilesWithoutSuperblock(log_path).into());
+                }
+                let options = file_manager.options();
+                Superblock::new(
+                    file_manager.page_size(),
+                    options.checksums,
+                    options.segment_size,
+                    &config.log_file,
+                )
+            }
+        };
+        let read_only = file_manager.options().read_only;
+        if !read_only {
+            // Stays unset on disk until the database is dropped
+            superblock.clean_shutdown = false;
+            superblock.write(file_manager.backend(), &superblock_path)?;
+        }
+
         let log_manager = Arc::new(Mutex::new(LogManager::new(
             file_manager.clone(),
-            dirname.as_ref().join(Self::LOG_FILE),
+            dirname.join(&config.log_file),
         )?));
 
-        let buffer_manager = Arc::new(Mutex::new(BufferManager::new(
+        let buffer_manager = Arc::new(BufferManager::new(
             file_manager.clone(),
             log_manager.clone(),
-            8, // default number of buffers
-        )));
+            config.buffers,
+        ));
+        buffer_manager.set_pin_timeout(config.pin_timeout);
+        buffer_manager.set_replacement_policy(config.replacement_policy);
+        let page_cache: Weak<dyn PageCache> = Arc::downgrade(&buffer_manager) as _;
+        file_manager.set_page_cache(page_cache);
 
-        info!("Database initialization done");
-        Ok(SimpleDB {
+        let db = SimpleDB {
+            dirname,
+            log_file: config.log_file.clone(),
+            superblock,
             file_manager,
             log_manager,
             buffer_manager,
-        })
+            tx_num: Arc::new(Mutex::new(0)),
+            lock_table: Arc::new(LockTable::with_timeout(config.lock_timeout)),
+            commit_latch: Arc::new(RwLock::new(())),
+            background_writer: None,
+            _dir_lock: dir_lock,
+        };
+
+        if !is_new && !read_only {
+            info!("Recovering existing database");
+            let (relocation, late) =
+                match BackupManifest::read(db.file_manager.backend(), &db.dirname) {
+                    Ok(manifest) => {
+                        // The log of a restored backup still names the files of the original database
+                        let late = manifest.late_lsns();
+                        let relocation = (manifest.source != db.dirname).then(|| {
+                            info!(
+                                "Database was restored from a backup of {:?}",
+                                manifest.source
+                            );
+                            (manifest.source, db.dirname.clone())
+                        });
+                        (relocation, late)
+                    }
+                    Err(e) if matches!(e.downcast_ref(), Some(BackupError::MissingManifest(_))) => {
+                        (None, 0..0)
+                    }
+                    Err(e) => return Err(e),
+                };
+            let mut tx = db.new_transaction()?;
+            tx.recover_backup(relocation, late)?;
+            tx.commit()?;
+        }
+
+        info!("Database initialization done");
+        Ok(db)
     }
 
     pub fn file_manager(&self) -> &FileManager {
@@ -48,4 +330,1078 @@ impl SimpleDB {
     pub fn log_manager(&self) -> &Mutex<LogManager> {
         &self.log_manager
     }
+
+    pub fn buffer_manager(&self) -> &Arc<BufferManager> {
+        &self.buffer_manager
+    }
+
+    /// Returns a snapshot of the file I/O performed by this database.
+    ///
+    /// The counters cover data and log files alike and keep growing
+    /// until [`reset_stats`](Self::reset_stats) is called.
+    pub fn stats(&self) -> IoStats {
+        self.file_manager.io_stats()
+    }
+
+    /// Sets all I/O counters reported by [`stats`](Self::stats) and all buffer pool
+    /// counters reported by [`buffer_stats`](Self::buffer_stats) back to zero.
+    pub fn reset_stats(&self) {
+        self.file_manager.reset_io_stats();
+        self.buffer_manager.reset_stats();
+    }
+
+    /// Returns a snapshot of the buffer pool counters.
+    ///
+    /// Together with [`buffer_frames`](Self::buffer_frames) this helps sizing the
+    /// pool: many waits and timeouts mean it is too small for the number of pages
+    /// pinned at once.
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.buffer_manager.stats()
+    }
+
+    /// Returns the page, pins, modifying transaction and LSN of every buffer in the pool.
+    pub fn buffer_frames(&self) -> anyhow::Result<Vec<FrameInfo>> {
+        self.buffer_manager.frames()
+    }
+
+    /// Starts a thread that writes modified buffers back while they are not pinned,
+    /// so that pins rarely have to wait for the write of the page they evict.
+    ///
+    /// Replaces the background writer started before, if any. The thread is
+    /// stopped when the database is dropped, see also
+    /// [`stop_background_writer`](Self::stop_background_writer).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use rimple::buffer::writer::BackgroundWriterOptions;
+    /// # use rimple::db::SimpleDB;
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let mut db = SimpleDB::new(tmp.path(), 400).unwrap();
+    /// db.start_background_writer(BackgroundWriterOptions {
+    ///     interval: Duration::from_millis(50),
+    ///     pages_per_round: 10,
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn start_background_writer(
+        &mut self,
+        options: BackgroundWriterOptions,
+    ) -> anyhow::Result<()> {
+        self.stop_background_writer();
+        self.background_writer = Some(BackgroundWriter::start(
+            self.buffer_manager.clone(),
+            options,
+        )?);
+        Ok(())
+    }
+
+    /// Stops the background writer and waits for it to finish its current round.
+    pub fn stop_background_writer(&mut self) {
+        self.background_writer = None;
+    }
+
+    /// Starts a new transaction on this database.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the start of the transaction cannot be logged.
+    pub fn new_transaction(&self) -> anyhow::Result<Transaction> {
+        Transaction::new(
+            self.file_manager.clone(),
+            self.log_manager.clone(),
+            self.buffer_manager.clone(),
+            self.tx_num.clone(),
+            self.lock_table.clone(),
+            self.commit_latch.clone(),
+        )
+    }
+
+    /// Copies the database into `dest` while transactions keep running.
+    ///
+    /// Data files are copied page by page, followed by the log up to its current
+    /// end, the superblock and finally a [`BackupManifest`]. Transactions keep
+    /// running and committing during the backup. The copy shows the database as
+    /// of the start of the backup: transactions that had not committed by then
+    /// are rolled back when the copy is opened. Restoring means opening `dest`
+    /// like any other database; the manifest tells recovery where the log's files
+    /// live now and which commits to ignore, so it has to stay in place until the
+    /// restored database was opened once.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`SuperblockError::AlreadyExists`] if `dest` holds a database.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::db::SimpleDB;
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+    /// let manifest = db.backup_to(tmp.path().join("backup")).unwrap();
+    /// assert_eq!(manifest.page_size, 400);
+    ///
+    /// let restored = SimpleDB::open(tmp.path().join("backup")).unwrap();
+    /// assert_eq!(restored.file_manager().page_size(), 400);
+    /// ```
+    pub fn backup_to(&self, dest: impl AsRef<Path>) -> anyhow::Result<BackupManifest> {
+        self.backup(dest.as_ref(), None)
+    }
+
+    /// Copies everything that changed since the backup in `base` into `dest`.
+    ///
+    /// Works like [`backup_to`](Self::backup_to), but only copies data pages whose
+    /// [LSN](Page::lsn) shows they were written after `base` started, and only the
+    /// part of the log written since. `base` may itself be incremental, so backups
+    /// form a chain that starts with a full backup. Use [`backup::restore`] to turn
+    /// a chain back into a database.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`BackupError::MissingManifest`] if `base` holds no backup, and
+    /// with [`BackupError::BrokenChain`] if it is a backup of another database.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::{backup, db::SimpleDB};
+    /// let tmp = tempfile::tempdir().unwrap();
+    /// let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+    /// db.backup_to(tmp.path().join("full")).unwrap();
+    /// db.backup_incremental_to(tmp.path().join("incr"), tmp.path().join("full"))
+    ///     .unwrap();
+    ///
+    /// let chain = [tmp.path().join("full"), tmp.path().join("incr")];
+    /// backup::restore(&chain, tmp.path().join("restored")).unwrap();
+    /// ```
+    pub fn backup_incremental_to(
+        &self,
+        dest: impl AsRef<Path>,
+        base: impl AsRef<Path>,
+    ) -> anyhow::Result<BackupManifest> {
+        let base_dir = base.as_ref();
+        let base = BackupManifest::read(&DiskBackend::read_only(), base_dir)?;
+        if base.source != self.dirname {
+            return Err(BackupError::BrokenChain {
+                path: base_dir.to_path_buf(),
+                reason: format!("it is a backup of {:?}", base.source),
+            }
+            .into());
+        }
+        self.backup(dest.as_ref(), Some(&base))
+    }
+
+    /// Copies the database into `dest`, restricted to the changes since `base` if given.
+    fn backup(&self, dest: &Path, base: Option<&BackupManifest>) -> anyhow::Result<BackupManifest> {
+        let created_at = backup::now();
+        info!("Backing up database {:?} to {:?}", self.dirname, dest);
+        std::fs::create_dir_all(dest)?;
+        let dest_superblock = dest.join(Self::SUPERBLOCK_FILE);
+        if Superblock::read(&DiskBackend::new(), &dest_superblock)?.is_some() {
+            return Err(SuperblockError::AlreadyExists(dest_superblock).into());
+        }
+        let options = FileOptions {
+            durability: DurabilityMode::FsyncOnFlush,
+            read_only: false,
+            ..self.file_manager.options().clone()
+        };
+        let target = FileManager::with_options(dest, self.file_manager.page_size(), options)?;
+
+        // Transactions that commit from here on are rolled back when the backup is
+        // opened, so the latch only makes sure no commit is halfway done
+        let start_lsn = {
+            let _latch = self.commit_latch.write().unwrap();
+            self.log_manager.lock().unwrap().latest_lsn()
+        };
+        // Pages written after the base backup started carry at least its start LSN
+        let since = base.map_or(0, |base| base.start_lsn);
+
+        // Log pages before the last one never change again
+        let log = self.dirname.join(&self.log_file);
+        let log_copy = dest.join(Self::LOG_FILE);
+        let log_start = self.file_manager.size(&log)?;
+        let stable_log = log_start.saturating_sub(1);
+        let log_from = base.map_or(0, |base| base.log_end.saturating_sub(1));
+        self.copy_pages(&target, &log, log_from..stable_log, 0, &log_copy)?;
+
+        let mut files = BTreeMap::new();
+        let mut changed_pages = BTreeMap::new();
+        let mut deleted_files = BTreeSet::new();
+        for path in backup::data_files(&self.dirname, &self.file_manager, &self.log_file)? {
+            let pages = self.file_manager.size(&path)?;
+            let name = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let copied = self.copy_pages(&target, &path, 0..pages, since, &dest.join(&name))?;
+            if !copied.is_empty() {
+                target.sync(&dest.join(&name))?;
+            }
+            if base.is_some() {
+                changed_pages.insert(name.clone(), copied);
+            }
+            files.insert(name, pages);
+        }
+        if let Some(base) = base {
+            deleted_files.extend(
+                base.files
+                    .keys()
+                    .filter(|name| !files.contains_key(*name))
+                    .cloned(),
+            );
+        }
+
+        // Only the last log page is written again, so it is copied while holding the
+        // log manager and the full pages in front of it after releasing it
+        let log_end = {
+            let mut log_manager = self.log_manager.lock().unwrap();
+            log_manager.flush_all()?;
+            let log_end = log_manager
+                .latest_lsn()
+                .div_ceil(self.file_manager.page_size() as Lsn);
+            if let Some(last) = log_end.checked_sub(1) {
+                let mut page = Page::with_size(self.file_manager.page_size());
+                self.file_manager
+                    .read(&PageId::new(log.clone(), last), &mut page)?;
+                target.write(&PageId::new(log_copy.clone(), last), &page)?;
+            }
+            log_end
+        };
+        self.copy_pages(
+            &target,
+            &log,
+            log_from.max(stable_log)..log_end.saturating_sub(1),
+            0,
+            &log_copy,
+        )?;
+        target.sync(&log_copy)?;
+
+        let mut superblock = self.superblock.clone();
+        superblock.log_file = Self::LOG_FILE.to_string();
+        superblock.clean_shutdown = false;
+        superblock.write(target.backend(), &dest_superblock)?;
+
+        let manifest = BackupManifest {
+            source: self.dirname.clone(),
+            created_at,
+            base: base.map(|base| base.created_at),
+            page_size: self.file_manager.page_size(),
+            start_lsn,
+            log_start,
+            log_end,
+            files,
+            changed_pages,
+            deleted_files,
+        };
+        manifest.write(target.backend(), dest)?;
+        info!("Backup to {:?} done", dest);
+        Ok(manifest)
+    }
+
+    /// Copies the pages in `blocks` of a file whose LSN is at least `since` to the
+    /// file `copy`, and returns the copied block numbers.
+    ///
+    /// Pages are read through the buffer manager, so a page is never copied halfway
+    /// through being written back.
+    fn copy_pages(
+        &self,
+        target: &FileManager,
+        path: &Path,
+        blocks: std::ops::Range<u64>,
+        since: u64,
+        copy: &Path,
+    ) -> anyhow::Result<Vec<u64>> {
+        let mut page = Page::with_size(self.file_manager.page_size());
+        let mut copied = vec![];
+        for block_no in blocks {
+            self.buffer_manager
+                .read_from_storage(&PageId::new(path.to_path_buf(), block_no), &mut page)?;
+            if page.lsn() >= since {
+                target.write(&PageId::new(copy.to_path_buf(), block_no), &page)?;
+                copied.push(block_no);
+            }
+        }
+        Ok(copied)
+    }
+}
+
+impl Drop for SimpleDB {
+    fn drop(&mut self) {
+        self.stop_background_writer();
+        if self.file_manager.options().read_only {
+            return;
+        }
+        self.superblock.clean_shutdown = true;
+        let path = self.dirname.join(Self::SUPERBLOCK_FILE);
+        if let Err(e) = self.superblock.write(self.file_manager.backend(), &path) {
+            warn!("Failed to record clean shutdown in {:?}: {}", path, e);
+        }
+    }
+}
+
+/// Checks that an existing database is opened with the settings it was created with.
+///
+/// A superblock that does not name its log file yet accepts any log file.
+fn validate(
+    superblock: &Superblock,
+    file_manager: &FileManager,
+    log_file: &str,
+) -> Result<(), SuperblockError> {
+    if superblock.page_size != file_manager.page_size() {
+        return Err(SuperblockError::PageSizeMismatch {
+            stored: superblock.page_size,
+            requested: file_manager.page_size(),
+        });
+    }
+    if superblock.checksums != file_manager.options().checksums {
+        return Err(SuperblockError::ChecksumSettingMismatch {
+            stored: superblock.checksums,
+            requested: file_manager.options().checksums,
+        });
+    }
+    if superblock.segment_size != file_manager.options().segment_size {
+        return Err(SuperblockError::SegmentSizeMismatch {
+            stored: superblock.segment_size,
+            requested: file_manager.options().segment_size,
+        });
+    }
+    if !superblock.log_file.is_empty() && superblock.log_file != log_file {
+        return Err(SuperblockError::LogFileMismatch {
+            stored: superblock.log_file.clone(),
+            requested: log_file.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Makes `config` use the log file recorded in `superblock`, if it names one.
+fn with_log_file(config: SimpleDBConfig, superblock: &Superblock) -> SimpleDBConfig {
+    if superblock.log_file.is_empty() {
+        config
+    } else {
+        config.log_file(superblock.log_file.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        io,
+        sync::{
+            Barrier,
+            atomic::{AtomicBool, Ordering},
+            mpsc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use crate::{
+        file::{DurabilityMode, backend::MemoryBackend, dir_lock::DirLockError},
+        tx::transaction::TransactionError,
+    };
+
+    use super::*;
+
+    /// Counts `sync` calls per file on top of a memory backend.
+    #[derive(Default)]
+    struct SyncCountingBackend {
+        inner: MemoryBackend,
+        syncs: Mutex<HashMap<PathBuf, usize>>,
+    }
+
+    impl SyncCountingBackend {
+        fn syncs(&self, path: &Path) -> usize {
+            self.syncs.lock().unwrap().get(path).copied().unwrap_or(0)
+        }
+    }
+
+    impl StorageBackend for SyncCountingBackend {
+        fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+            self.inner.read(path, offset, buf)
+        }
+
+        fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+            self.inner.write(path, offset, buf)
+        }
+
+        fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+            self.inner.append(path, buf)
+        }
+
+        fn size(&self, path: &Path) -> io::Result<u64> {
+            self.inner.size(path)
+        }
+
+        fn sync(&self, path: &Path) -> io::Result<()> {
+            *self
+                .syncs
+                .lock()
+                .unwrap()
+                .entry(path.to_path_buf())
+                .or_default() += 1;
+            self.inner.sync(path)
+        }
+
+        fn delete(&self, path: &Path) -> io::Result<()> {
+            self.inner.delete(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.inner.rename(from, to)
+        }
+    }
+
+    fn commit_one_write(durability: DurabilityMode) -> Arc<SyncCountingBackend> {
+        let backend = Arc::new(SyncCountingBackend::default());
+        let options = FileOptions {
+            durability,
+            ..FileOptions::default()
+        };
+        let db = SimpleDB::with_backend("db", 400, options, backend.clone()).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(Path::new("db/data")).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+        tx.commit().unwrap();
+        backend
+    }
+
+    #[test]
+    fn fsync_on_flush_syncs_data_and_log_on_commit() {
+        let backend = commit_one_write(DurabilityMode::FsyncOnFlush);
+
+        assert!(backend.syncs(Path::new("db/data")) > 0);
+        assert!(backend.syncs(&Path::new("db").join(SimpleDB::LOG_FILE)) > 0);
+    }
+
+    #[test]
+    fn durability_none_never_syncs_data_or_log() {
+        let backend = commit_one_write(DurabilityMode::None);
+
+        assert_eq!(backend.syncs(Path::new("db/data")), 0);
+        assert_eq!(backend.syncs(&Path::new("db").join(SimpleDB::LOG_FILE)), 0);
+    }
+
+    #[test]
+    fn open_uses_the_page_size_the_database_was_created_with() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        drop(SimpleDB::create(tmp.path(), 400).unwrap());
+
+        // test
+        let db = SimpleDB::open(tmp.path()).unwrap();
+
+        // verify
+        assert_eq!(db.file_manager().page_size(), 400);
+    }
+
+    #[test]
+    fn opening_with_a_different_page_size_fails() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        drop(SimpleDB::new(tmp.path(), 400).unwrap());
+
+        // test
+        let err = SimpleDB::new(tmp.path(), 512).err().unwrap();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::PageSizeMismatch {
+                stored: 400,
+                requested: 512
+            })
+        ));
+    }
+
+    #[test]
+    fn create_refuses_an_existing_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        drop(SimpleDB::create(tmp.path(), 400).unwrap());
+
+        let err = SimpleDB::create(tmp.path(), 400).err().unwrap();
+
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::AlreadyExists(_))
+        ));
+    }
+
+    #[test]
+    fn a_second_opener_is_locked_out() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path(), 400).unwrap();
+
+        // test
+        let err = SimpleDB::new(tmp.path(), 400).err().unwrap();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<DirLockError>(),
+            Some(DirLockError::HeldExclusively { .. })
+        ));
+        drop(db);
+        assert!(SimpleDB::open(tmp.path()).is_ok());
+    }
+
+    #[test]
+    fn open_refuses_a_missing_database() {
+        let tmp = tempfile::tempdir().unwrap();
+
+        let err = SimpleDB::open(tmp.path().join("nothing")).err().unwrap();
+
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::Missing(_))
+        ));
+        assert!(!tmp.path().join("nothing").exists());
+    }
+
+    #[test]
+    fn files_without_a_superblock_are_not_taken_for_a_new_database() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        drop(SimpleDB::new(tmp.path(), 400).unwrap());
+        std::fs::write(tmp.path().join("data"), [7; 400]).unwrap();
+        std::fs::remove_file(tmp.path().join(SimpleDB::SUPERBLOCK_FILE)).unwrap();
+
+        // test
+        let err = SimpleDB::new(tmp.path(), 400).err().unwrap();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::FilesWithoutSuperblock(_))
+        ));
+        assert!(!tmp.path().join(SimpleDB::SUPERBLOCK_FILE).exists());
+    }
+
+    #[test]
+    fn a_log_without_a_superblock_is_not_taken_for_a_new_database() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        drop(SimpleDB::with_backend("db", 400, FileOptions::default(), backend.clone()).unwrap());
+        backend
+            .delete(&Path::new("db").join(SimpleDB::SUPERBLOCK_FILE))
+            .unwrap();
+
+        // test
+        let err = SimpleDB::with_backend("db", 400, FileOptions::default(), backend)
+            .err()
+            .unwrap();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::FilesWithoutSuperblock(_))
+        ));
+    }
+
+    fn directory_contents(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let content = std::fs::read(&path).unwrap();
+                (path, content)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn read_only_open_reads_data_without_changing_the_directory() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path().join("data");
+        let page_id = {
+            let db = SimpleDB::new(tmp.path(), 400).unwrap();
+            let mut tx = db.new_transaction().unwrap();
+            let page_id = tx.append(&data).unwrap();
+            tx.pin(&page_id).unwrap();
+            tx.set_int(&page_id, 0, 42, true).unwrap();
+            tx.commit().unwrap();
+            page_id
+        };
+        let before = directory_contents(tmp.path());
+
+        // test
+        let db = SimpleDB::open_read_only(tmp.path()).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        tx.pin(&page_id).unwrap();
+        let value = tx.get_int(&page_id, 0).unwrap();
+        let set_err = tx.set_int(&page_id, 0, 7, true).unwrap_err();
+        let append_err = tx.append(&data).unwrap_err();
+        tx.commit().unwrap();
+        drop(db);
+
+        // verify
+        assert_eq!(value, 42);
+        for err in [set_err, append_err] {
+            assert!(matches!(
+                err.downcast_ref::<TransactionError>(),
+                Some(TransactionError::ReadOnly { .. })
+            ));
+        }
+        assert_eq!(directory_contents(tmp.path()), before);
+    }
+
+    #[test]
+    fn read_only_openers_share_the_directory_but_exclude_writers() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        drop(SimpleDB::new(tmp.path(), 400).unwrap());
+
+        // test
+        let first = SimpleDB::open_read_only(tmp.path()).unwrap();
+        let second = SimpleDB::open_read_only(tmp.path());
+        let writer = SimpleDB::open(tmp.path());
+
+        // verify
+        assert!(second.is_ok());
+        assert!(
+            writer
+                .err()
+                .unwrap()
+                .downcast_ref::<DirLockError>()
+                .is_some()
+        );
+        drop(first);
+    }
+
+    #[test]
+    fn stats_report_data_and_log_io_until_reset() {
+        // setup
+        let db = SimpleDB::with_backend(
+            "db",
+            400,
+            FileOptions::default(),
+            Arc::new(MemoryBackend::new()),
+        )
+        .unwrap();
+        db.reset_stats();
+
+        // test
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(Path::new("db/data")).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+        tx.commit().unwrap();
+        let stats = db.stats();
+        db.reset_stats();
+
+        // verify
+        let data = stats.file(Path::new("db/data"));
+        assert_eq!(data.appends, 1);
+        assert!(data.writes >= 1);
+        assert!(stats.file(&Path::new("db").join(SimpleDB::LOG_FILE)).writes >= 1);
+        assert_eq!(db.stats().total.writes, 0);
+    }
+
+    #[test]
+    fn buffer_frames_show_uncommitted_changes_until_commit() {
+        // setup
+        let db = SimpleDB::with_backend(
+            "db",
+            400,
+            FileOptions::default(),
+            Arc::new(MemoryBackend::new()),
+        )
+        .unwrap();
+        db.reset_stats();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(Path::new("db/data")).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+
+        // test
+        let frame_of = |db: &SimpleDB| {
+            db.buffer_frames()
+                .unwrap()
+                .into_iter()
+                .find(|frame| frame.page_id.as_ref() == Some(&page_id))
+                .unwrap()
+        };
+        let before_commit = frame_of(&db);
+        tx.commit().unwrap();
+        let after_commit = frame_of(&db);
+
+        // verify
+        assert_eq!(before_commit.pins, 1);
+        assert!(before_commit.modifying_txn.is_some());
+        assert!(before_commit.lsn.is_some());
+        assert_eq!(after_commit.pins, 0);
+        assert_eq!(after_commit.modifying_txn, None);
+        let stats = db.buffer_stats();
+        assert_eq!((stats.pins, stats.misses, stats.write_backs), (1, 1, 1));
+    }
+
+    #[test]
+    fn clean_shutdown_is_recorded_on_drop() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let path = Path::new("db").join(SimpleDB::SUPERBLOCK_FILE);
+        let db =
+            SimpleDB::with_backend("db", 400, FileOptions::default(), backend.clone()).unwrap();
+        let while_open = Superblock::read(backend.as_ref(), &path).unwrap().unwrap();
+
+        // test
+        drop(db);
+
+        // verify
+        let after_drop = Superblock::read(backend.as_ref(), &path).unwrap().unwrap();
+        assert!(!while_open.clean_shutdown);
+        assert!(after_drop.clean_shutdown);
+        assert_eq!(after_drop.created_at, while_open.created_at);
+    }
+
+    #[test]
+    fn backup_restores_committed_data() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(&tmp.path().join("db/data")).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+        tx.commit().unwrap();
+
+        // test
+        let manifest = db.backup_to(tmp.path().join("backup")).unwrap();
+        let restored = SimpleDB::open(tmp.path().join("backup")).unwrap();
+
+        // verify
+        let backup_page = PageId::new(tmp.path().join("backup/data"), 0);
+        let mut tx = restored.new_transaction().unwrap();
+        tx.pin(&backup_page).unwrap();
+        assert_eq!(tx.get_int(&backup_page, 0).unwrap(), 42);
+        assert_eq!(manifest.files, [("data".to_string(), 1)].into());
+        assert!(manifest.log_end >= manifest.log_start);
+    }
+
+    #[test]
+    fn backup_rolls_back_transactions_running_during_the_backup() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path().join("db/data");
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        let mut setup = db.new_transaction().unwrap();
+        for _ in 0..10 {
+            let page_id = setup.append(&data).unwrap();
+            setup.pin(&page_id).unwrap();
+            setup.set_int(&page_id, 0, 1, true).unwrap();
+            setup.unpin(&page_id).unwrap();
+        }
+        setup.commit().unwrap();
+
+        // More pages than buffers, so some uncommitted changes reach the disk
+        let mut running = db.new_transaction().unwrap();
+        for block_no in 0..10 {
+            let page_id = PageId::new(data.clone(), block_no);
+            running.pin(&page_id).unwrap();
+            running.set_int(&page_id, 0, 2, true).unwrap();
+            running.unpin(&page_id).unwrap();
+        }
+
+        // test
+        db.backup_to(tmp.path().join("backup")).unwrap();
+        running.commit().unwrap();
+        let restored = SimpleDB::open(tmp.path().join("backup")).unwrap();
+
+        // verify
+        let mut tx = restored.new_transaction().unwrap();
+        for block_no in 0..10 {
+            let page_id = PageId::new(tmp.path().join("backup/data"), block_no);
+            tx.pin(&page_id).unwrap();
+            assert_eq!(tx.get_int(&page_id, 0).unwrap(), 1);
+            tx.unpin(&page_id).unwrap();
+        }
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = PageId::new(data, 0);
+        tx.pin(&page_id).unwrap();
+        assert_eq!(tx.get_int(&page_id, 0).unwrap(), 2);
+    }
+
+    /// Holds up the first read of the file `gated` after [`arm`](Self::arm) until
+    /// released, on top of a disk backend.
+    struct GatedBackend {
+        inner: DiskBackend,
+        gated: PathBuf,
+        armed: AtomicBool,
+        reached: Barrier,
+        release: Mutex<Option<mpsc::Receiver<()>>>,
+        timed_out: AtomicBool,
+    }
+
+    impl GatedBackend {
+        fn new(gated: PathBuf) -> (Self, mpsc::Sender<()>) {
+            let (release, receiver) = mpsc::channel();
+            let backend = Self {
+                inner: DiskBackend::new(),
+                gated,
+                armed: AtomicBool::new(false),
+                reached: Barrier::new(2),
+                release: Mutex::new(Some(receiver)),
+                timed_out: AtomicBool::new(false),
+            };
+            (backend, release)
+        }
+
+        fn arm(&self) {
+            self.armed.store(true, Ordering::SeqCst);
+        }
+    }
+
+    impl StorageBackend for GatedBackend {
+        fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+            if path == self.gated && self.armed.swap(false, Ordering::SeqCst) {
+                self.reached.wait();
+                let release = self.release.lock().unwrap().take().unwrap();
+                if release.recv_timeout(Duration::from_secs(5)).is_err() {
+                    self.timed_out.store(true, Ordering::SeqCst);
+                }
+            }
+            self.inner.read(path, offset, buf)
+        }
+
+        fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+            self.inner.write(path, offset, buf)
+        }
+
+        fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+            self.inner.append(path, buf)
+        }
+
+        fn size(&self, path: &Path) -> io::Result<u64> {
+            self.inner.size(path)
+        }
+
+        fn sync(&self, path: &Path) -> io::Result<()> {
+            self.inner.sync(path)
+        }
+
+        fn delete(&self, path: &Path) -> io::Result<()> {
+            self.inner.delete(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.inner.rename(from, to)
+        }
+    }
+
+    #[test]
+    fn commits_during_a_backup_neither_wait_nor_reach_the_copy() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("db");
+        std::fs::create_dir(&dir).unwrap();
+        let (backend, release) = GatedBackend::new(dir.join("data"));
+        let backend = Arc::new(backend);
+        let db =
+            SimpleDB::with_backend(&dir, 400, FileOptions::default(), backend.clone()).unwrap();
+        let data = PageId::new(dir.join("data"), 0);
+        let other = PageId::new(dir.join("other"), 0);
+        set_committed(&db, &data, 1);
+        set_committed(&db, &other, 1);
+        let mut running = db.new_transaction().unwrap();
+        running.pin(&other).unwrap();
+        running.set_int(&other, 0, 2, true).unwrap();
+
+        // test
+        backend.arm();
+        thread::scope(|scope| {
+            let backup = scope.spawn(|| db.backup_to(tmp.path().join("backup")));
+            // The backup copies "data" before "other"
+            backend.reached.wait();
+            running.commit().unwrap();
+            release.send(()).unwrap();
+            backup.join().unwrap().unwrap();
+        });
+
+        // verify
+        assert!(!backend.timed_out.load(Ordering::SeqCst));
+        let restored = SimpleDB::open(tmp.path().join("backup")).unwrap();
+        let backup_page = PageId::new(tmp.path().join("backup/other"), 0);
+        let mut tx = restored.new_transaction().unwrap();
+        tx.pin(&backup_page).unwrap();
+        assert_eq!(tx.get_int(&backup_page, 0).unwrap(), 1);
+        let mut tx = db.new_transaction().unwrap();
+        tx.pin(&other).unwrap();
+        assert_eq!(tx.get_int(&other, 0).unwrap(), 2);
+    }
+
+    #[test]
+    fn backup_stores_a_configured_log_under_the_default_name() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDBConfig::new(tmp.path().join("db"))
+            .page_size(400)
+            .log_file("wal.log")
+            .open()
+            .unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(&tmp.path().join("db/data")).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+        tx.commit().unwrap();
+
+        // test
+        let manifest = db.backup_to(tmp.path().join("backup")).unwrap();
+
+        // verify
+        assert_eq!(manifest.files, [("data".to_string(), 1)].into());
+        assert!(tmp.path().join("backup").join(SimpleDB::LOG_FILE).exists());
+        assert!(!tmp.path().join("backup/wal.log").exists());
+        let restored = SimpleDB::open(tmp.path().join("backup")).unwrap();
+        let backup_page = PageId::new(tmp.path().join("backup/data"), 0);
+        let mut tx = restored.new_transaction().unwrap();
+        tx.pin(&backup_page).unwrap();
+        assert_eq!(tx.get_int(&backup_page, 0).unwrap(), 42);
+    }
+
+    #[test]
+    fn backup_refuses_an_existing_database() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        drop(SimpleDB::new(tmp.path().join("other"), 400).unwrap());
+
+        let err = db.backup_to(tmp.path().join("other")).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::AlreadyExists(_))
+        ));
+    }
+
+    fn set_committed(db: &SimpleDB, page_id: &PageId, value: i32) {
+        let mut tx = db.new_transaction().unwrap();
+        if page_id.block_no() == db.file_manager().size(page_id.path()).unwrap() {
+            tx.append(page_id.path()).unwrap();
+        }
+        tx.pin(page_id).unwrap();
+        tx.set_int(page_id, 0, value, true).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn incremental_backup_copies_only_pages_written_since_its_base() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path().join("db/data");
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        for block_no in 0..5 {
+            set_committed(&db, &PageId::new(data.clone(), block_no), 1);
+        }
+        let full = db.backup_to(tmp.path().join("full")).unwrap();
+        set_committed(&db, &PageId::new(data.clone(), 3), 2);
+
+        // test
+        let incremental = db
+            .backup_incremental_to(tmp.path().join("incr"), tmp.path().join("full"))
+            .unwrap();
+
+        // verify
+        assert_eq!(incremental.base, Some(full.created_at));
+        assert!(incremental.start_lsn > full.start_lsn);
+        assert_eq!(
+            incremental.changed_pages,
+            [("data".to_string(), vec![3])].into()
+        );
+        assert_eq!(incremental.files, full.files);
+    }
+
+    #[test]
+    fn restore_applies_incremental_backups_in_order() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let data = tmp.path().join("db/data");
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        for block_no in 0..3 {
+            set_committed(&db, &PageId::new(data.clone(), block_no), 1);
+        }
+        db.backup_to(tmp.path().join("full")).unwrap();
+        set_committed(&db, &PageId::new(data.clone(), 0), 2);
+        db.backup_incremental_to(tmp.path().join("incr1"), tmp.path().join("full"))
+            .unwrap();
+        set_committed(&db, &PageId::new(data.clone(), 1), 3);
+        set_committed(&db, &PageId::new(data.clone(), 3), 4);
+        let mut running = db.new_transaction().unwrap();
+        let page_id = PageId::new(data.clone(), 2);
+        running.pin(&page_id).unwrap();
+        running.set_int(&page_id, 0, 5, true).unwrap();
+        db.backup_incremental_to(tmp.path().join("incr2"), tmp.path().join("incr1"))
+            .unwrap();
+        running.commit().unwrap();
+
+        // test
+        let chain = ["full", "incr1", "incr2"].map(|name| tmp.path().join(name));
+        backup::restore(&chain, tmp.path().join("restored")).unwrap();
+        let restored = SimpleDB::open(tmp.path().join("restored")).unwrap();
+
+        // verify
+        let mut tx = restored.new_transaction().unwrap();
+        let values: Vec<_> = (0..4)
+            .map(|block_no| {
+                let page_id = PageId::new(tmp.path().join("restored/data"), block_no);
+                tx.pin(&page_id).unwrap();
+                tx.get_int(&page_id, 0).unwrap()
+            })
+            .collect();
+        assert_eq!(values, [2, 3, 1, 4]);
+    }
+
+    #[test]
+    fn restore_deletes_files_deleted_since_the_base_backup() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        set_committed(&db, &PageId::new(tmp.path().join("db/data"), 0), 1);
+        set_committed(&db, &PageId::new(tmp.path().join("db/gone"), 0), 1);
+        db.backup_to(tmp.path().join("full")).unwrap();
+        db.file_manager()
+            .delete(&tmp.path().join("db/gone"))
+            .unwrap();
+        let incremental = db
+            .backup_incremental_to(tmp.path().join("incr"), tmp.path().join("full"))
+            .unwrap();
+
+        // test
+        let chain = ["full", "incr"].map(|name| tmp.path().join(name));
+        backup::restore(&chain, tmp.path().join("restored")).unwrap();
+
+        // verify
+        assert_eq!(incremental.deleted_files, ["gone".to_string()].into());
+        assert!(tmp.path().join("restored/data").exists());
+        assert!(!tmp.path().join("restored/gone").exists());
+        SimpleDB::open(tmp.path().join("restored")).unwrap();
+    }
+
+    #[test]
+    fn restore_rejects_a_chain_with_a_gap() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path().join("db"), 400).unwrap();
+        db.backup_to(tmp.path().join("full")).unwrap();
+        db.backup_incremental_to(tmp.path().join("incr1"), tmp.path().join("full"))
+            .unwrap();
+        db.backup_incremental_to(tmp.path().join("incr2"), tmp.path().join("incr1"))
+            .unwrap();
+
+        // test
+        let chain = ["full", "incr2"].map(|name| tmp.path().join(name));
+        let err = backup::restore(&chain, tmp.path().join("restored")).unwrap_err();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<BackupError>(),
+            Some(BackupError::BrokenChain { .. })
+        ));
+        assert!(!tmp.path().join("restored").exists());
+    }
 }
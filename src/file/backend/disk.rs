@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io,
+    os::{
+        fd::AsRawFd,
+        unix::fs::{FileExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use log::{debug, trace};
+
+use crate::file::backend::StorageBackend;
+
+/// Stores files on the local file system.
+///
+/// Open files are cached to avoid repeated filesystem calls. By default every
+/// file is opened with `O_SYNC` so that a completed write has reached the disk.
+/// A backend created with [`DiskBackend::read_only`] opens files without write
+/// permission and refuses to modify anything.
+///
+/// All reads and writes use positional I/O (`pread`/`pwrite`), so threads sharing
+/// a cached handle never race on a common file cursor. The cache lock is only
+/// held while looking up a handle, not during the I/O itself. Vectored reads and
+/// writes map to `preadv`/`pwritev`, so a range of pages costs a single syscall.
+pub struct DiskBackend {
+    open_files: Mutex<HashMap<PathBuf, Arc<File>>>,
+    /// Whether files are opened with `O_SYNC`.
+    sync_writes: bool,
+    /// Whether files are opened without write permission.
+    read_only: bool,
+    /// Serializes appends, which need the end of the file to stay put between
+    /// measuring it and writing behind it.
+    append_lock: Mutex<()>,
+}
+
+impl Default for DiskBackend {
+    fn default() -> Self {
+        Self::with_sync_writes(true)
+    }
+}
+
+impl DiskBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a disk backend that opens files with `O_SYNC` only if `sync_writes` is set.
+    ///
+    /// Without synchronous writes, data only reaches the disk once [`StorageBackend::sync`]
+    /// is called or the operating system decides to write it back.
+    pub fn with_sync_writes(sync_writes: bool) -> Self {
+        Self {
+            open_files: Mutex::new(HashMap::new()),
+            sync_writes,
+            read_only: false,
+            append_lock: Mutex::new(()),
+        }
+    }
+
+    /// Creates a disk backend that only reads existing files.
+    ///
+    /// Files are opened without write permission, so reading a missing file fails
+    /// with [`io::ErrorKind::NotFound`] instead of creating it. Writes, appends and
+    /// deletes fail with [`io::ErrorKind::PermissionDenied`].
+    pub fn read_only() -> Self {
+        Self {
+            read_only: true,
+            ..Self::with_sync_writes(false)
+        }
+    }
+
+    /// Fails if this backend must not modify files.
+    fn check_writable(&self, path: &Path) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("{:?} is opened read-only", path),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Gets a file handle, using the cache or opening a new file if needed.
+    ///
+    /// Unless disabled, files are opened with `O_SYNC` flag for synchronous I/O
+    /// to ensure data is immediately written to disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file cannot be opened or the cache lock fails.
+    fn get_file(&self, file_path: &Path) -> io::Result<Arc<File>> {
+        debug!("Fetching file {:?}", file_path);
+        let mut open_files = self
+            .open_files
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire file cache lock"))?;
+
+        if let Some(file) = open_files.get(file_path) {
+            trace!("File was already in cache {:?}", file_path);
+            return Ok(file.clone());
+        }
+
+        trace!("File not found in cache. Creating new: {:?}", file_path);
+        let flags = if self.sync_writes { libc::O_SYNC } else { 0 };
+        let file = Arc::new(
+            OpenOptions::new()
+                .custom_flags(flags)
+                .read(true)
+                .write(!self.read_only)
+                .create(!self.read_only)
+                .truncate(false)
+                .open(file_path)?,
+        );
+
+        open_files.insert(file_path.to_path_buf(), file.clone());
+        Ok(file)
+    }
+}
+
+impl StorageBackend for DiskBackend {
+    fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.get_file(path)?.read_exact_at(buf, offset)
+    }
+
+    fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+        self.check_writable(path)?;
+        self.get_file(path)?.write_all_at(buf, offset)
+    }
+
+    fn read_vectored(&self, path: &Path, offset: u64, bufs: &mut [&mut [u8]]) -> io::Result<()> {
+        let file = self.get_file(path)?;
+        let spans: Vec<_> = bufs
+            .iter_mut()
+            .map(|buf| (buf.as_mut_ptr().cast_const(), buf.len()))
+            .collect();
+        let total = spans.iter().map(|(_, len)| len).sum();
+        let mut done = 0;
+        while done < total {
+            let iovecs = remaining_iovecs(&spans, done);
+            // SAFETY: every iovec points into a live, exclusively borrowed buffer of `bufs`.
+            let read = unsafe {
+                libc::preadv(
+                    file.as_raw_fd(),
+                    iovecs.as_ptr(),
+                    iovecs.len() as libc::c_int,
+                    (offset + done as u64) as libc::off_t,
+                )
+            };
+            match read {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n if n > 0 => done += n as usize,
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_vectored(&self, path: &Path, offset: u64, bufs: &[&[u8]]) -> io::Result<()> {
+        self.check_writable(path)?;
+        let file = self.get_file(path)?;
+        let spans: Vec<_> = bufs.iter().map(|buf| (buf.as_ptr(), buf.len())).collect();
+        let total = spans.iter().map(|(_, len)| len).sum();
+        let mut done = 0;
+        while done < total {
+            let iovecs = remaining_iovecs(&spans, done);
+            // SAFETY: every iovec points into a live buffer of `bufs` that pwritev only reads.
+            let written = unsafe {
+                libc::pwritev(
+                    file.as_raw_fd(),
+                    iovecs.as_ptr(),
+                    iovecs.len() as libc::c_int,
+                    (offset + done as u64) as libc::off_t,
+                )
+            };
+            match written {
+                0 => return Err(io::ErrorKind::WriteZero.into()),
+                n if n > 0 => done += n as usize,
+                _ => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+        self.check_writable(path)?;
+        let file = self.get_file(path)?;
+        let _guard = self
+            .append_lock
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire append lock"))?;
+        let offset = file.metadata()?.len();
+        file.write_all_at(buf, offset)?;
+        Ok(offset)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        match std::fs::metadata(path) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        self.get_file(path)?.sync_all()
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        self.check_writable(path)?;
+        self.open_files
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire file cache lock"))?
+            .remove(path);
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check_writable(to)?;
+        {
+            let mut open_files = self
+                .open_files
+                .lock()
+                .map_err(|_| io::Error::other("Failed to acquire file cache lock"))?;
+            open_files.remove(from);
+            open_files.remove(to);
+        }
+        std::fs::rename(from, to)?;
+        // The new directory entry is only durable once the directory itself is synced
+        let dir = match to.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()
+    }
+}
+
+/// The most buffers passed to a single `preadv`/`pwritev` call.
+const MAX_IOVECS: usize = 1024;
+
+/// Builds the iovecs for the part of the buffers after the first `skip` bytes.
+fn remaining_iovecs(spans: &[(*const u8, usize)], skip: usize) -> Vec<libc::iovec> {
+    let mut skip = skip;
+    let mut iovecs = Vec::new();
+    for &(ptr, len) in spans {
+        if skip >= len {
+            skip -= len;
+            continue;
+        }
+        iovecs.push(libc::iovec {
+            // SAFETY: `skip < len`, so the pointer stays inside the buffer.
+            iov_base: unsafe { ptr.add(skip) } as *mut libc::c_void,
+            iov_len: len - skip,
+        });
+        skip = 0;
+        if iovecs.len() == MAX_IOVECS {
+            break;
+        }
+    }
+    iovecs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vectored_io_round_trips_across_buffers() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("data");
+        let backend = DiskBackend::new();
+        let chunks: Vec<Vec<u8>> = (0..2000u16).map(|i| i.to_be_bytes().repeat(3)).collect();
+        let bufs: Vec<&[u8]> = chunks.iter().map(Vec::as_slice).collect();
+
+        // test
+        backend.write_vectored(&path, 10, &bufs).unwrap();
+        let mut read = vec![vec![0; 6]; 2000];
+        let mut read_bufs: Vec<&mut [u8]> = read.iter_mut().map(Vec::as_mut_slice).collect();
+        backend.read_vectored(&path, 10, &mut read_bufs).unwrap();
+
+        // verify
+        assert_eq!(read, chunks);
+        assert_eq!(backend.size(&path).unwrap(), 10 + 2000 * 6);
+    }
+
+    #[test]
+    fn vectored_read_past_the_end_fails() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("data");
+        let backend = DiskBackend::new();
+        backend.write(&path, 0, &[1; 8]).unwrap();
+
+        // test
+        let mut first = [0; 4];
+        let mut second = [0; 8];
+        let result = backend.read_vectored(&path, 0, &mut [&mut first, &mut second]);
+
+        // verify
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
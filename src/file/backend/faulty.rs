@@ -0,0 +1,349 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::file::{PageId, backend::StorageBackend};
+
+/// A fault to inject into a particular write.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fault {
+    /// The write fails with `EIO` and leaves no trace.
+    Fail,
+    /// The backend crashes before the write reaches storage.
+    Crash,
+    /// The given number of leading bytes reach durable storage, then the backend crashes.
+    Tear(usize),
+}
+
+#[derive(Default)]
+struct FaultState {
+    /// The contents of every file touched so far, including writes that were not synced.
+    live: HashMap<PathBuf, Vec<u8>>,
+    /// Number of writes and appends attempted so far.
+    writes: u64,
+    faults: HashMap<u64, Fault>,
+    failing_pages: HashSet<(PathBuf, u64)>,
+    crashed: bool,
+}
+
+/// Wraps another backend and injects storage faults for crash-recovery testing.
+///
+/// The wrapped backend plays the role of the durable medium. Writes and appends
+/// only become visible to it when the file is synced; until then they live in a
+/// volatile layer that is thrown away by a simulated crash. After a crash every
+/// operation fails, and the database can be reopened on the wrapped backend to
+/// see what survived.
+///
+/// Writes and appends are numbered from 1 in the order they are issued. Faults
+/// can be attached to a write number, or to a page that then fails all reads
+/// and writes with `EIO`. Pages are located assuming unsegmented files with the
+/// given slot size, see [`FileManager::slot_size`](crate::file::FileManager::slot_size).
+///
+/// Files are only made durable by [`StorageBackend::sync`], so the database has
+/// to run with [`DurabilityMode::FsyncOnFlush`](crate::file::DurabilityMode::FsyncOnFlush)
+/// for committed data to survive a crash. Deletes take effect immediately.
+///
+/// # Examples
+///
+/// ```
+/// # use rimple::file::backend::{FaultyBackend, MemoryBackend, StorageBackend};
+/// # use std::{path::Path, sync::Arc};
+/// let disk = Arc::new(MemoryBackend::new());
+/// let backend = FaultyBackend::new(disk.clone(), 8);
+/// backend.write(Path::new("data"), 0, b"synced").unwrap();
+/// backend.sync(Path::new("data")).unwrap();
+/// backend.append(Path::new("data"), b"lost").unwrap();
+///
+/// backend.crash();
+/// assert!(backend.size(Path::new("data")).is_err());
+/// assert_eq!(disk.size(Path::new("data")).unwrap(), 6);
+/// ```
+pub struct FaultyBackend {
+    durable: Arc<dyn StorageBackend>,
+    slot_size: u64,
+    state: Mutex<FaultState>,
+}
+
+impl FaultyBackend {
+    /// Creates a fault-free backend on top of `durable`.
+    pub fn new(durable: Arc<dyn StorageBackend>, slot_size: usize) -> Self {
+        Self {
+            durable,
+            slot_size: slot_size as u64,
+            state: Mutex::new(FaultState::default()),
+        }
+    }
+
+    /// Makes the `n`-th write or append fail with `EIO` without touching storage.
+    pub fn fail_write(&self, n: u64) {
+        self.state.lock().unwrap().faults.insert(n, Fault::Fail);
+    }
+
+    /// Crashes the backend instead of performing the `n`-th write or append.
+    pub fn crash_at_write(&self, n: u64) {
+        self.state.lock().unwrap().faults.insert(n, Fault::Crash);
+    }
+
+    /// Tears the `n`-th write or append: only its first `keep` bytes reach durable
+    /// storage before the backend crashes.
+    pub fn tear_write(&self, n: u64, keep: usize) {
+        self.state
+            .lock()
+            .unwrap()
+            .faults
+            .insert(n, Fault::Tear(keep));
+    }
+
+    /// Makes every read and write of the given page fail with `EIO`.
+    pub fn fail_page(&self, page_id: &PageId) {
+        self.state
+            .lock()
+            .unwrap()
+            .failing_pages
+            .insert((page_id.path().to_path_buf(), page_id.block_no()));
+    }
+
+    /// Simulates a crash: all unsynced writes are lost and every further operation fails.
+    pub fn crash(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.crashed = true;
+        state.live.clear();
+    }
+
+    /// Returns whether the backend has crashed.
+    pub fn is_crashed(&self) -> bool {
+        self.state.lock().unwrap().crashed
+    }
+
+    /// Returns the number of writes and appends issued so far, including failed ones.
+    pub fn writes(&self) -> u64 {
+        self.state.lock().unwrap().writes
+    }
+
+    /// Locks the state, failing if the backend has crashed.
+    fn state(&self) -> io::Result<MutexGuard<'_, FaultState>> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire faulty backend lock"))?;
+        if state.crashed {
+            return Err(crashed());
+        }
+        Ok(state)
+    }
+
+    /// Fails with `EIO` if the byte range touches a page marked with [`fail_page`](Self::fail_page).
+    fn check_pages(
+        &self,
+        state: &FaultState,
+        path: &Path,
+        offset: u64,
+        len: usize,
+    ) -> io::Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let first = offset / self.slot_size;
+        let last = (offset + len as u64 - 1) / self.slot_size;
+        let fails = (first..=last).any(|block_no| {
+            state
+                .failing_pages
+                .contains(&(path.to_path_buf(), block_no))
+        });
+        if fails {
+            return Err(io::Error::from_raw_os_error(libc::EIO));
+        }
+        Ok(())
+    }
+
+    /// Counts a write and applies the fault attached to it, if any.
+    fn inject(
+        &self,
+        state: &mut FaultState,
+        path: &Path,
+        offset: u64,
+        buf: &[u8],
+    ) -> io::Result<()> {
+        state.writes += 1;
+        match state.faults.get(&state.writes).copied() {
+            None => Ok(()),
+            Some(Fault::Fail) => Err(io::Error::from_raw_os_error(libc::EIO)),
+            Some(Fault::Crash) => {
+                state.crashed = true;
+                state.live.clear();
+                Err(crashed())
+            }
+            Some(Fault::Tear(keep)) => {
+                self.durable
+                    .write(path, offset, &buf[..keep.min(buf.len())])?;
+                state.crashed = true;
+                state.live.clear();
+                Err(crashed())
+            }
+        }
+    }
+
+    /// Returns the volatile contents of `path`, loading them from durable storage first.
+    fn live_file<'a>(&self, state: &'a mut FaultState, path: &Path) -> io::Result<&'a mut Vec<u8>> {
+        if !state.live.contains_key(path) {
+            let mut content = vec![0; self.durable.size(path)? as usize];
+            self.durable.read(path, 0, &mut content)?;
+            state.live.insert(path.to_path_buf(), content);
+        }
+        Ok(state.live.get_mut(path).expect("file was just loaded"))
+    }
+}
+
+impl StorageBackend for FaultyBackend {
+    fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let mut state = self.state()?;
+        self.check_pages(&state, path, offset, buf.len())?;
+        let content = self.live_file(&mut state, path)?;
+        let start = offset as usize;
+        let end = start + buf.len();
+        if end > content.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.copy_from_slice(&content[start..end]);
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+        let mut state = self.state()?;
+        self.check_pages(&state, path, offset, buf.len())?;
+        self.inject(&mut state, path, offset, buf)?;
+        let content = self.live_file(&mut state, path)?;
+        let start = offset as usize;
+        let end = start + buf.len();
+        if end > content.len() {
+            content.resize(end, 0);
+        }
+        content[start..end].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+        let mut state = self.state()?;
+        let offset = self.live_file(&mut state, path)?.len() as u64;
+        self.check_pages(&state, path, offset, buf.len())?;
+        self.inject(&mut state, path, offset, buf)?;
+        self.live_file(&mut state, path)?.extend_from_slice(buf);
+        Ok(offset)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        let mut state = self.state()?;
+        Ok(self.live_file(&mut state, path)?.len() as u64)
+    }
+
+    fn sync(&self, path: &Path) -> io::Result<()> {
+        let state = self.state()?;
+        if let Some(content) = state.live.get(path) {
+            self.durable.delete(path)?;
+            self.durable.write(path, 0, content)?;
+        }
+        self.durable.sync(path)
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.state()?;
+        state.live.remove(path);
+        self.durable.delete(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.state()?;
+        match state.live.remove(from) {
+            Some(content) => state.live.insert(to.to_path_buf(), content),
+            None => state.live.remove(to),
+        };
+        self.durable.rename(from, to)
+    }
+}
+
+fn crashed() -> io::Error {
+    io::Error::other("Storage crashed")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::file::backend::MemoryBackend;
+
+    use super::*;
+
+    #[test]
+    fn crash_drops_unsynced_writes_only() {
+        // setup
+        let disk = Arc::new(MemoryBackend::new());
+        let backend = FaultyBackend::new(disk.clone(), 4);
+        backend.write(Path::new("data"), 0, b"aaaa").unwrap();
+        backend.sync(Path::new("data")).unwrap();
+        backend.write(Path::new("data"), 0, b"bbbb").unwrap();
+
+        // test
+        backend.crash();
+
+        // verify
+        let mut buf = [0; 4];
+        disk.read(Path::new("data"), 0, &mut buf).unwrap();
+        assert_eq!(&buf, b"aaaa");
+        assert!(backend.read(Path::new("data"), 0, &mut buf).is_err());
+    }
+
+    #[test]
+    fn failed_write_leaves_no_trace() {
+        // setup
+        let backend = FaultyBackend::new(Arc::new(MemoryBackend::new()), 4);
+        backend.fail_write(2);
+
+        // test
+        backend.append(Path::new("data"), b"aaaa").unwrap();
+        let err = backend.append(Path::new("data"), b"bbbb").unwrap_err();
+        backend.append(Path::new("data"), b"cccc").unwrap();
+
+        // verify
+        assert_eq!(err.raw_os_error(), Some(libc::EIO));
+        let mut buf = [0; 8];
+        backend.read(Path::new("data"), 0, &mut buf).unwrap();
+        assert_eq!(&buf, b"aaaacccc");
+        assert_eq!(backend.writes(), 3);
+    }
+
+    #[test]
+    fn torn_write_persists_a_prefix_and_crashes() {
+        // setup
+        let disk = Arc::new(MemoryBackend::new());
+        let backend = FaultyBackend::new(disk.clone(), 4);
+        backend.tear_write(1, 2);
+
+        // test
+        let result = backend.write(Path::new("data"), 4, b"abcd");
+
+        // verify
+        assert!(result.is_err());
+        assert!(backend.is_crashed());
+        let mut buf = [0; 6];
+        disk.read(Path::new("data"), 0, &mut buf).unwrap();
+        assert_eq!(&buf, b"\0\0\0\0ab");
+    }
+
+    #[test]
+    fn failing_page_returns_eio() {
+        // setup
+        let backend = FaultyBackend::new(Arc::new(MemoryBackend::new()), 4);
+        backend.write(Path::new("data"), 0, b"aaaabbbb").unwrap();
+        backend.fail_page(&PageId::new(PathBuf::from("data"), 1));
+
+        // test
+        let mut buf = [0; 4];
+        let first = backend.read(Path::new("data"), 0, &mut buf);
+        let second = backend.read(Path::new("data"), 4, &mut buf);
+
+        // verify
+        assert!(first.is_ok());
+        assert_eq!(second.unwrap_err().raw_os_error(), Some(libc::EIO));
+    }
+}
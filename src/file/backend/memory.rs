@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::file::backend::StorageBackend;
+
+/// Keeps all files in memory.
+///
+/// Nothing survives the backend being dropped, but a backend shared through an
+/// `Arc` can be handed to several [`FileManager`](crate::file::FileManager)s in
+/// turn, e.g. to reopen a database in tests without touching the disk.
+///
+/// # Examples
+///
+/// ```
+/// # use rimple::file::backend::{MemoryBackend, StorageBackend};
+/// # use std::path::Path;
+/// let backend = MemoryBackend::new();
+/// backend.write(Path::new("data"), 2, b"abc").unwrap();
+/// assert_eq!(backend.size(Path::new("data")).unwrap(), 5);
+/// ```
+#[derive(Default)]
+pub struct MemoryBackend {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn files(&self) -> io::Result<MutexGuard<'_, HashMap<PathBuf, Vec<u8>>>> {
+        self.files
+            .lock()
+            .map_err(|_| io::Error::other("Failed to acquire memory backend lock"))
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        let files = self.files()?;
+        let content = files.get(path).map(Vec::as_slice).unwrap_or_default();
+        let start = offset as usize;
+        let end = start + buf.len();
+        if end > content.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.copy_from_slice(&content[start..end]);
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+        let mut files = self.files()?;
+        let content = files.entry(path.to_path_buf()).or_default();
+        let start = offset as usize;
+        let end = start + buf.len();
+        if end > content.len() {
+            content.resize(end, 0);
+        }
+        content[start..end].copy_from_slice(buf);
+        Ok(())
+    }
+
+    fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+        let mut files = self.files()?;
+        let content = files.entry(path.to_path_buf()).or_default();
+        let offset = content.len() as u64;
+        content.extend_from_slice(buf);
+        Ok(offset)
+    }
+
+    fn size(&self, path: &Path) -> io::Result<u64> {
+        Ok(self.files()?.get(path).map_or(0, |c| c.len() as u64))
+    }
+
+    fn sync(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn delete(&self, path: &Path) -> io::Result<()> {
+        self.files()?.remove(path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.files()?;
+        let content = files.remove(from).ok_or(io::ErrorKind::NotFound)?;
+        files.insert(to.to_path_buf(), content);
+        Ok(())
+    }
+}
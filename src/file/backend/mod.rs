@@ -0,0 +1,77 @@
+//! Raw byte storage underneath the [`FileManager`](crate::file::FileManager).
+//!
+//! A [`StorageBackend`] knows nothing about pages, checksums or layouts. It stores
+//! byte ranges in named files and is the single place where the database touches
+//! the operating system. Swapping the backend lets the same engine run on disk,
+//! entirely in memory, or on top of a simulated storage layer.
+
+pub mod disk;
+pub mod faulty;
+pub mod memory;
+
+use std::{io, path::Path};
+
+#[doc(inline)]
+pub use self::disk::DiskBackend;
+#[doc(inline)]
+pub use self::faulty::FaultyBackend;
+#[doc(inline)]
+pub use self::memory::MemoryBackend;
+
+/// Byte-addressed file storage used by the [`FileManager`](crate::file::FileManager).
+///
+/// Files are identified by path and created implicitly by the first write or append.
+/// Implementations must be safe to share between threads.
+pub trait StorageBackend: Send + Sync {
+    /// Fills `buf` with the bytes stored at `offset` in `path`.
+    ///
+    /// Fails with [`io::ErrorKind::UnexpectedEof`] if the file is shorter than
+    /// `offset + buf.len()`.
+    fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Writes `buf` at `offset` in `path`, growing the file if needed.
+    fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()>;
+
+    /// Fills the buffers in order with the contiguous bytes stored at `offset` in `path`.
+    ///
+    /// The default implementation reads each buffer separately. Backends that can
+    /// scatter a single read into several buffers should override it.
+    fn read_vectored(&self, path: &Path, offset: u64, bufs: &mut [&mut [u8]]) -> io::Result<()> {
+        let mut offset = offset;
+        for buf in bufs {
+            self.read(path, offset, buf)?;
+            offset += buf.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffers in order as contiguous bytes at `offset` in `path`.
+    ///
+    /// The default implementation writes each buffer separately. Backends that can
+    /// gather several buffers into a single write should override it.
+    fn write_vectored(&self, path: &Path, offset: u64, bufs: &[&[u8]]) -> io::Result<()> {
+        let mut offset = offset;
+        for buf in bufs {
+            self.write(path, offset, buf)?;
+            offset += buf.len() as u64;
+        }
+        Ok(())
+    }
+
+    /// Writes `buf` at the end of `path` and returns the offset it was written to.
+    fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64>;
+
+    /// Returns the length of `path` in bytes, or 0 if the file does not exist.
+    fn size(&self, path: &Path) -> io::Result<u64>;
+
+    /// Makes all previous writes to `path` durable.
+    fn sync(&self, path: &Path) -> io::Result<()>;
+
+    /// Removes `path`. Deleting a file that does not exist is not an error.
+    fn delete(&self, path: &Path) -> io::Result<()>;
+
+    /// Atomically replaces `to` with `from` and makes the rename durable.
+    ///
+    /// Sync `from` first, otherwise its contents may not survive a crash.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
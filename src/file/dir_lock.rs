@@ -0,0 +1,231 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+/// How a database directory is locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Only a single process may hold the directory, e.g. to write to it.
+    Exclusive,
+    /// Any number of processes may hold the directory at once, as long as
+    /// nobody holds it exclusively. Meant for read-only access.
+    Shared,
+}
+
+/// Errors raised when a database directory is already in use.
+#[derive(thiserror::Error, Debug)]
+pub enum DirLockError {
+    /// Another process holds the directory exclusively.
+    ///
+    /// `pid` is `None` if the holder has not written its PID yet.
+    #[error("Database directory {dir:?} is locked by {}", owner(.pid))]
+    HeldExclusively { dir: PathBuf, pid: Option<u32> },
+
+    /// Other processes hold the directory in shared mode.
+    #[error("Database directory {dir:?} is locked in shared mode by other processes")]
+    HeldShared { dir: PathBuf },
+}
+
+/// An advisory `flock` on a lock file inside a database directory.
+///
+/// The lock keeps other processes from opening the same database for as long as
+/// it is held and is released when dropped, or by the operating system if the
+/// process dies. An exclusive holder writes its PID into the lock file so that
+/// other openers can report who is in the way.
+pub struct DirLock {
+    file: File,
+    mode: LockMode,
+}
+
+impl DirLock {
+    pub const LOCK_FILE: &'static str = "simpledb.lock";
+
+    /// Locks the directory `dir` in the given mode without blocking.
+    ///
+    /// # Errors
+    ///
+    /// * `DirLockError::HeldExclusively` - If another process holds the directory exclusively
+    /// * `DirLockError::HeldShared` - If an exclusive lock is requested while shared holders exist
+    pub fn acquire(dir: impl AsRef<Path>, mode: LockMode) -> anyhow::Result<Self> {
+        let dir = dir.as_ref();
+        let path = dir.join(Self::LOCK_FILE);
+        // Shared holders only read, so they need no write permission unless the
+        // lock file has to be created first
+        let mut file = match mode {
+            LockMode::Shared if path.exists() => File::open(&path)?,
+            _ => OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?,
+        };
+
+        let operation = match mode {
+            LockMode::Exclusive => libc::LOCK_EX,
+            LockMode::Shared => libc::LOCK_SH,
+        };
+        // SAFETY: the descriptor belongs to `file`, which is alive for the whole call
+        if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err.into());
+            }
+            // An exclusive holder writes its PID only after taking the lock, so an
+            // empty lock file does not tell shared holders apart from a new exclusive one
+            if mode == LockMode::Exclusive && is_held_shared(&file)? {
+                return Err(DirLockError::HeldShared {
+                    dir: dir.to_path_buf(),
+                }
+                .into());
+            }
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            return Err(DirLockError::HeldExclusively {
+                dir: dir.to_path_buf(),
+                pid: content.trim().parse().ok(),
+            }
+            .into());
+        }
+
+        // Any PID left in the file belongs to a holder that is gone by now. Shared
+        // holders never write to the file, so clearing it cannot hurt them either.
+        if mode == LockMode::Exclusive {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            write!(file, "{}", std::process::id())?;
+            file.sync_all()?;
+        }
+
+        debug!("Locked database directory {:?} in {:?} mode", dir, mode);
+        Ok(Self { file, mode })
+    }
+
+    /// Returns the mode the directory is locked in.
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+}
+
+/// Returns whether the lock on `file` is only held in shared mode, by probing for a shared lock.
+fn is_held_shared(file: &File) -> io::Result<bool> {
+    // SAFETY: the descriptor belongs to `file`, which is alive for the whole call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) } == 0 {
+        // SAFETY: as above
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() != io::ErrorKind::WouldBlock {
+        return Err(err);
+    }
+    Ok(false)
+}
+
+fn owner(pid: &Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!("process {pid}"),
+        None => "another process".to_string(),
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        if self.mode == LockMode::Exclusive {
+            let _ = self.file.set_len(0);
+        }
+        // Closing the file releases the lock, unlocking explicitly just makes it obvious
+        // SAFETY: the descriptor belongs to `self.file`, which is still open
+        unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn second_exclusive_lock_reports_the_holders_pid() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let _held = DirLock::acquire(tmp.path(), LockMode::Exclusive).unwrap();
+
+        // test
+        let err = DirLock::acquire(tmp.path(), LockMode::Exclusive)
+            .err()
+            .unwrap();
+
+        // verify
+        match err.downcast_ref::<DirLockError>() {
+            Some(DirLockError::HeldExclusively { pid, .. }) => {
+                assert_eq!(*pid, Some(std::process::id()))
+            }
+            other => panic!("Expected an exclusive holder, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shared_locks_can_be_held_together_but_exclude_exclusive_ones() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let _first = DirLock::acquire(tmp.path(), LockMode::Shared).unwrap();
+        let _second = DirLock::acquire(tmp.path(), LockMode::Shared).unwrap();
+
+        // test
+        let err = DirLock::acquire(tmp.path(), LockMode::Exclusive)
+            .err()
+            .unwrap();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<DirLockError>(),
+            Some(DirLockError::HeldShared { .. })
+        ));
+    }
+
+    #[test]
+    fn exclusive_holder_without_a_pid_is_reported_as_unknown() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let held = DirLock::acquire(tmp.path(), LockMode::Exclusive).unwrap();
+        // As if the holder had not written its PID yet
+        held.file.set_len(0).unwrap();
+
+        // test
+        let exclusive = DirLock::acquire(tmp.path(), LockMode::Exclusive)
+            .err()
+            .unwrap();
+        let shared = DirLock::acquire(tmp.path(), LockMode::Shared)
+            .err()
+            .unwrap();
+
+        // verify
+        for err in [exclusive, shared] {
+            assert!(matches!(
+                err.downcast_ref::<DirLockError>(),
+                Some(DirLockError::HeldExclusively { pid: None, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn exclusive_lock_excludes_shared_ones() {
+        let tmp = tempfile::tempdir().unwrap();
+        let _held = DirLock::acquire(tmp.path(), LockMode::Exclusive).unwrap();
+
+        assert!(DirLock::acquire(tmp.path(), LockMode::Shared).is_err());
+    }
+
+    #[test]
+    fn dropping_the_lock_releases_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        drop(DirLock::acquire(tmp.path(), LockMode::Exclusive).unwrap());
+
+        assert!(DirLock::acquire(tmp.path(), LockMode::Exclusive).is_ok());
+    }
+}
@@ -1,31 +1,109 @@
 use std::{
-    collections::HashMap,
-    fs::{File, OpenOptions},
-    io::{self, Read, Seek, Write},
-    os::unix::fs::OpenOptionsExt,
+    collections::{HashMap, HashSet},
+    io,
     path::{Path, PathBuf},
-    sync::Mutex,
+    sync::{
+        Arc, Mutex, RwLock, Weak,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
 };
 
-use log::{debug, trace};
+use log::{debug, trace, warn};
 
-use crate::file::{PageId, Page};
+use crate::file::{
+    DurabilityMode, FileOptions, Page, PageId,
+    backend::{DiskBackend, MemoryBackend, StorageBackend},
+    checksum::{CHECKSUM_SIZE, crc32},
+    stats::{IoCounters, IoStats},
+    temp_file::{PageCache, TEMP_FILE_PREFIX, TempFile},
+};
+
+/// Size of the header in front of every stored page, which holds the page's LSN.
+pub(crate) const PAGE_HEADER_SIZE: usize = 8;
+
+/// Errors raised by the [`FileManager`] when the stored data cannot be trusted.
+#[derive(thiserror::Error, Debug)]
+pub enum FileError {
+    /// The checksum stored with a page does not match its contents, e.g. because of a torn write.
+    #[error(
+        "Checksum mismatch in page {page_id}: stored {stored:#010x}, computed {computed:#010x}"
+    )]
+    ChecksumMismatch {
+        /// The page whose contents failed verification.
+        page_id: PageId,
+        /// The checksum found on disk.
+        stored: u32,
+        /// The checksum computed from the page contents on disk.
+        computed: u32,
+    },
+
+    /// A file does not end on a page boundary, e.g. because an append was interrupted.
+    #[error("File {path:?} ends with a partial page at offset {offset}")]
+    PartialPage {
+        /// The file with the trailing partial page.
+        path: PathBuf,
+        /// The offset at which the partial page starts.
+        offset: u64,
+    },
+
+    /// A file was about to be modified through a read-only file manager.
+    #[error("Cannot modify {path:?}: files are opened read-only")]
+    ReadOnly {
+        /// The file that would have been modified.
+        path: PathBuf,
+    },
+}
 
-/// Manages file I/O operations with caching and page-based access.
+/// Manages page-based access to files stored in a [`StorageBackend`].
 ///
 /// The `FileManager` provides high-level operations for reading and writing
-/// pages to disk files, with automatic file caching and synchronous I/O
-/// to ensure data durability.
+/// pages, while the backend decides where the bytes actually live. By default
+/// files are kept on disk through a [`DiskBackend`].
 ///
 /// # Features
 ///
 /// - **Page-based access**: All I/O operations work with fixed-size pages
-/// - **File caching**: Open files are cached to avoid repeated filesystem calls  
-/// - **Synchronous I/O**: Uses `O_SYNC` flag to ensure data is written to disk
-/// - **Automatic cleanup**: Removes temporary files on initialization
+/// - **Pluggable storage**: Any [`StorageBackend`] can hold the files, e.g. disk or memory
+/// - **Page LSNs**: Every page is stored with a header holding its [LSN](Page::lsn)
+/// - **Checksums**: Every page is stored with a CRC-32 trailer that is verified on read
+/// - **Segmented files**: A logical file can be split into size-capped segment files
+/// - **Configurable durability**: Writes are synchronous, fsynced on flush or not forced
+///   at all, depending on the [`DurabilityMode`]
+/// - **I/O statistics**: Reads, writes and appends are counted per file, see [`FileManager::io_stats`]
+/// - **Temporary files**: Scratch files that skip `O_SYNC` and are deleted when dropped,
+///   see [`FileManager::create_temp_file`]
+/// - **Automatic cleanup**: Removes leftover temporary files on initialization
+///
+/// # On-disk layout
+///
+/// Each page occupies a slot made of an 8-byte header holding the page's
+/// [LSN](Page::lsn), the `page_size` bytes of the page and a 4-byte big-endian
+/// checksum of header and page. With checksums disabled through [`FileOptions`]
+/// the checksum is left out. A slot consisting only of zeros is a page that was
+/// never written and is read back as an empty page.
+///
+/// With [`FileOptions::segment_size`] set, a logical file `name` is stored in the
+/// segment files `name.0`, `name.1`, … that each hold as many whole slots as fit
+/// into the segment size. Page `n` lives in segment `n / pages_per_segment`.
 pub struct FileManager {
     page_size: usize,
-    open_files: Mutex<HashMap<PathBuf, File>>,
+    options: FileOptions,
+    backend: Arc<dyn StorageBackend>,
+    /// Holds temporary files, which never need to be durable.
+    temp_backend: Arc<dyn StorageBackend>,
+    /// The directory temporary files are created in.
+    dir: PathBuf,
+    next_temp_id: AtomicU64,
+    /// Files written since their last `fsync` under [`DurabilityMode::FsyncOnFlush`].
+    unsynced_files: Mutex<HashSet<PathBuf>>,
+    /// Serializes appends per logical file, so that no two appends measure the
+    /// same end of file and hand out the same page.
+    append_locks: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>,
+    /// The cache holding pages of the files, which forgets the pages of temporary
+    /// files when they are dropped.
+    page_cache: RwLock<Option<Weak<dyn PageCache>>>,
+    io_counters: IoCounters,
 }
 
 impl FileManager {
@@ -44,8 +122,44 @@ impl FileManager {
     ///
     /// Returns an I/O error if the directory cannot be created or accessed.
     pub fn new(path: impl AsRef<Path>, page_size: usize) -> io::Result<Self> {
+        Self::with_options(path, page_size, FileOptions::default())
+    }
+
+    /// Creates a new file manager with explicit [`FileOptions`].
+    ///
+    /// With [`FileOptions::read_only`] set, the directory must exist already and
+    /// temporary files are kept in memory, so nothing in the directory changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The directory path where files will be managed
+    /// * `page_size` - The fixed size of each page in bytes
+    /// * `options` - Layout and access tunables
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the directory cannot be created or accessed.
+    pub fn with_options(
+        path: impl AsRef<Path>,
+        page_size: usize,
+        options: FileOptions,
+    ) -> io::Result<Self> {
         debug!("Start to initialize file manager");
         let path_buf = path.as_ref().to_path_buf();
+        if options.read_only {
+            if !path_buf.is_dir() {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Database directory {:?} does not exist", path_buf),
+                ));
+            }
+            debug!("File manager initialization done (read-only)");
+            return Ok(Self {
+                dir: path_buf,
+                temp_backend: Arc::new(MemoryBackend::new()),
+                ..Self::with_backend(Arc::new(DiskBackend::read_only()), page_size, options)
+            });
+        }
         let is_new = !path_buf.exists();
 
         if is_new {
@@ -54,57 +168,59 @@ impl FileManager {
 
         trace!("Cleaning up temporary files in directory: {:?}", path_buf);
         for file in path_buf.read_dir()?.flatten() {
-            let file_path = file.path();
-            if file_path.to_str().is_some_and(|s| s.starts_with("temp")) {
-                std::fs::remove_file(file_path)?;
+            if is_temp_file_name(&file.file_name()) {
+                std::fs::remove_file(file.path())?;
             }
         }
 
         debug!("File manager initialization done");
+        let backend = DiskBackend::with_sync_writes(options.durability == DurabilityMode::Sync);
         Ok(Self {
-            page_size,
-            open_files: Mutex::new(HashMap::new()),
+            dir: path_buf,
+            temp_backend: Arc::new(DiskBackend::with_sync_writes(false)),
+            ..Self::with_backend(Arc::new(backend), page_size, options)
         })
     }
 
-    /// Gets a file handle, using the cache or opening a new file if needed.
+    /// Creates a new file manager on top of an arbitrary storage backend.
     ///
-    /// Files are opened with `O_SYNC` flag for synchronous I/O to ensure
-    /// data is immediately written to disk.
+    /// Temporary files are created at the root of the backend and share it with
+    /// all other files.
     ///
     /// # Arguments
     ///
-    /// * `file_path` - Path to the file to open
-    ///
-    /// # Returns
+    /// * `backend` - Where the bytes of all files are stored
+    /// * `page_size` - The fixed size of each page in bytes
+    /// * `options` - Layout and access tunables
     ///
-    /// Returns a cloned file handle on success.
+    /// # Examples
     ///
-    /// # Errors
-    ///
-    /// Returns an I/O error if the file cannot be opened or the cache lock fails.
-    pub(crate) fn get_file(&self, file_path: &Path) -> anyhow::Result<File> {
-        debug!("Fetching file {:?}", file_path);
-        let mut open_files = self
-            .open_files
-            .lock()
-            .map_err(|e| anyhow::anyhow!("Failed to acquire file cache lock: {}", e))?;
-
-        if let Some(file) = open_files.get(file_path) {
-            trace!("File was already in cache {:?}", file_path);
-            return Ok(file.try_clone()?);
+    /// ```
+    /// # use rimple::file::{FileManager, FileOptions, Page, PageId};
+    /// # use rimple::file::backend::MemoryBackend;
+    /// # use std::sync::Arc;
+    /// let fm = FileManager::with_backend(Arc::new(MemoryBackend::new()), 64, FileOptions::default());
+    /// let page_id = fm.append_page("data".as_ref()).unwrap();
+    /// fm.write(&page_id, &Page::with_bytes(&[1; 64])).unwrap();
+    /// assert_eq!(fm.size("data".as_ref()).unwrap(), 1);
+    /// ```
+    pub fn with_backend(
+        backend: Arc<dyn StorageBackend>,
+        page_size: usize,
+        options: FileOptions,
+    ) -> Self {
+        Self {
+            page_size,
+            options,
+            temp_backend: backend.clone(),
+            backend,
+            dir: PathBuf::new(),
+            next_temp_id: AtomicU64::new(0),
+            unsynced_files: Mutex::new(HashSet::new()),
+            append_locks: Mutex::new(HashMap::new()),
+            page_cache: RwLock::new(None),
+            io_counters: IoCounters::default(),
         }
-
-        trace!("File not found in cache. Creating new: {:?}", file_path);
-        let file = OpenOptions::new()
-            .custom_flags(libc::O_SYNC)
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(file_path)?;
-
-        open_files.insert(file_path.to_path_buf(), file.try_clone()?);
-        Ok(file)
     }
 
     /// Reads a page from the specified page.
@@ -116,14 +232,116 @@ impl FileManager {
     ///
     /// # Errors
     ///
-    /// Returns an I/O error if the file cannot be accessed or read.
+    /// Returns an I/O error if the file cannot be accessed or read, and
+    /// [`FileError::ChecksumMismatch`] if the page fails verification. The
+    /// contents of `page` are left untouched when verification fails.
     pub fn read(&self, page_id: &PageId, page: &mut Page) -> anyhow::Result<()> {
-        let mut file = self.get_file(page_id.path())?;
-        let offset = page_id.block_no() * self.page_size as u64;
-        file.seek(std::io::SeekFrom::Start(offset))?;
+        let (path, offset) = self.locate(page_id.path(), page_id.block_no());
+        let mut slot = vec![0; self.slot_size()];
+        let start = Instant::now();
+        match self.backend_for(&path).read(&path, offset, &mut slot) {
+            // A page in the hole of a short segment, which reads like a page never written
+            Err(e)
+                if e.kind() == io::ErrorKind::UnexpectedEof
+                    && page_id.block_no() < self.size(page_id.path())? =>
+            {
+                slot.fill(0)
+            }
+            result => result?,
+        }
+        self.io_counters
+            .record_read(page_id.path(), 1, slot.len(), start.elapsed());
+        let (data, trailer) = slot.split_at(PAGE_HEADER_SIZE + self.page_size);
+        if self.options.checksums {
+            verify(page_id, data, trailer)?;
+        }
 
-        let buf = page.content_mut();
-        file.read_exact(buf)?;
+        let (header, content) = data.split_at(PAGE_HEADER_SIZE);
+        page.content_mut().copy_from_slice(content);
+        page.set_lsn(u64::from_be_bytes(header.try_into()?));
+        Ok(())
+    }
+
+    /// Reads a page without verifying its checksum, e.g. to salvage what a torn
+    /// write left of it.
+    pub(crate) fn read_unverified(&self, page_id: &PageId, page: &mut Page) -> anyhow::Result<()> {
+        let (path, offset) = self.locate(page_id.path(), page_id.block_no());
+        let mut slot = vec![0; self.slot_size()];
+        self.backend_for(&path).read(&path, offset, &mut slot)?;
+        let (header, content) =
+            slot[..PAGE_HEADER_SIZE + self.page_size].split_at(PAGE_HEADER_SIZE);
+        page.content_mut().copy_from_slice(content);
+        page.set_lsn(u64::from_be_bytes(header.try_into()?));
+        Ok(())
+    }
+
+    /// Rewrites a page that fails verification, e.g. because a crash tore its last write.
+    ///
+    /// The slot keeps whatever it holds now and only gets a new checksum, so the page
+    /// is a mix of its old and new contents. Recovery repairs pages this way before it
+    /// undoes the changes of unfinished transactions on them.
+    pub(crate) fn repair(&self, page_id: &PageId) -> anyhow::Result<()> {
+        let mut page = Page::with_size(self.page_size);
+        self.read_unverified(page_id, &mut page)?;
+        self.write(page_id, &page)
+    }
+
+    /// Reads consecutive pages of a file, starting at `first_block`, into `pages`.
+    ///
+    /// Pages within the same segment are fetched with a single vectored read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file cannot be read, e.g. because the range extends
+    /// beyond its end, and [`FileError::ChecksumMismatch`] if a page fails verification.
+    /// The contents of `pages` are unspecified after an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::{FileManager, FileOptions, Page};
+    /// # use rimple::file::backend::MemoryBackend;
+    /// # use std::sync::Arc;
+    /// let fm = FileManager::with_backend(Arc::new(MemoryBackend::new()), 64, FileOptions::default());
+    /// let pages = vec![Page::with_bytes(&[1; 64]), Page::with_bytes(&[2; 64])];
+    /// fm.write_many("data".as_ref(), 0, &pages).unwrap();
+    ///
+    /// let mut read = vec![Page::with_size(64), Page::with_size(64)];
+    /// fm.read_many("data".as_ref(), 0, &mut read).unwrap();
+    /// assert_eq!(read[1].content(), &[2; 64]);
+    /// ```
+    pub fn read_many(
+        &self,
+        path: &Path,
+        first_block: u64,
+        pages: &mut [Page],
+    ) -> anyhow::Result<()> {
+        let mut done = 0;
+        while done < pages.len() {
+            let block_no = first_block + done as u64;
+            let count = self.pages_left_in_segment(block_no).min(pages.len() - done);
+            self.read_run(path, block_no, &mut pages[done..done + count])?;
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Writes `pages` to consecutive pages of a file, starting at `first_block`.
+    ///
+    /// Pages within the same segment are stored with a single vectored write.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file cannot be accessed or written.
+    pub fn write_many(&self, path: &Path, first_block: u64, pages: &[Page]) -> anyhow::Result<()> {
+        self.check_writable(path)?;
+        let mut done = 0;
+        while done < pages.len() {
+            let block_no = first_block + done as u64;
+            let count = self.pages_left_in_segment(block_no).min(pages.len() - done);
+            self.write_run(path, block_no, &pages[done..done + count])?;
+            done += count;
+        }
         Ok(())
     }
 
@@ -138,12 +356,14 @@ impl FileManager {
     ///
     /// Returns an I/O error if the file cannot be accessed or written.
     pub fn write(&self, page_id: &PageId, page: &Page) -> anyhow::Result<()> {
-        let mut file = self.get_file(page_id.path())?;
-        let offset = page_id.block_no() * self.page_size as u64;
-        file.seek(std::io::SeekFrom::Start(offset))?;
-
-        let buf = page.content();
-        file.write_all(buf)?;
+        self.check_writable(page_id.path())?;
+        let (path, offset) = self.locate(page_id.path(), page_id.block_no());
+        let slot = self.encode(page);
+        let start = Instant::now();
+        self.backend_for(&path).write(&path, offset, &slot)?;
+        self.io_counters
+            .record_write(page_id.path(), 1, slot.len(), start.elapsed());
+        self.track_unsynced(&path);
         Ok(())
     }
 
@@ -157,14 +377,114 @@ impl FileManager {
     ///
     /// Returns the `PageId` of the newly created page.
     ///
+    /// A partial page at the end of the file is left over from an interrupted append.
+    /// Its page was never handed out, so the new page replaces it.
+    ///
     /// # Errors
     ///
-    /// Returns an I/O error if the file cannot be accessed or extended.
+    /// Returns an I/O error if the file cannot be accessed or extended, and
+    /// [`FileError::PartialPage`] without changing the file if it ends before the
+    /// new page was expected to start.
     pub fn append_page(&self, path: &Path) -> anyhow::Result<PageId> {
-        let new_page_id = PageId::new(path.to_path_buf(), self.size(path)?);
-        self.write(&new_page_id, &Page::with_size(self.page_size))?;
+        self.check_writable(path)?;
+        let append_lock = self
+            .append_locks
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone();
+        let _guard = append_lock.lock().unwrap();
+        let block_no = self.size(path)?;
+        let (segment, expected_offset) = self.locate(path, block_no);
+        let slot = self.encode(&Page::with_size(self.page_size));
+        let backend = self.backend_for(&segment);
+        let segment_size = backend.size(&segment)?;
+        if segment_size < expected_offset {
+            return Err(FileError::PartialPage {
+                path: segment,
+                offset: segment_size,
+            }
+            .into());
+        }
+        let start = Instant::now();
+        if segment_size > expected_offset {
+            warn!("Replacing the partial page at offset {expected_offset} of {segment:?}");
+            backend.write(&segment, expected_offset, &slot)?;
+        } else {
+            backend.append(&segment, &slot)?;
+        }
+        self.io_counters
+            .record_append(path, 1, slot.len(), start.elapsed());
+        self.track_unsynced(&segment);
 
-        Ok(new_page_id)
+        Ok(PageId::new(path.to_path_buf(), block_no))
+    }
+
+    /// Forces all writes to the specified file to stable storage.
+    ///
+    /// This always issues an `fsync`, regardless of the configured [`DurabilityMode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the file cannot be synced.
+    pub fn sync(&self, path: &Path) -> anyhow::Result<()> {
+        let segments = match self.pages_per_segment() {
+            Some(pages) => {
+                let size = self.size(path)?;
+                // The first segment of an empty file may not exist, and syncing would create it
+                let first = self.segment_path(path, 0);
+                if size == 0 && self.backend_for(&first).size(&first)? == 0 {
+                    return Ok(());
+                }
+                size.div_ceil(pages).max(1)
+            }
+            None => 1,
+        };
+        for segment in 0..segments {
+            let segment_path = self.segment_path(path, segment);
+            self.backend_for(&segment_path).sync(&segment_path)?;
+            self.unsynced_files.lock().unwrap().remove(&segment_path);
+        }
+        Ok(())
+    }
+
+    /// Deletes the file, or every segment of it.
+    ///
+    /// Cached pages of the file must have left the buffer pool, otherwise writing
+    /// them back creates the file again.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`FileError::ReadOnly`] if the file manager is read-only.
+    pub fn delete(&self, path: &Path) -> anyhow::Result<()> {
+        self.check_writable(path)?;
+        let segments = match self.pages_per_segment() {
+            Some(pages) => self.size(path)?.div_ceil(pages).max(1),
+            None => 1,
+        };
+        for segment in 0..segments {
+            let segment_path = self.segment_path(path, segment);
+            self.backend_for(&segment_path).delete(&segment_path)?;
+            self.unsynced_files.lock().unwrap().remove(&segment_path);
+        }
+        Ok(())
+    }
+
+    /// Makes writes durable as required at a flush point of the given durability mode.
+    ///
+    /// Under [`DurabilityMode::FsyncOnFlush`] this syncs every file written since its
+    /// last sync, which includes data pages evicted earlier by the buffer pool. The
+    /// other modes need no extra work.
+    pub(crate) fn sync_on_flush(&self) -> anyhow::Result<()> {
+        if self.options.durability != DurabilityMode::FsyncOnFlush {
+            return Ok(());
+        }
+        let paths: Vec<PathBuf> = self.unsynced_files.lock().unwrap().drain().collect();
+        for path in paths {
+            self.backend.sync(&path)?;
+        }
+        Ok(())
     }
 
     /// Returns the configured page size.
@@ -172,28 +492,334 @@ impl FileManager {
         self.page_size
     }
 
+    /// Returns the number of bytes a single page occupies in storage, including its
+    /// header and checksum.
+    pub fn slot_size(&self) -> usize {
+        if self.options.checksums {
+            PAGE_HEADER_SIZE + self.page_size + CHECKSUM_SIZE
+        } else {
+            PAGE_HEADER_SIZE + self.page_size
+        }
+    }
+
+    /// Creates a new, uniquely named temporary file.
+    ///
+    /// Temporary files hold scratch data such as sort runs or materialized
+    /// intermediate results. They are written without `O_SYNC`, never synced on
+    /// flush, and transactions do not log changes to them. The file is deleted
+    /// when the returned handle is dropped, and any temporary file left behind by
+    /// a crash is removed the next time a file manager opens the directory.
+    ///
+    /// The file is created lazily by its first write or append. Pages of the file
+    /// must be unpinned before the handle is dropped, so the buffer pool can forget
+    /// them without writing them back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::{FileManager, FileOptions};
+    /// # use rimple::file::backend::MemoryBackend;
+    /// # use std::sync::Arc;
+    /// let fm = FileManager::with_backend(Arc::new(MemoryBackend::new()), 64, FileOptions::default());
+    /// let temp = fm.create_temp_file();
+    /// fm.append_page(temp.path()).unwrap();
+    /// assert!(fm.is_temp_file(temp.path()));
+    ///
+    /// let path = temp.path().to_path_buf();
+    /// drop(temp);
+    /// assert_eq!(fm.size(&path).unwrap(), 0);
+    /// ```
+    pub fn create_temp_file(&self) -> TempFile {
+        let id = self.next_temp_id.fetch_add(1, Ordering::Relaxed);
+        let name = format!("{}{}-{}", TEMP_FILE_PREFIX, std::process::id(), id);
+        TempFile::new(
+            self.dir.join(name),
+            self.temp_backend.clone(),
+            self.options.segment_size.is_some(),
+            self.page_cache.read().unwrap().clone(),
+        )
+    }
+
+    /// Sets the cache that holds pages of the files, so that temporary files
+    /// created afterwards take their cached pages along when dropped.
+    pub(crate) fn set_page_cache(&self, page_cache: Weak<dyn PageCache>) {
+        *self.page_cache.write().unwrap() = Some(page_cache);
+    }
+
+    /// Returns whether `path` names a temporary file, or a segment of one.
+    pub fn is_temp_file(&self, path: &Path) -> bool {
+        path.parent().unwrap_or(Path::new("")) == self.dir
+            && path.file_name().is_some_and(is_temp_file_name)
+    }
+
+    /// Returns a snapshot of the I/O performed since creation or the last [`reset_io_stats`](Self::reset_io_stats).
+    ///
+    /// Counters are kept per logical file, so the segments of a segmented file
+    /// are reported together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::{FileManager, FileOptions, Page};
+    /// # use rimple::file::backend::MemoryBackend;
+    /// # use std::sync::Arc;
+    /// let fm = FileManager::with_backend(Arc::new(MemoryBackend::new()), 64, FileOptions::default());
+    /// let page_id = fm.append_page("data".as_ref()).unwrap();
+    /// fm.read(&page_id, &mut Page::with_size(64)).unwrap();
+    ///
+    /// let stats = fm.io_stats();
+    /// assert_eq!(stats.total.appends, 1);
+    /// assert_eq!(stats.file("data".as_ref()).reads, 1);
+    /// ```
+    pub fn io_stats(&self) -> IoStats {
+        self.io_counters.snapshot()
+    }
+
+    /// Sets all I/O counters back to zero.
+    pub fn reset_io_stats(&self) {
+        self.io_counters.reset();
+    }
+
+    /// Returns the storage backend holding the files.
+    pub(crate) fn backend(&self) -> &dyn StorageBackend {
+        self.backend.as_ref()
+    }
+
+    /// Returns the options this file manager was created with.
+    pub fn options(&self) -> &FileOptions {
+        &self.options
+    }
+
     /// Returns the number of pages in the specified file.
     ///
+    /// For segmented files the size is determined by the last segment that holds
+    /// a page. Every segment before it counts as full, even if it is shorter, the
+    /// same way a write past the end of an unsegmented file leaves a hole.
+    ///
     /// # Arguments
     ///
     /// * `path` - The file to measure
     ///
     /// # Returns
     ///
-    /// The number of pages, or 0 if the file cannot be accessed.
+    /// The number of pages, or 0 if the file does not exist.
     pub fn size(&self, path: &Path) -> anyhow::Result<u64> {
-        self.get_file(path).and_then(|f| {
-            f.metadata()
-                .map_err(|e| anyhow::anyhow!("Failed to get meta data of file: {}", e))
-                .map(|m| m.len() / self.page_size as u64)
-        })
+        let slot_size = self.slot_size() as u64;
+        let Some(pages_per_segment) = self.pages_per_segment() else {
+            return Ok(self.backend_for(path).size(path)? / slot_size);
+        };
+
+        let mut pages = 0;
+        for segment in 0.. {
+            let segment_path = self.segment_path(path, segment);
+            let segment_pages = self.backend_for(&segment_path).size(&segment_path)? / slot_size;
+            if segment_pages == 0 {
+                break;
+            }
+            pages = segment * pages_per_segment + segment_pages.min(pages_per_segment);
+        }
+        Ok(pages)
+    }
+
+    /// Remembers that `path` has writes that are not durable yet, if that matters.
+    fn track_unsynced(&self, path: &Path) {
+        if self.options.durability == DurabilityMode::FsyncOnFlush && !self.is_temp_file(path) {
+            self.unsynced_files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf());
+        }
+    }
+
+    /// Reads pages that all live in the same segment.
+    fn read_run(&self, path: &Path, first_block: u64, pages: &mut [Page]) -> anyhow::Result<()> {
+        let (segment, offset) = self.locate(path, first_block);
+        let mut headers = vec![[0; PAGE_HEADER_SIZE]; pages.len()];
+        let mut trailers = vec![[0; CHECKSUM_SIZE]; pages.len()];
+        let mut bufs: Vec<&mut [u8]> = Vec::with_capacity(3 * pages.len());
+        for ((page, header), trailer) in pages
+            .iter_mut()
+            .zip(headers.iter_mut())
+            .zip(trailers.iter_mut())
+        {
+            bufs.push(header);
+            bufs.push(page.content_mut());
+            if self.options.checksums {
+                bufs.push(trailer);
+            }
+        }
+
+        let start = Instant::now();
+        match self
+            .backend_for(&segment)
+            .read_vectored(&segment, offset, &mut bufs)
+        {
+            // The run reaches into the hole of a short segment, so read it page by page
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                drop(bufs);
+                for (i, page) in pages.iter_mut().enumerate() {
+                    self.read(
+                        &PageId::new(path.to_path_buf(), first_block + i as u64),
+                        page,
+                    )?;
+                }
+                return Ok(());
+            }
+            result => result?,
+        }
+        self.io_counters.record_read(
+            path,
+            pages.len(),
+            pages.len() * self.slot_size(),
+            start.elapsed(),
+        );
+
+        for (i, ((page, header), trailer)) in
+            pages.iter_mut().zip(&headers).zip(&trailers).enumerate()
+        {
+            if self.options.checksums {
+                let page_id = PageId::new(path.to_path_buf(), first_block + i as u64);
+                verify(&page_id, &[header, page.content()].concat(), trailer)?;
+            }
+            page.set_lsn(u64::from_be_bytes(*header));
+        }
+        Ok(())
+    }
+
+    /// Writes pages that all live in the same segment.
+    fn write_run(&self, path: &Path, first_block: u64, pages: &[Page]) -> anyhow::Result<()> {
+        let (segment, offset) = self.locate(path, first_block);
+        let headers: Vec<_> = pages.iter().map(|page| page.lsn().to_be_bytes()).collect();
+        let trailers: Vec<_> = pages
+            .iter()
+            .zip(&headers)
+            .map(|(page, header)| crc32(&[header, page.content()].concat()).to_be_bytes())
+            .collect();
+        let mut bufs: Vec<&[u8]> = Vec::with_capacity(3 * pages.len());
+        for ((page, header), trailer) in pages.iter().zip(&headers).zip(&trailers) {
+            bufs.push(header);
+            bufs.push(page.content());
+            if self.options.checksums {
+                bufs.push(trailer);
+            }
+        }
+
+        let start = Instant::now();
+        self.backend_for(&segment)
+            .write_vectored(&segment, offset, &bufs)?;
+        self.io_counters.record_write(
+            path,
+            pages.len(),
+            pages.len() * self.slot_size(),
+            start.elapsed(),
+        );
+        self.track_unsynced(&segment);
+        Ok(())
+    }
+
+    /// Returns how many pages from `block_no` on fit into the segment holding it.
+    fn pages_left_in_segment(&self, block_no: u64) -> usize {
+        match self.pages_per_segment() {
+            Some(pages) => (pages - block_no % pages) as usize,
+            None => usize::MAX,
+        }
+    }
+
+    /// Fails with [`FileError::ReadOnly`] if `path` must not be modified.
+    ///
+    /// Temporary files stay writable, they never touch the directory.
+    fn check_writable(&self, path: &Path) -> anyhow::Result<()> {
+        if self.options.read_only && !self.is_temp_file(path) {
+            return Err(FileError::ReadOnly {
+                path: path.to_path_buf(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Returns the backend holding `path`, which differs for temporary files.
+    fn backend_for(&self, path: &Path) -> &dyn StorageBackend {
+        if self.is_temp_file(path) {
+            self.temp_backend.as_ref()
+        } else {
+            self.backend.as_ref()
+        }
+    }
+
+    /// Returns how many pages fit into one segment, or `None` if files are not segmented.
+    fn pages_per_segment(&self) -> Option<u64> {
+        self.options
+            .segment_size
+            .map(|bytes| (bytes / self.slot_size() as u64).max(1))
+    }
+
+    /// Returns the OS file holding the given segment of a logical file.
+    fn segment_path(&self, path: &Path, segment: u64) -> PathBuf {
+        if self.options.segment_size.is_none() {
+            return path.to_path_buf();
+        }
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", segment));
+        name.into()
+    }
+
+    /// Maps a page of a logical file to the OS file and byte offset it is stored at.
+    fn locate(&self, path: &Path, block_no: u64) -> (PathBuf, u64) {
+        let slot_size = self.slot_size() as u64;
+        match self.pages_per_segment() {
+            Some(pages) => (
+                self.segment_path(path, block_no / pages),
+                (block_no % pages) * slot_size,
+            ),
+            None => (path.to_path_buf(), block_no * slot_size),
+        }
+    }
+
+    /// Serializes a page into its on-disk slot: header, content and, if enabled, checksum.
+    ///
+    /// The whole slot is written with a single call so its parts cannot be split apart.
+    fn encode(&self, page: &Page) -> Vec<u8> {
+        let mut slot = Vec::with_capacity(self.slot_size());
+        slot.extend_from_slice(&page.lsn().to_be_bytes());
+        slot.extend_from_slice(page.content());
+        if self.options.checksums {
+            slot.extend_from_slice(&crc32(&slot).to_be_bytes());
+        }
+        slot
     }
 }
 
+/// Returns whether a file name has the prefix reserved for temporary files.
+fn is_temp_file_name(name: &std::ffi::OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|name| name.starts_with(TEMP_FILE_PREFIX))
+}
+
+/// Checks the header and content of a page read from storage against the checksum
+/// stored behind them.
+///
+/// A slot of only zeros is a page that was never written and passes.
+fn verify(page_id: &PageId, data: &[u8], trailer: &[u8]) -> anyhow::Result<()> {
+    let stored = u32::from_be_bytes(trailer.try_into()?);
+    let computed = crc32(data);
+    if stored != computed && (stored != 0 || data.iter().any(|&b| b != 0)) {
+        return Err(FileError::ChecksumMismatch {
+            page_id: page_id.clone(),
+            stored,
+            computed,
+        }
+        .into());
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 pub(crate) mod test {
     use tempfile::TempDir;
 
+    use crate::file::backend::MemoryBackend;
+
     use super::*;
 
     pub(crate) fn temp_file_manager(page_size: usize) -> (FileManager, TempDir) {
@@ -204,6 +830,14 @@ pub(crate) mod test {
         )
     }
 
+    pub(crate) fn memory_file_manager(page_size: usize) -> (FileManager, Arc<MemoryBackend>) {
+        let backend = Arc::new(MemoryBackend::new());
+        (
+            FileManager::with_backend(backend.clone(), page_size, FileOptions::default()),
+            backend,
+        )
+    }
+
     #[test]
     fn create_a_new_database_directory() {
         // setup
@@ -213,6 +847,69 @@ pub(crate) mod test {
         assert_eq!(fm.page_size(), 4096);
     }
 
+    #[test]
+    fn io_stats_count_per_file_and_in_total() {
+        // setup
+        let (fm, _) = memory_file_manager(64);
+        let data = fm.append_page("data".as_ref()).unwrap();
+        fm.append_page("index".as_ref()).unwrap();
+        fm.write(&data, &Page::with_bytes(&[7; 64])).unwrap();
+
+        // test
+        fm.read(&data, &mut Page::with_size(64)).unwrap();
+        fm.read(&data, &mut Page::with_size(64)).unwrap();
+        let stats = fm.io_stats();
+        fm.reset_io_stats();
+
+        // verify
+        let slot = (PAGE_HEADER_SIZE + 64 + CHECKSUM_SIZE) as u64;
+        let data_stats = stats.file("data".as_ref());
+        assert_eq!(data_stats.reads, 2);
+        assert_eq!(data_stats.writes, 1);
+        assert_eq!(data_stats.appends, 1);
+        assert_eq!(data_stats.bytes_read, 2 * slot);
+        assert_eq!(data_stats.bytes_written, 2 * slot);
+        assert_eq!(stats.total.appends, 2);
+        assert_eq!(stats.total.bytes_written, 3 * slot);
+        assert_eq!(fm.io_stats(), IoStats::default());
+    }
+
+    #[test]
+    fn temp_files_are_uniquely_named_and_deleted_on_drop() {
+        // setup
+        let (fm, tmp) = temp_file_manager(64);
+        let first = fm.create_temp_file();
+        let second = fm.create_temp_file();
+
+        // test
+        fm.append_page(first.path()).unwrap();
+        fm.append_page(second.path()).unwrap();
+        let first_path = first.path().to_path_buf();
+        drop(first);
+
+        // verify
+        assert_ne!(first_path, second.path());
+        assert_eq!(second.path().parent(), Some(tmp.path()));
+        assert!(!first_path.exists());
+        assert!(second.path().exists());
+        assert!(fm.is_temp_file(second.path()));
+    }
+
+    #[test]
+    fn startup_removes_leftover_temp_files_only() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("temp-1-0"), [0; 68]).unwrap();
+        std::fs::write(tmp.path().join("template"), [0; 68]).unwrap();
+
+        // test
+        FileManager::new(&tmp, 64).unwrap();
+
+        // verify
+        assert!(!tmp.path().join("temp-1-0").exists());
+        assert!(tmp.path().join("template").exists());
+    }
+
     #[test]
     fn writing_a_single_page_and_reading_it_is_consistent() {
         // setup
@@ -252,6 +949,413 @@ pub(crate) mod test {
         assert_eq!(fm.size(&path).unwrap(), 2);
     }
 
+    #[test]
+    fn corrupted_page_is_reported_with_its_page_id() {
+        // setup
+        let (fm, tmp) = temp_file_manager(128);
+        let path = tmp.path().join("corrupt");
+        let mut page = Page::with_size(128);
+        page.set_string(0, "important").unwrap();
+        fm.write(&PageId::new(path.clone(), 0), &page).unwrap();
+        fm.write(&PageId::new(path.clone(), 1), &page).unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[fm.slot_size() + 10] ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+
+        // test
+        let mut read_page = Page::with_size(128);
+        fm.read(&PageId::new(path.clone(), 0), &mut read_page)
+            .unwrap();
+        let err = fm
+            .read(&PageId::new(path.clone(), 1), &mut read_page)
+            .unwrap_err();
+
+        // verify
+        match err.downcast_ref::<FileError>() {
+            Some(FileError::ChecksumMismatch { page_id, .. }) => {
+                assert_eq!(page_id, &PageId::new(path, 1))
+            }
+            other => panic!("Expected a checksum mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn never_written_pages_read_as_empty() {
+        // setup
+        let (fm, tmp) = temp_file_manager(128);
+        let path = tmp.path().join("sparse");
+        fm.write(&PageId::new(path.clone(), 2), &Page::with_size(128))
+            .unwrap();
+
+        // test
+        let mut page = Page::with_bytes(&[1; 128]);
+        fm.read(&PageId::new(path, 0), &mut page).unwrap();
+
+        // verify
+        assert!(page.content().iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn disabling_checksums_stores_pages_without_trailer() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let options = FileOptions {
+            checksums: false,
+            ..FileOptions::default()
+        };
+        let fm = FileManager::with_options(&tmp, 128, options).unwrap();
+        let path = tmp.path().join("bare");
+
+        // test
+        fm.write(&PageId::new(path.clone(), 0), &Page::with_bytes(&[7; 128]))
+            .unwrap();
+
+        // verify
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len(),
+            (PAGE_HEADER_SIZE + 128) as u64
+        );
+        assert_eq!(fm.size(&path).unwrap(), 1);
+    }
+
+    #[test]
+    fn page_lsn_round_trips_through_the_header() {
+        // setup
+        let fm =
+            FileManager::with_backend(Arc::new(MemoryBackend::new()), 64, FileOptions::default());
+        let path = Path::new("data");
+        let mut pages: Vec<_> = (0..3).map(|_| Page::with_bytes(&[5; 64])).collect();
+        for (i, page) in pages.iter_mut().enumerate() {
+            page.set_lsn(100 + i as u64);
+        }
+
+        // test
+        fm.write(&PageId::new(path.to_path_buf(), 0), &pages[0])
+            .unwrap();
+        fm.write_many(path, 1, &pages[1..]).unwrap();
+        let mut single = Page::with_size(64);
+        fm.read(&PageId::new(path.to_path_buf(), 2), &mut single)
+            .unwrap();
+        let mut many: Vec<_> = (0..3).map(|_| Page::with_size(64)).collect();
+        fm.read_many(path, 0, &mut many).unwrap();
+
+        // verify
+        assert_eq!(single.lsn(), 102);
+        assert_eq!(
+            many.iter().map(Page::lsn).collect::<Vec<_>>(),
+            [100, 101, 102]
+        );
+        assert_eq!(many[0].content(), &[5; 64]);
+    }
+
+    #[test]
+    fn corrupted_header_fails_verification() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let fm = FileManager::with_backend(backend.clone(), 64, FileOptions::default());
+        let page_id = PageId::new(PathBuf::from("data"), 0);
+        let mut page = Page::with_size(64);
+        page.set_lsn(7);
+        fm.write(&page_id, &page).unwrap();
+
+        // test
+        backend.write(Path::new("data"), 7, &[8]).unwrap();
+        let result = fm.read(&page_id, &mut page);
+
+        // verify
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<FileError>(),
+            Some(FileError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn memory_backend_round_trips_pages() {
+        // setup
+        let (fm, _) = memory_file_manager(64);
+        let path = Path::new("memfile");
+        let mut page = Page::with_size(64);
+        page.set_string(0, "in memory").unwrap();
+
+        // test
+        let page_id = fm.append_page(path).unwrap();
+        fm.write(&page_id, &page).unwrap();
+        let mut read_page = Page::with_size(64);
+        fm.read(&page_id, &mut read_page).unwrap();
+
+        // verify
+        assert_eq!(fm.size(path).unwrap(), 1);
+        assert_eq!(read_page.get_string(0).unwrap(), "in memory");
+    }
+
+    #[test]
+    fn appending_after_a_partial_page_replaces_it() {
+        // setup
+        let (fm, backend) = memory_file_manager(64);
+        let path = Path::new("torn");
+        fm.append_page(path).unwrap();
+        backend.append(path, &[1, 2, 3]).unwrap();
+
+        // test
+        let first = fm.append_page(path).unwrap();
+        let second = fm.append_page(path).unwrap();
+
+        // verify
+        assert_eq!((first.block_no(), second.block_no()), (1, 2));
+        assert_eq!(fm.size(path).unwrap(), 3);
+        assert_eq!(backend.size(path).unwrap(), 3 * fm.slot_size() as u64);
+        let mut page = Page::with_size(64);
+        fm.read(&first, &mut page).unwrap();
+        assert_eq!(page.content(), Page::with_size(64).content());
+    }
+
+    #[test]
+    fn concurrent_writes_to_distinct_pages_of_one_file_do_not_interfere() {
+        // setup
+        const THREADS: u64 = 8;
+        const PAGES_PER_THREAD: u64 = 32;
+        let (fm, tmp) = temp_file_manager(256);
+        let fm = Arc::new(fm);
+        let path = tmp.path().join("stress");
+        let fill = |block_no: u64| Page::with_bytes(&[(block_no % 251) as u8 + 1; 256]);
+
+        // test
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let fm = fm.clone();
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..PAGES_PER_THREAD {
+                        let page_id = PageId::new(path.clone(), i * THREADS + t);
+                        fm.write(&page_id, &fill(page_id.block_no())).unwrap();
+                        // Read back right away to interleave reads with other threads' writes
+                        let mut page = Page::with_size(256);
+                        fm.read(&page_id, &mut page).unwrap();
+                        assert_eq!(page.content(), fill(page_id.block_no()).content());
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().expect("Writer thread panicked");
+        }
+
+        // verify
+        assert_eq!(fm.size(&path).unwrap(), THREADS * PAGES_PER_THREAD);
+        for block_no in 0..THREADS * PAGES_PER_THREAD {
+            let mut page = Page::with_size(256);
+            fm.read(&PageId::new(path.clone(), block_no), &mut page)
+                .unwrap();
+            assert_eq!(page.content(), fill(block_no).content());
+        }
+    }
+
+    #[test]
+    fn read_many_and_write_many_span_segments() {
+        // setup
+        let (_, backend) = memory_file_manager(64);
+        let options = FileOptions {
+            segment_size: Some(3 * (PAGE_HEADER_SIZE + 64 + CHECKSUM_SIZE) as u64),
+            ..FileOptions::default()
+        };
+        let fm = FileManager::with_backend(backend, 64, options);
+        let path = Path::new("segmented");
+        let pages: Vec<_> = (1..=5).map(|i| Page::with_bytes(&[i; 64])).collect();
+
+        // test
+        fm.write_many(path, 1, &pages).unwrap();
+        let mut read: Vec<_> = (0..5).map(|_| Page::with_size(64)).collect();
+        fm.read_many(path, 1, &mut read).unwrap();
+
+        // verify
+        assert_eq!(fm.size(path).unwrap(), 6);
+        for (i, page) in read.iter().enumerate() {
+            assert_eq!(page.content(), &[i as u8 + 1; 64]);
+        }
+        let mut single = Page::with_size(64);
+        fm.read(&PageId::new(path.into(), 3), &mut single).unwrap();
+        assert_eq!(single.content(), &[3; 64]);
+        let stats = fm.io_stats();
+        assert_eq!((stats.total.reads, stats.total.writes), (6, 5));
+    }
+
+    #[test]
+    fn read_many_detects_a_corrupted_page() {
+        // setup
+        let (fm, backend) = memory_file_manager(64);
+        let path = Path::new("data");
+        let pages: Vec<_> = (0..3).map(|_| Page::with_bytes(&[1; 64])).collect();
+        fm.write_many(path, 0, &pages).unwrap();
+        backend
+            .write(
+                path,
+                (PAGE_HEADER_SIZE + 64 + CHECKSUM_SIZE) as u64 + 5,
+                &[9],
+            )
+            .unwrap();
+
+        // test
+        let mut read: Vec<_> = (0..3).map(|_| Page::with_size(64)).collect();
+        let err = fm.read_many(path, 0, &mut read).unwrap_err();
+
+        // verify
+        match err.downcast_ref::<FileError>() {
+            Some(FileError::ChecksumMismatch { page_id, .. }) => assert_eq!(page_id.block_no(), 1),
+            other => panic!("Expected a checksum mismatch, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn segmented_files_spread_pages_across_segment_files() {
+        // setup
+        let (_, backend) = memory_file_manager(64);
+        let options = FileOptions {
+            segment_size: Some(3 * (PAGE_HEADER_SIZE + 64 + CHECKSUM_SIZE) as u64),
+            ..FileOptions::default()
+        };
+        let fm = FileManager::with_backend(backend.clone(), 64, options);
+        let path = Path::new("segmented");
+
+        // test
+        for _ in 0..7 {
+            fm.append_page(path).unwrap();
+        }
+        fm.write(&PageId::new(path.into(), 4), &Page::with_bytes(&[4; 64]))
+            .unwrap();
+
+        // verify
+        assert_eq!(fm.size(path).unwrap(), 7);
+        let segment_pages: Vec<_> = (0..4)
+            .map(|i| {
+                backend
+                    .size(Path::new(&format!("segmented.{}", i)))
+                    .unwrap()
+                    / 68
+            })
+            .collect();
+        assert_eq!(segment_pages, vec![3, 3, 1, 0]);
+        assert_eq!(backend.size(path).unwrap(), 0);
+
+        let mut page = Page::with_size(64);
+        fm.read(&PageId::new(path.into(), 4), &mut page).unwrap();
+        assert_eq!(page.content(), &[4; 64]);
+    }
+
+    #[test]
+    fn a_short_segment_counts_as_full_before_the_last_one() {
+        // setup
+        let (_, backend) = memory_file_manager(64);
+        let options = FileOptions {
+            segment_size: Some(3 * (PAGE_HEADER_SIZE + 64 + CHECKSUM_SIZE) as u64),
+            ..FileOptions::default()
+        };
+        let fm = FileManager::with_backend(backend, 64, options);
+        let path = Path::new("segmented");
+        fm.append_page(path).unwrap();
+        fm.append_page(path).unwrap();
+
+        // test
+        fm.write(&PageId::new(path.into(), 4), &Page::with_bytes(&[4; 64]))
+            .unwrap();
+
+        // verify
+        assert_eq!(fm.size(path).unwrap(), 5);
+        let mut hole = Page::with_bytes(&[1; 64]);
+        fm.read(&PageId::new(path.into(), 2), &mut hole).unwrap();
+        assert_eq!(hole.content(), &[0; 64]);
+        let mut pages: Vec<_> = (0..5).map(|_| Page::with_size(64)).collect();
+        fm.read_many(path, 0, &mut pages).unwrap();
+        assert_eq!(pages[4].content(), &[4; 64]);
+        assert_eq!(fm.append_page(path).unwrap().block_no(), 5);
+    }
+
+    #[test]
+    fn syncing_an_empty_segmented_file_does_not_create_it() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let options = FileOptions {
+            segment_size: Some(3 * (PAGE_HEADER_SIZE + 64 + CHECKSUM_SIZE) as u64),
+            ..FileOptions::default()
+        };
+        let fm = FileManager::with_options(tmp.path(), 64, options).unwrap();
+        let path = tmp.path().join("segmented");
+
+        // test
+        fm.sync(&path).unwrap();
+
+        // verify
+        assert!(!tmp.path().join("segmented.0").exists());
+    }
+
+    /// Sleeps before measuring a file, so that concurrent appends interleave.
+    #[derive(Default)]
+    struct SlowSizeBackend(MemoryBackend);
+
+    impl StorageBackend for SlowSizeBackend {
+        fn read(&self, path: &Path, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+            self.0.read(path, offset, buf)
+        }
+
+        fn write(&self, path: &Path, offset: u64, buf: &[u8]) -> io::Result<()> {
+            self.0.write(path, offset, buf)
+        }
+
+        fn append(&self, path: &Path, buf: &[u8]) -> io::Result<u64> {
+            self.0.append(path, buf)
+        }
+
+        fn size(&self, path: &Path) -> io::Result<u64> {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            self.0.size(path)
+        }
+
+        fn sync(&self, path: &Path) -> io::Result<()> {
+            self.0.sync(path)
+        }
+
+        fn delete(&self, path: &Path) -> io::Result<()> {
+            self.0.delete(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.0.rename(from, to)
+        }
+    }
+
+    #[test]
+    fn concurrent_appends_hand_out_distinct_pages() {
+        // setup
+        const THREADS: usize = 4;
+        const APPENDS_PER_THREAD: usize = 10;
+        let backend = Arc::new(SlowSizeBackend::default());
+        let fm = FileManager::with_backend(backend, 64, FileOptions::default());
+        let path = Path::new("data");
+
+        // test
+        let mut block_nos: Vec<u64> = std::thread::scope(|scope| {
+            let appenders: Vec<_> = (0..THREADS)
+                .map(|_| {
+                    scope.spawn(|| {
+                        (0..APPENDS_PER_THREAD)
+                            .map(|_| fm.append_page(path).unwrap().block_no())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            appenders
+                .into_iter()
+                .flat_map(|appender| appender.join().unwrap())
+                .collect()
+        });
+
+        // verify
+        block_nos.sort();
+        let expected: Vec<u64> = (0..(THREADS * APPENDS_PER_THREAD) as u64).collect();
+        assert_eq!(block_nos, expected);
+        assert_eq!(fm.size(path).unwrap(), expected.len() as u64);
+    }
+
     #[test]
     fn reading_nonexistent_file_returns_error() {
         // setup
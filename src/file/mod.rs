@@ -3,17 +3,31 @@
 //! This module provides core abstractions for file-based storage including:
 //! - Page identification and addressing
 //! - Page-based data storage with type-safe serialization  
-//! - File management with caching and synchronous I/O
+//! - File management with caching, synchronous I/O and page checksums
+//! - Per-file I/O statistics
 
 // Private modules - not exposed in public API
+pub mod backend;
+pub mod dir_lock;
 pub mod page_id;
 pub mod manager;
+pub mod options;
 pub mod page;
+pub mod stats;
+pub mod superblock;
+pub mod temp_file;
+pub(crate) mod checksum;
 
 // Public re-exports with inlined documentation
 #[doc(inline)]
 pub use self::page_id::PageId;
 #[doc(inline)]
-pub use self::manager::FileManager;
+pub use self::manager::{FileError, FileManager};
 #[doc(inline)]
-pub use self::page::Page;
+pub use self::options::{DurabilityMode, FileOptions};
+#[doc(inline)]
+pub use self::page::{Lsn, Page};
+#[doc(inline)]
+pub use self::stats::{FileIoStats, IoStats};
+#[doc(inline)]
+pub use self::temp_file::TempFile;
@@ -0,0 +1,63 @@
+/// How hard the database works to make writes survive a crash or power loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurabilityMode {
+    /// Every write is synchronous (`O_SYNC`) and on disk once it returns.
+    #[default]
+    Sync,
+    /// Writes are buffered by the operating system and made durable with an explicit
+    /// `fsync` whenever the log or the buffer pool is flushed, e.g. on commit.
+    FsyncOnFlush,
+    /// Writes are never forced to disk. Only meant for tests and bulk loads that can be redone.
+    None,
+}
+
+/// Tunables that change how the [`FileManager`](crate::file::FileManager) lays out and accesses pages.
+///
+/// # Examples
+///
+/// ```
+/// # use rimple::file::{DurabilityMode, FileOptions};
+/// let options = FileOptions {
+///     durability: DurabilityMode::FsyncOnFlush,
+///     ..FileOptions::default()
+/// };
+/// assert!(options.checksums);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileOptions {
+    /// Whether every page is stored with a checksum that is verified on read.
+    ///
+    /// Enabled by default. Disabling it saves the checksum computation on each
+    /// I/O and is only meant for benchmarks. The setting changes the on-disk
+    /// layout, so a directory must always be opened with the same value.
+    pub checksums: bool,
+
+    /// When writes are forced to stable storage. Defaults to [`DurabilityMode::Sync`].
+    pub durability: DurabilityMode,
+
+    /// Maximum size of a single OS file in bytes, or `None` to keep every logical
+    /// file in one OS file.
+    ///
+    /// When set, files are split into segment files `name.0`, `name.1`, … holding
+    /// as many whole pages as fit. Like `checksums`, this determines the on-disk
+    /// layout and must not change for an existing directory.
+    pub segment_size: Option<u64>,
+
+    /// Whether files are opened without write permission.
+    ///
+    /// Any attempt to write, append or delete through a read-only file manager
+    /// fails with [`FileError::ReadOnly`](crate::file::FileError::ReadOnly), and
+    /// the directory is neither created nor cleaned up.
+    pub read_only: bool,
+}
+
+impl Default for FileOptions {
+    fn default() -> Self {
+        Self {
+            checksums: true,
+            durability: DurabilityMode::default(),
+            segment_size: None,
+            read_only: false,
+        }
+    }
+}
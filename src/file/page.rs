@@ -1,5 +1,12 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use anyhow::Error;
 
+/// A log sequence number, the position of a record in the log.
+///
+/// LSNs grow with every record appended, also across restarts of the database.
+pub type Lsn = u64;
+
 /// A page represents a fixed-size page of bytes that can be read from or written to disk.
 ///
 /// Pages provide methods to store and retrieve various data types in a binary format
@@ -8,11 +15,14 @@ use anyhow::Error;
 /// # Data Format
 ///
 /// - **Integers**: Stored as 32-bit big-endian values
+/// - **Longs**: `i64` and `u64` are stored as 64-bit big-endian values
+/// - **Floats**: `f64` is stored as its 64-bit IEEE 754 representation in big-endian order
+/// - **Bytes and booleans**: Stored as a single byte (booleans as `0` or `1`)
+/// - **Timestamps**: Stored as signed 64-bit microseconds since the Unix epoch
 /// - **Byte arrays**: Stored with a 4-byte big-endian length prefix followed by the data
 /// - **Strings**: Stored as byte arrays with UTF-8 encoding
 ///
 /// # TODO
-/// - Add support for other primitive types (e.g. i64, f32, f64, dates, etc.)
 /// - Add support for null-terminated strings
 ///
 /// # Examples
@@ -26,6 +36,8 @@ use anyhow::Error;
 #[derive(Debug)]
 pub struct Page {
     content: Vec<u8>,
+    /// The LSN stored in the page header, see [`Page::lsn`].
+    lsn: Lsn,
 }
 
 /// Errors that can occur during page operations.
@@ -66,6 +78,7 @@ impl Page {
     pub fn with_bytes(bytes: &[u8]) -> Self {
         Self {
             content: bytes.to_vec(),
+            lsn: 0,
         }
     }
 
@@ -85,6 +98,7 @@ impl Page {
     pub fn with_size(size: usize) -> Self {
         Self {
             content: vec![0; size],
+            lsn: 0,
         }
     }
 
@@ -150,6 +164,262 @@ impl Page {
         Ok(())
     }
 
+    /// Reads a 64-bit signed integer from the page at the specified offset.
+    ///
+    /// The integer is stored in big-endian format.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let page = Page::with_bytes(&[0, 0, 0, 1, 0, 0, 0, 0]);
+    /// assert_eq!(page.get_i64(0).unwrap(), 1 << 32);
+    /// ```
+    pub fn get_i64(&self, offset: usize) -> anyhow::Result<i64> {
+        self.get_array(offset).map(i64::from_be_bytes)
+    }
+
+    /// Writes a 64-bit signed integer to the page at the specified offset.
+    ///
+    /// The integer is stored in big-endian format.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let mut page = Page::with_size(8);
+    /// page.set_i64(0, -5_000_000_000).unwrap();
+    /// assert_eq!(page.get_i64(0).unwrap(), -5_000_000_000);
+    /// ```
+    pub fn set_i64(&mut self, offset: usize, value: i64) -> anyhow::Result<()> {
+        self.set_array(offset, value.to_be_bytes())
+    }
+
+    /// Reads a 64-bit unsigned integer from the page at the specified offset.
+    ///
+    /// The integer is stored in big-endian format.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let page = Page::with_bytes(&[0xFF; 8]);
+    /// assert_eq!(page.get_u64(0).unwrap(), u64::MAX);
+    /// ```
+    pub fn get_u64(&self, offset: usize) -> anyhow::Result<u64> {
+        self.get_array(offset).map(u64::from_be_bytes)
+    }
+
+    /// Writes a 64-bit unsigned integer to the page at the specified offset.
+    ///
+    /// The integer is stored in big-endian format.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let mut page = Page::with_size(8);
+    /// page.set_u64(0, 10_000_000_000).unwrap();
+    /// assert_eq!(page.get_u64(0).unwrap(), 10_000_000_000);
+    /// ```
+    pub fn set_u64(&mut self, offset: usize, value: u64) -> anyhow::Result<()> {
+        self.set_array(offset, value.to_be_bytes())
+    }
+
+    /// Reads a 64-bit floating point number from the page at the specified offset.
+    ///
+    /// The value is stored as its IEEE 754 bit pattern in big-endian format.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let page = Page::with_bytes(&1.5f64.to_be_bytes());
+    /// assert_eq!(page.get_f64(0).unwrap(), 1.5);
+    /// ```
+    pub fn get_f64(&self, offset: usize) -> anyhow::Result<f64> {
+        self.get_array(offset).map(f64::from_be_bytes)
+    }
+
+    /// Writes a 64-bit floating point number to the page at the specified offset.
+    ///
+    /// The value is stored as its IEEE 754 bit pattern in big-endian format.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let mut page = Page::with_size(8);
+    /// page.set_f64(0, 19.99).unwrap();
+    /// assert_eq!(page.get_f64(0).unwrap(), 19.99);
+    /// ```
+    pub fn set_f64(&mut self, offset: usize, value: f64) -> anyhow::Result<()> {
+        self.set_array(offset, value.to_be_bytes())
+    }
+
+    /// Reads a single byte from the page at the specified offset.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let page = Page::with_bytes(&[7, 42]);
+    /// assert_eq!(page.get_u8(1).unwrap(), 42);
+    /// ```
+    pub fn get_u8(&self, offset: usize) -> anyhow::Result<u8> {
+        self.get_array(offset).map(u8::from_be_bytes)
+    }
+
+    /// Writes a single byte to the page at the specified offset.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let mut page = Page::with_size(1);
+    /// page.set_u8(0, 255).unwrap();
+    /// assert_eq!(page.get_u8(0).unwrap(), 255);
+    /// ```
+    pub fn set_u8(&mut self, offset: usize, value: u8) -> anyhow::Result<()> {
+        self.set_array(offset, value.to_be_bytes())
+    }
+
+    /// Reads a boolean from the page at the specified offset.
+    ///
+    /// Booleans are stored as a single byte that is either `0` or `1`.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset exceeds the page size
+    /// * `PageError::InvalidData` - If the stored byte is neither `0` nor `1`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let page = Page::with_bytes(&[0, 1, 2]);
+    /// assert!(!page.get_bool(0).unwrap());
+    /// assert!(page.get_bool(1).unwrap());
+    /// assert!(page.get_bool(2).is_err());
+    /// ```
+    pub fn get_bool(&self, offset: usize) -> anyhow::Result<bool> {
+        match self.get_u8(offset)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::new(PageError::InvalidData)),
+        }
+    }
+
+    /// Writes a boolean to the page at the specified offset.
+    ///
+    /// Booleans are stored as a single byte that is either `0` or `1`.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset exceeds the page size
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let mut page = Page::with_size(1);
+    /// page.set_bool(0, true).unwrap();
+    /// assert!(page.get_bool(0).unwrap());
+    /// ```
+    pub fn set_bool(&mut self, offset: usize, value: bool) -> anyhow::Result<()> {
+        self.set_u8(offset, value as u8)
+    }
+
+    /// Reads a timestamp from the page at the specified offset.
+    ///
+    /// Timestamps are stored as signed 64-bit microseconds relative to the Unix epoch,
+    /// so points in time before 1970 are supported as well.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    /// * `PageError::InvalidData` - If the stored value is not representable as a `SystemTime`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let page = Page::with_bytes(&1_000_000i64.to_be_bytes());
+    /// assert_eq!(page.get_timestamp(0).unwrap(), UNIX_EPOCH + Duration::from_secs(1));
+    /// ```
+    pub fn get_timestamp(&self, offset: usize) -> anyhow::Result<SystemTime> {
+        let micros = self.get_i64(offset)?;
+        let delta = Duration::from_micros(micros.unsigned_abs());
+        let timestamp = if micros >= 0 {
+            UNIX_EPOCH.checked_add(delta)
+        } else {
+            UNIX_EPOCH.checked_sub(delta)
+        };
+        timestamp.ok_or_else(|| Error::new(PageError::InvalidData))
+    }
+
+    /// Writes a timestamp to the page at the specified offset.
+    ///
+    /// The timestamp is truncated to microsecond precision and stored as signed
+    /// 64-bit microseconds relative to the Unix epoch.
+    ///
+    /// # Errors
+    ///
+    /// * `PageError::OutOfBounds` - If the offset + 8 bytes exceeds the page size
+    /// * `PageError::InvalidData` - If the timestamp is too far from the epoch to fit into 64 bits
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// # use std::time::{Duration, UNIX_EPOCH};
+    /// let mut page = Page::with_size(8);
+    /// let before_epoch = UNIX_EPOCH - Duration::from_secs(86_400);
+    /// page.set_timestamp(0, before_epoch).unwrap();
+    /// assert_eq!(page.get_timestamp(0).unwrap(), before_epoch);
+    /// ```
+    pub fn set_timestamp(&mut self, offset: usize, value: SystemTime) -> anyhow::Result<()> {
+        let micros = match value.duration_since(UNIX_EPOCH) {
+            Ok(after) => i64::try_from(after.as_micros()),
+            Err(before) => i64::try_from(before.duration().as_micros()).map(|m| -m),
+        }
+        .map_err(|_| PageError::InvalidData)?;
+        self.set_i64(offset, micros)
+    }
+
     /// Reads a byte slice from the page at the specified offset.
     ///
     /// The byte data is stored with a 4-byte length prefix (big-endian) followed by the actual bytes.
@@ -313,6 +583,30 @@ impl Page {
         &mut self.content
     }
 
+    /// Returns the log sequence number stored in the page header.
+    ///
+    /// The header is kept by the [`FileManager`](crate::file::FileManager) next to
+    /// the content, so it never takes space away from the page itself. It holds the
+    /// LSN of the latest change to the page, and a page that was never changed
+    /// through the buffer pool has LSN 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// let mut page = Page::with_size(16);
+    /// page.set_lsn(42);
+    /// assert_eq!(page.lsn(), 42);
+    /// ```
+    pub fn lsn(&self) -> Lsn {
+        self.lsn
+    }
+
+    /// Sets the log sequence number stored in the page header.
+    pub fn set_lsn(&mut self, lsn: Lsn) {
+        self.lsn = lsn;
+    }
+
     /// Returns the size of the page in bytes.
     ///
     /// # Examples
@@ -326,6 +620,19 @@ impl Page {
         self.content.len()
     }
 
+    /// Returns `true` if the page has a size of zero bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rimple::file::Page;
+    /// assert!(Page::with_size(0).is_empty());
+    /// assert!(!Page::with_size(8).is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
     /// Checks if the specified offset and size are within the page bounds.
     ///
     /// # Arguments
@@ -344,6 +651,23 @@ impl Page {
         }
     }
 
+    /// Reads `N` raw bytes from the page at the specified offset.
+    fn get_array<const N: usize>(&self, offset: usize) -> anyhow::Result<[u8; N]> {
+        self.assert_offset_within_bounds(offset, N)?;
+
+        self.content[offset..offset + N]
+            .try_into()
+            .map_err(|_| Error::new(PageError::InvalidData))
+    }
+
+    /// Writes `N` raw bytes to the page at the specified offset.
+    fn set_array<const N: usize>(&mut self, offset: usize, bytes: [u8; N]) -> anyhow::Result<()> {
+        self.assert_offset_within_bounds(offset, N)?;
+
+        self.content[offset..offset + N].copy_from_slice(&bytes);
+        Ok(())
+    }
+
     /// Calculates the maximum storage space required for a string.
     ///
     /// This includes the 4-byte length prefix plus the string's byte length.
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// I/O counters of a single file, or of all files together.
+///
+/// Appends count towards `appends` only, but their bytes and time are included
+/// in `bytes_written` and `write_time`. Byte counts include checksum trailers.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileIoStats {
+    /// Number of pages read.
+    pub reads: u64,
+    /// Number of pages written, not counting appends.
+    pub writes: u64,
+    /// Number of pages appended.
+    pub appends: u64,
+    /// Number of bytes read from storage.
+    pub bytes_read: u64,
+    /// Number of bytes written to storage, including appends.
+    pub bytes_written: u64,
+    /// Cumulative time spent reading.
+    pub read_time: Duration,
+    /// Cumulative time spent writing and appending.
+    pub write_time: Duration,
+}
+
+impl FileIoStats {
+    fn add(&mut self, other: &FileIoStats) {
+        self.reads += other.reads;
+        self.writes += other.writes;
+        self.appends += other.appends;
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+        self.read_time += other.read_time;
+        self.write_time += other.write_time;
+    }
+}
+
+/// A point-in-time snapshot of the I/O performed by a [`FileManager`](crate::file::FileManager).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IoStats {
+    /// The counters summed over all files.
+    pub total: FileIoStats,
+    /// The counters of each file that saw any I/O, keyed by logical file path.
+    pub files: HashMap<PathBuf, FileIoStats>,
+}
+
+impl IoStats {
+    /// Returns the counters of a single file, or zeros if it saw no I/O.
+    pub fn file(&self, path: &Path) -> FileIoStats {
+        self.files.get(path).cloned().unwrap_or_default()
+    }
+}
+
+/// Collects [`FileIoStats`] per file while I/O is going on.
+#[derive(Default)]
+pub(crate) struct IoCounters {
+    files: Mutex<HashMap<PathBuf, FileIoStats>>,
+}
+
+impl IoCounters {
+    pub(crate) fn record_read(&self, path: &Path, pages: usize, bytes: usize, elapsed: Duration) {
+        self.update(path, |stats| {
+            stats.reads += pages as u64;
+            stats.bytes_read += bytes as u64;
+            stats.read_time += elapsed;
+        });
+    }
+
+    pub(crate) fn record_write(&self, path: &Path, pages: usize, bytes: usize, elapsed: Duration) {
+        self.update(path, |stats| {
+            stats.writes += pages as u64;
+            stats.bytes_written += bytes as u64;
+            stats.write_time += elapsed;
+        });
+    }
+
+    pub(crate) fn record_append(&self, path: &Path, pages: usize, bytes: usize, elapsed: Duration) {
+        self.update(path, |stats| {
+            stats.appends += pages as u64;
+            stats.bytes_written += bytes as u64;
+            stats.write_time += elapsed;
+        });
+    }
+
+    pub(crate) fn snapshot(&self) -> IoStats {
+        let files = self.files.lock().unwrap().clone();
+        let mut total = FileIoStats::default();
+        for stats in files.values() {
+            total.add(stats);
+        }
+        IoStats { total, files }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.files.lock().unwrap().clear();
+    }
+
+    fn update(&self, path: &Path, f: impl FnOnce(&mut FileIoStats)) {
+        let mut files = self.files.lock().unwrap();
+        match files.get_mut(path) {
+            Some(stats) => f(stats),
+            None => f(files.entry(path.to_path_buf()).or_default()),
+        }
+    }
+}
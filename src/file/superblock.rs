@@ -0,0 +1,378 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::file::{
+    Page,
+    backend::StorageBackend,
+    checksum::{CHECKSUM_SIZE, crc32},
+};
+
+/// Identifies a rimple database directory ("RIMPLEDB" in ASCII).
+const MAGIC: u64 = 0x5249_4D50_4C45_4442;
+
+/// Version of the on-disk format described by the superblock.
+///
+/// Version 2 added the segment size, version 3 the LSN header in front of every
+/// page and version 4 the checksums in front of the records of each log page. Older
+/// databases are refused with [`SuperblockError::OutdatedFormat`], as their pages
+/// cannot be read in the current layout.
+pub const FORMAT_VERSION: i32 = 4;
+
+/// Size of the fixed fields of the superblock in bytes.
+///
+/// The name of the log file follows them, and a checksum over everything before
+/// it ends the superblock. A superblock naming no log file is 64 bytes long.
+const FIXED_SIZE: usize = 60;
+
+const MAGIC_POS: usize = 0;
+const VERSION_POS: usize = MAGIC_POS + 8;
+const PAGE_SIZE_POS: usize = VERSION_POS + 4;
+const CHECKSUMS_POS: usize = PAGE_SIZE_POS + 4;
+const CLEAN_SHUTDOWN_POS: usize = CHECKSUMS_POS + 1;
+const CREATED_AT_POS: usize = CLEAN_SHUTDOWN_POS + 1;
+const SEGMENT_SIZE_POS: usize = CREATED_AT_POS + 8;
+/// The length of the log file name, whose bytes start at [`FIXED_SIZE`].
+const LOG_FILE_LEN_POS: usize = SEGMENT_SIZE_POS + 8;
+
+/// Errors raised when a database directory does not match what the caller expects.
+#[derive(thiserror::Error, Debug)]
+pub enum SuperblockError {
+    /// The directory does not contain a database.
+    #[error("No database found: superblock {0:?} does not exist")]
+    Missing(PathBuf),
+
+    /// A database already exists where a new one should be created.
+    #[error("A database already exists: superblock {0:?} is present")]
+    AlreadyExists(PathBuf),
+
+    /// The superblock is damaged or belongs to something else.
+    #[error("Superblock {0:?} is corrupted or not a rimple database")]
+    Corrupted(PathBuf),
+
+    /// The database was written by an incompatible version.
+    #[error("Unsupported format version {found}, expected {expected}")]
+    UnsupportedVersion { found: i32, expected: i32 },
+
+    /// The database was written in an older format that cannot be upgraded in place.
+    ///
+    /// Version 2 added segment files and version 3 an LSN header in every page slot,
+    /// so the files of an older database cannot be read as they are.
+    #[error(
+        "Database uses the outdated format version {found}, which this build cannot read or \
+         upgrade; it only reads version {expected}, so the data has to be copied into a new \
+         database with the build that created it"
+    )]
+    OutdatedFormat { found: i32, expected: i32 },
+
+    /// The database was created with a different page size.
+    #[error("Database uses a page size of {stored} bytes, but {requested} was requested")]
+    PageSizeMismatch { stored: usize, requested: usize },
+
+    /// The database was created with a different checksum setting.
+    #[error(
+        "Database was created with checksums {stored}, but checksums {requested} was requested"
+    )]
+    ChecksumSettingMismatch { stored: bool, requested: bool },
+
+    /// The directory holds database files, but the superblock describing them is gone.
+    #[error("Found {0:?} but no superblock, refusing to create a database over it")]
+    FilesWithoutSuperblock(PathBuf),
+
+    /// The database was created with a different segment size.
+    #[error("Database uses segments of {stored:?} bytes, but {requested:?} was requested")]
+    SegmentSizeMismatch {
+        stored: Option<u64>,
+        requested: Option<u64>,
+    },
+
+    /// The database was created with a different log file.
+    #[error("Database logs to {stored:?}, but {requested:?} was requested")]
+    LogFileMismatch { stored: String, requested: String },
+}
+
+/// Describes how a database directory was created and whether it was shut down cleanly.
+///
+/// The superblock is stored in its own small file, independent of the page size,
+/// so it can be read before anything else about the database is known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Superblock {
+    /// The page size all files of the database were written with.
+    pub page_size: usize,
+    /// Whether pages are stored with checksums.
+    pub checksums: bool,
+    /// The maximum size of a segment file, if files are segmented.
+    pub segment_size: Option<u64>,
+    /// The name of the log file in the database directory.
+    ///
+    /// Empty if the superblock was written before the name was recorded.
+    pub log_file: String,
+    /// When the database was created.
+    pub created_at: SystemTime,
+    /// Whether the database was closed properly the last time it was open.
+    pub clean_shutdown: bool,
+}
+
+impl Superblock {
+    /// Describes a database that is being created right now.
+    pub fn new(
+        page_size: usize,
+        checksums: bool,
+        segment_size: Option<u64>,
+        log_file: impl Into<String>,
+    ) -> Self {
+        Self {
+            page_size,
+            checksums,
+            segment_size,
+            log_file: log_file.into(),
+            created_at: SystemTime::now(),
+            clean_shutdown: false,
+        }
+    }
+
+    /// Reads the superblock at `path`, returning `None` if there is none.
+    ///
+    /// # Errors
+    ///
+    /// * `SuperblockError::Corrupted` - If the file is damaged or not a superblock
+    /// * `SuperblockError::UnsupportedVersion` - If the format version is not supported
+    pub fn read(backend: &dyn StorageBackend, path: &Path) -> anyhow::Result<Option<Self>> {
+        let size = backend.size(path)? as usize;
+        if size == 0 {
+            return Ok(None);
+        }
+        if size < FIXED_SIZE + CHECKSUM_SIZE {
+            return Err(SuperblockError::Corrupted(path.to_path_buf()).into());
+        }
+
+        let mut page = Page::with_size(size);
+        backend
+            .read(path, 0, page.content_mut())
+            .map_err(|_| SuperblockError::Corrupted(path.to_path_buf()))?;
+        let crc_pos = size - CHECKSUM_SIZE;
+        let stored_crc = page.get_integer(crc_pos)? as u32;
+        if page.get_u64(MAGIC_POS)? != MAGIC
+            || stored_crc != crc32(&page.content()[..crc_pos])
+            || page.get_integer(LOG_FILE_LEN_POS)? as usize != crc_pos - FIXED_SIZE
+        {
+            return Err(SuperblockError::Corrupted(path.to_path_buf()).into());
+        }
+
+        let version = page.get_integer(VERSION_POS)?;
+        if (1..FORMAT_VERSION).contains(&version) {
+            return Err(SuperblockError::OutdatedFormat {
+                found: version,
+                expected: FORMAT_VERSION,
+            }
+            .into());
+        }
+        if version != FORMAT_VERSION {
+            return Err(SuperblockError::UnsupportedVersion {
+                found: version,
+                expected: FORMAT_VERSION,
+            }
+            .into());
+        }
+
+        Ok(Some(Self {
+            page_size: page.get_integer(PAGE_SIZE_POS)? as usize,
+            checksums: page.get_bool(CHECKSUMS_POS)?,
+            // Zero is never a valid segment size and stands for unsegmented files
+            segment_size: Some(page.get_u64(SEGMENT_SIZE_POS)?).filter(|&size| size > 0),
+            log_file: String::from_utf8(page.content()[FIXED_SIZE..crc_pos].to_vec())
+                .map_err(|_| SuperblockError::Corrupted(path.to_path_buf()))?,
+            clean_shutdown: page.get_bool(CLEAN_SHUTDOWN_POS)?,
+            created_at: page.get_timestamp(CREATED_AT_POS)?,
+        }))
+    }
+
+    /// Writes the superblock to `path` and makes it durable.
+    ///
+    /// The superblock is written to a temporary file first and renamed over `path`,
+    /// so a crash leaves either the old or the new superblock behind.
+    pub fn write(&self, backend: &dyn StorageBackend, path: &Path) -> anyhow::Result<()> {
+        let crc_pos = FIXED_SIZE + self.log_file.len();
+        let mut page = Page::with_size(crc_pos + CHECKSUM_SIZE);
+        page.set_u64(MAGIC_POS, MAGIC)?;
+        page.set_integer(VERSION_POS, FORMAT_VERSION)?;
+        page.set_integer(PAGE_SIZE_POS, self.page_size as i32)?;
+        page.set_bool(CHECKSUMS_POS, self.checksums)?;
+        page.set_bool(CLEAN_SHUTDOWN_POS, self.clean_shutdown)?;
+        page.set_timestamp(CREATED_AT_POS, self.created_at)?;
+        page.set_u64(SEGMENT_SIZE_POS, self.segment_size.unwrap_or(0))?;
+        page.set_integer(LOG_FILE_LEN_POS, self.log_file.len() as i32)?;
+        page.content_mut()[FIXED_SIZE..crc_pos].copy_from_slice(self.log_file.as_bytes());
+        let crc = crc32(&page.content()[..crc_pos]);
+        page.set_integer(crc_pos, crc as i32)?;
+
+        let temp = temp_path(path);
+        backend.delete(&temp)?;
+        backend.write(&temp, 0, page.content())?;
+        backend.sync(&temp)?;
+        backend.rename(&temp, path)?;
+        Ok(())
+    }
+}
+
+/// Returns the path a new superblock for `path` is written to before it replaces the old one.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::file::backend::MemoryBackend;
+
+    use super::*;
+
+    #[test]
+    fn superblock_round_trips() {
+        // setup
+        let backend = MemoryBackend::new();
+        let path = Path::new("meta");
+        let superblock = Superblock::new(512, true, Some(1 << 30), "wal.log");
+
+        // test
+        superblock.write(&backend, path).unwrap();
+
+        // verify
+        let read = Superblock::read(&backend, path).unwrap().unwrap();
+        assert_eq!(read.page_size, 512);
+        assert!(read.checksums);
+        assert_eq!(read.segment_size, Some(1 << 30));
+        assert_eq!(read.log_file, "wal.log");
+        assert!(!read.clean_shutdown);
+    }
+
+    #[test]
+    fn rewriting_the_superblock_leaves_no_temporary_file() {
+        // setup
+        let backend = MemoryBackend::new();
+        let path = Path::new("meta");
+        let mut superblock = Superblock::new(512, false, None, "simpledb.log");
+        superblock.write(&backend, path).unwrap();
+
+        // test
+        superblock.clean_shutdown = true;
+        superblock.write(&backend, path).unwrap();
+
+        // verify
+        assert!(
+            Superblock::read(&backend, path)
+                .unwrap()
+                .unwrap()
+                .clean_shutdown
+        );
+        assert_eq!(backend.size(&temp_path(path)).unwrap(), 0);
+    }
+
+    #[test]
+    fn missing_superblock_reads_as_none() {
+        let backend = MemoryBackend::new();
+        assert!(
+            Superblock::read(&backend, Path::new("meta"))
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn superblocks_of_older_format_versions_are_reported_as_outdated() {
+        for version in 1..FORMAT_VERSION {
+            // setup
+            let backend = MemoryBackend::new();
+            let path = Path::new("meta");
+            // Version 1 had no segment size, so its bytes are zero
+            let mut page = Page::with_size(FIXED_SIZE + CHECKSUM_SIZE);
+            page.set_u64(MAGIC_POS, MAGIC).unwrap();
+            page.set_integer(VERSION_POS, version).unwrap();
+            page.set_integer(PAGE_SIZE_POS, 400).unwrap();
+            page.set_bool(CHECKSUMS_POS, true).unwrap();
+            page.set_timestamp(CREATED_AT_POS, SystemTime::now())
+                .unwrap();
+            let crc = crc32(&page.content()[..FIXED_SIZE]);
+            page.set_integer(FIXED_SIZE, crc as i32).unwrap();
+            backend.write(path, 0, page.content()).unwrap();
+
+            // test
+            let err = Superblock::read(&backend, path).unwrap_err();
+
+            // verify
+            assert!(
+                matches!(
+                    err.downcast_ref::<SuperblockError>(),
+                    Some(SuperblockError::OutdatedFormat { found, expected: FORMAT_VERSION })
+                        if *found == version
+                ),
+                "version {version}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn long_log_file_names_round_trip() {
+        // setup
+        let backend = MemoryBackend::new();
+        let path = Path::new("meta");
+        let mut superblock = Superblock::new(512, true, None, "production-write-ahead.log");
+        superblock.write(&backend, path).unwrap();
+
+        // test
+        superblock.log_file = "l".repeat(1000);
+        superblock.write(&backend, path).unwrap();
+
+        // verify
+        let read = Superblock::read(&backend, path).unwrap().unwrap();
+        assert_eq!(read.log_file, "l".repeat(1000));
+    }
+
+    #[test]
+    fn superblocks_without_a_log_file_name_keep_the_fixed_size() {
+        // setup
+        let backend = MemoryBackend::new();
+        let path = Path::new("meta");
+        // Written before the name of the log file was recorded
+        let mut page = Page::with_size(64);
+        page.set_u64(MAGIC_POS, MAGIC).unwrap();
+        page.set_integer(VERSION_POS, FORMAT_VERSION).unwrap();
+        page.set_integer(PAGE_SIZE_POS, 400).unwrap();
+        page.set_timestamp(CREATED_AT_POS, SystemTime::now())
+            .unwrap();
+        let crc = crc32(&page.content()[..60]);
+        page.set_integer(60, crc as i32).unwrap();
+        backend.write(path, 0, page.content()).unwrap();
+
+        // test
+        let read = Superblock::read(&backend, path).unwrap().unwrap();
+        read.write(&backend, path).unwrap();
+
+        // verify
+        assert_eq!(read.page_size, 400);
+        assert_eq!(read.log_file, "");
+        assert_eq!(backend.size(path).unwrap(), 64);
+    }
+
+    #[test]
+    fn damaged_superblock_is_rejected() {
+        // setup
+        let backend = MemoryBackend::new();
+        let path = Path::new("meta");
+        Superblock::new(512, true, None, "simpledb.log")
+            .write(&backend, path)
+            .unwrap();
+        backend.write(path, 13, &[0xFF]).unwrap();
+
+        // test
+        let err = Superblock::read(&backend, path).unwrap_err();
+
+        // verify
+        assert!(matches!(
+            err.downcast_ref::<SuperblockError>(),
+            Some(SuperblockError::Corrupted(_))
+        ));
+    }
+}
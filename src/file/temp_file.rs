@@ -0,0 +1,86 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Weak},
+};
+
+use log::warn;
+
+use crate::file::backend::StorageBackend;
+
+/// The file name prefix reserved for temporary files.
+pub(crate) const TEMP_FILE_PREFIX: &str = "temp-";
+
+/// Keeps copies of pages in memory, such as the buffer pool.
+///
+/// Told about temporary files that are deleted, so it does not write their
+/// pages back later and create the files again.
+pub(crate) trait PageCache: Send + Sync {
+    /// Forgets every cached page of `path` without writing it back.
+    fn discard_file(&self, path: &Path) -> anyhow::Result<()>;
+}
+
+/// A temporary file that is deleted when dropped.
+///
+/// Created by [`FileManager::create_temp_file`](crate::file::FileManager::create_temp_file).
+/// The handle only owns the file's name and lifetime; pages are read and written
+/// through the file manager or a transaction using [`TempFile::path`].
+pub struct TempFile {
+    path: PathBuf,
+    backend: Arc<dyn StorageBackend>,
+    /// Whether the file is split into segment files `path.0`, `path.1`, ...
+    segmented: bool,
+    /// The cache that may hold pages of the file when it is dropped.
+    page_cache: Option<Weak<dyn PageCache>>,
+}
+
+impl TempFile {
+    pub(crate) fn new(
+        path: PathBuf,
+        backend: Arc<dyn StorageBackend>,
+        segmented: bool,
+        page_cache: Option<Weak<dyn PageCache>>,
+    ) -> Self {
+        Self {
+            path,
+            backend,
+            segmented,
+            page_cache,
+        }
+    }
+
+    /// Returns the path to use for I/O on this file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn delete(&self) -> std::io::Result<()> {
+        if !self.segmented {
+            return self.backend.delete(&self.path);
+        }
+        for segment in 0.. {
+            let mut name = self.path.as_os_str().to_owned();
+            name.push(format!(".{}", segment));
+            let segment_path = PathBuf::from(name);
+            let size = self.backend.size(&segment_path)?;
+            self.backend.delete(&segment_path)?;
+            if size == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Dropped first, as writing back a cached page would create the file again
+        if let Some(page_cache) = self.page_cache.as_ref().and_then(Weak::upgrade)
+            && let Err(e) = page_cache.discard_file(&self.path)
+        {
+            warn!("Failed to discard cached pages of {:?}: {}", self.path, e);
+        }
+        if let Err(e) = self.delete() {
+            warn!("Failed to delete temporary file {:?}: {}", self.path, e);
+        }
+    }
+}
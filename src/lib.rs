@@ -1,4 +1,6 @@
+pub mod backup;
 pub mod buffer;
+pub mod config;
 pub mod db;
 pub mod file;
 pub mod log;
@@ -1,6 +1,9 @@
 use std::sync::Arc;
 
-use crate::file::{PageId, FileManager, Page};
+use crate::{
+    file::{FileManager, Lsn, Page, PageId},
+    log::manager::{BOUNDARY_POS, lsn_at},
+};
 
 pub(crate) struct LogIterator {
     file_manager: Arc<FileManager>,
@@ -8,6 +11,8 @@ pub(crate) struct LogIterator {
     blk: PageId,
     page: Page,
     boundary: i32,
+    /// The LSN of the record returned last.
+    lsn: Lsn,
 }
 
 impl LogIterator {
@@ -15,7 +20,7 @@ impl LogIterator {
         let page_size = file_manager.page_size();
         let mut page = Page::with_size(page_size);
         file_manager.read(&blk, &mut page)?;
-        let boundary = page.get_integer(0)?;
+        let boundary = page.get_integer(BOUNDARY_POS)?;
         let current_position = boundary as usize;
 
         Ok(Self {
@@ -24,26 +29,33 @@ impl LogIterator {
             blk,
             boundary,
             current_position,
+            lsn: 0,
         })
     }
+
+    /// Returns the LSN of the record returned last by [`next`](Iterator::next).
+    pub fn lsn(&self) -> Lsn {
+        self.lsn
+    }
 }
 
 impl Iterator for LogIterator {
     type Item = Vec<u8>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_position >= self.file_manager.page_size() && self.blk.block_no() == 0 {
-            return None;
-        }
-
-        if self.current_position == self.file_manager.page_size() {
+        // Pages without records are skipped
+        while self.current_position >= self.file_manager.page_size() {
+            if self.blk.block_no() == 0 {
+                return None;
+            }
             self.blk = PageId::new(self.blk.path().to_path_buf(), self.blk.block_no() - 1);
             self.file_manager.read(&self.blk, &mut self.page).ok()?;
-            self.boundary = self.page.get_integer(0).ok()?;
+            self.boundary = self.page.get_integer(BOUNDARY_POS).ok()?;
             self.current_position = self.boundary as usize;
         }
 
         let record = self.page.get_bytes(self.current_position).ok()?.to_vec();
+        self.lsn = lsn_at(&self.blk, self.page.len(), self.current_position);
         self.current_position += std::mem::size_of::<i32>() + record.len();
         Some(record)
     }
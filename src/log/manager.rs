@@ -1,19 +1,39 @@
 use std::{path::PathBuf, sync::Arc};
 
-use log::{debug, trace};
+use log::{debug, trace, warn};
 
 use crate::{
-    file::{PageId, FileManager, Page},
+    file::{DurabilityMode, FileError, FileManager, Lsn, Page, PageId, checksum::crc32},
     log::iterator::LogIterator,
 };
 
+/// Position of the boundary of the records as the page was last written.
+const WRITTEN_BOUNDARY_POS: usize = 0;
+/// Position of the checksum of the records as the page was last written.
+const WRITTEN_CRC_POS: usize = WRITTEN_BOUNDARY_POS + 4;
+/// Position of the boundary of the records, the offset of the newest one.
+pub(crate) const BOUNDARY_POS: usize = WRITTEN_CRC_POS + 4;
+/// Position of the checksum of the records from the boundary to the end of the page.
+const CRC_POS: usize = BOUNDARY_POS + 4;
+/// Size of the header in front of the records of a log page.
+const HEADER_SIZE: usize = CRC_POS + 4;
+
+/// Appends records to the log and makes them durable.
+///
+/// Records fill each page from its end towards its start, and the current page is
+/// written again on every flush until it is full. Its header holds the boundary and
+/// checksum of the records as the page was written last, followed by the ones of the
+/// records it holds now. Rewriting the page only changes the header and the space in
+/// front of the records written before, so if a crash tears the write, one of the two
+/// pairs still matches the records that were durable. The torn tail is discarded when
+/// the log is opened.
 pub struct LogManager {
     file_manager: Arc<FileManager>,
     log_file: PathBuf,
     log_page: Page,
     current_page: PageId,
-    latest_lsn: usize,
-    latest_saved_lsn: usize,
+    latest_lsn: Lsn,
+    latest_saved_lsn: Lsn,
 }
 
 impl LogManager {
@@ -26,91 +46,210 @@ impl LogManager {
         let page_size = file_manager.page_size();
         let mut log_page = Page::with_size(page_size);
         let log_size = file_manager.size(log_file.as_path())?;
+        let read_only = file_manager.options().read_only;
 
-        let current_page = if log_size == 0 {
+        let current_page = if log_size == 0 && read_only {
+            trace!("Log file at {:?} is empty and read-only.", log_file);
+            set_header(&mut log_page, page_size)?;
+            PageId::new(log_file.clone(), 0)
+        } else if log_size == 0 {
             trace!("Log file at {:?} is empty. Allocating page.", log_file);
             let blk = file_manager.append_page(log_file.as_path())?;
-            log_page.set_integer(0, file_manager.page_size() as i32)?;
+            set_header(&mut log_page, page_size)?;
             file_manager.write(&blk, &log_page)?;
             blk
         } else {
             trace!("Log file at {:?} already exists.", log_file);
             let page = PageId::new(log_file.clone(), log_size - 1);
-            file_manager.read(&page, &mut log_page)?;
+            let mut torn = false;
+            if let Err(e) = file_manager.read(&page, &mut log_page) {
+                if !matches!(
+                    e.downcast_ref::<FileError>(),
+                    Some(FileError::ChecksumMismatch { .. })
+                ) {
+                    return Err(e);
+                }
+                warn!("Log page {} was torn: {}", page, e);
+                file_manager.read_unverified(&page, &mut log_page)?;
+                torn = true;
+            }
+            let boundary = log_page.get_integer(BOUNDARY_POS)? as usize;
+            // A page without any intact records was torn on its first write
+            let durable = durable_boundary(&log_page)?.unwrap_or(page_size);
+            if torn || durable != boundary {
+                warn!(
+                    "Discarding the torn tail of log page {}, keeping {} bytes of records",
+                    page,
+                    page_size - durable
+                );
+                set_header(&mut log_page, durable)?;
+                if !read_only {
+                    file_manager.write(&page, &log_page)?;
+                }
+            } else {
+                set_header(&mut log_page, boundary)?;
+            }
             page
         };
 
+        let boundary = log_page.get_integer(BOUNDARY_POS)? as usize;
+        let latest_lsn = lsn_at(&current_page, page_size, boundary);
         debug!("Log manager initialization done");
         Ok(Self {
             file_manager,
             log_file,
             log_page,
             current_page,
-            latest_lsn: 0,
-            latest_saved_lsn: 0,
+            latest_lsn,
+            latest_saved_lsn: latest_lsn,
         })
     }
 
-    pub fn append(&mut self, record: &[u8]) -> anyhow::Result<usize> {
-        let mut boundary = self.log_page.get_integer(0)? as usize;
+    /// Appends a record to the log and returns its LSN.
+    ///
+    /// LSNs are log positions, so they keep growing across restarts of the database.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`FileError::ReadOnly`] if the log is read-only.
+    pub fn append(&mut self, record: &[u8]) -> anyhow::Result<Lsn> {
+        if self.is_read_only() {
+            return Err(FileError::ReadOnly {
+                path: self.log_file.clone(),
+            }
+            .into());
+        }
+        let mut boundary = self.log_page.get_integer(BOUNDARY_POS)? as usize;
 
         let record_size = record.len();
         let bytes_needed = record_size + std::mem::size_of::<i32>();
 
-        if boundary < std::mem::size_of::<i32>() + bytes_needed {
+        if boundary < HEADER_SIZE + bytes_needed {
             // Not enough space for the record and its size
             self.flush_internal()?;
             self.current_page = self.append_new_page()?;
-            boundary = self.log_page.get_integer(0)? as usize;
+            boundary = self.log_page.get_integer(BOUNDARY_POS)? as usize;
         }
 
         let rec_pos = boundary - bytes_needed;
         self.log_page.set_bytes(rec_pos, record)?;
-        self.log_page.set_integer(0, rec_pos as i32)?;
-        self.latest_lsn += 1;
+        self.log_page.set_integer(BOUNDARY_POS, rec_pos as i32)?;
+        self.latest_lsn = lsn_at(&self.current_page, self.log_page.len(), rec_pos);
         Ok(self.latest_lsn)
     }
 
-    pub fn flush(&mut self, lsn: usize) -> anyhow::Result<()> {
+    pub fn flush(&mut self, lsn: Lsn) -> anyhow::Result<()> {
         if lsn >= self.latest_saved_lsn {
             return self.flush_internal();
         }
         Ok(())
     }
 
+    /// Writes the current log page, making every appended record durable.
+    pub fn flush_all(&mut self) -> anyhow::Result<()> {
+        self.flush_internal()
+    }
+
+    /// Returns the LSN of the most recently appended record.
+    pub fn latest_lsn(&self) -> Lsn {
+        self.latest_lsn
+    }
+
+    /// Returns whether the log is read-only, in which case nothing can be appended.
+    pub fn is_read_only(&self) -> bool {
+        self.file_manager.options().read_only
+    }
+
     fn flush_internal(&mut self) -> anyhow::Result<()> {
+        if self.is_read_only() || self.latest_lsn == self.latest_saved_lsn {
+            return Ok(());
+        }
+        let boundary = self.log_page.get_integer(BOUNDARY_POS)? as usize;
+        let crc = crc32(&self.log_page.content()[boundary..]);
+        self.log_page.set_integer(CRC_POS, crc as i32)?;
         self.file_manager
             .write(&self.current_page, &self.log_page)?;
+        if self.file_manager.options().durability == DurabilityMode::FsyncOnFlush {
+            self.file_manager.sync(&self.log_file)?;
+        }
+        // What this write made durable is what the next write of the page falls back to
+        self.log_page
+            .set_integer(WRITTEN_BOUNDARY_POS, boundary as i32)?;
+        self.log_page.set_integer(WRITTEN_CRC_POS, crc as i32)?;
         self.latest_saved_lsn = self.latest_lsn;
         Ok(())
     }
 
-    pub(crate) fn iter(&self) -> anyhow::Result<LogIterator> {
+    /// Flushes the current log page and returns an iterator over all records, newest first.
+    pub(crate) fn iter(&mut self) -> anyhow::Result<LogIterator> {
+        self.flush_internal()?;
         LogIterator::new(self.file_manager.clone(), self.current_page.clone())
     }
 
     fn append_new_page(&mut self) -> anyhow::Result<PageId> {
         let blk = self.file_manager.append_page(self.log_file.as_path())?;
-        self.log_page
-            .set_integer(0, self.file_manager.page_size() as i32)?;
+        set_header(&mut self.log_page, self.file_manager.page_size())?;
         self.file_manager.write(&blk, &self.log_page)?;
         Ok(blk)
     }
 }
 
+/// Sets both boundaries of a log page to `boundary`, with the checksum of the
+/// records behind it.
+fn set_header(page: &mut Page, boundary: usize) -> anyhow::Result<()> {
+    let crc = crc32(&page.content()[boundary..]) as i32;
+    page.set_integer(WRITTEN_BOUNDARY_POS, boundary as i32)?;
+    page.set_integer(WRITTEN_CRC_POS, crc)?;
+    page.set_integer(BOUNDARY_POS, boundary as i32)?;
+    page.set_integer(CRC_POS, crc)?;
+    Ok(())
+}
+
+/// Returns the boundary of the intact records of a log page, or `None` if neither
+/// boundary in its header matches its checksum.
+///
+/// The current boundary is tried first, then the one the page was written with before.
+fn durable_boundary(page: &Page) -> anyhow::Result<Option<usize>> {
+    for (boundary_pos, crc_pos) in [
+        (BOUNDARY_POS, CRC_POS),
+        (WRITTEN_BOUNDARY_POS, WRITTEN_CRC_POS),
+    ] {
+        // A torn header may hold any value, negative ones included
+        let boundary = page.get_integer(boundary_pos)? as u32 as usize;
+        if (HEADER_SIZE..=page.len()).contains(&boundary)
+            && crc32(&page.content()[boundary..]) == page.get_integer(crc_pos)? as u32
+        {
+            return Ok(Some(boundary));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the LSN of the record starting at `rec_pos` in the given log page.
+///
+/// Records fill each page from its end towards its start, so the number of bytes
+/// in front of a record grows with every record appended.
+pub(crate) fn lsn_at(page_id: &PageId, page_size: usize, rec_pos: usize) -> Lsn {
+    page_id.block_no() * page_size as Lsn + (page_size - rec_pos) as Lsn
+}
+
 #[cfg(test)]
 mod test {
 
-    use tempfile::TempDir;
+    use std::path::Path;
+
+    use crate::file::{
+        FileOptions,
+        backend::{MemoryBackend, StorageBackend},
+    };
 
     use super::*;
 
-    fn temp_log_manager(page_size: usize) -> (LogManager, TempDir) {
-        let (fm, tmp) = crate::file::manager::test::temp_file_manager(page_size);
+    fn memory_log_manager(page_size: usize) -> (LogManager, Arc<MemoryBackend>) {
+        let (fm, backend) = crate::file::manager::test::memory_file_manager(page_size);
         (
-            LogManager::new(Arc::new(fm), tmp.path().join("logfile"))
-                .expect("Failed to create LogManager"),
-            tmp,
+            LogManager::new(Arc::new(fm), "logfile").expect("Failed to create LogManager"),
+            backend,
         )
     }
 
@@ -131,28 +270,25 @@ mod test {
 
     #[test]
     fn empty_log_iterates_nothing() {
-        let (log, _tmp) = temp_log_manager(4096);
+        let (mut log, _) = memory_log_manager(4096);
         let items: Vec<_> = log.iter().unwrap().collect();
         assert!(items.is_empty());
     }
 
     #[test]
     fn append_and_iter_single_page() {
-        let (mut lm, _) = temp_log_manager(4096);
+        let (mut lm, _) = memory_log_manager(4096);
         let mut last_lsn = 0;
         for i in 1..=5 {
-            last_lsn = lm
+            let lsn = lm
                 .append(&mk_record(&format!("rec{:03}", i), 1000 + i))
                 .unwrap();
-            assert_eq!(last_lsn, i as usize);
+            assert!(lsn > last_lsn);
+            last_lsn = lsn;
         }
         lm.flush(last_lsn).unwrap();
 
-        let got: Vec<_> = lm
-            .iter()
-            .unwrap()
-            .map(|e| parse_entry(&e))
-            .collect();
+        let got: Vec<_> = lm.iter().unwrap().map(|e| parse_entry(&e)).collect();
 
         let exp: Vec<_> = (1..=5)
             .rev()
@@ -161,9 +297,75 @@ mod test {
         assert_eq!(got, exp);
     }
 
+    #[test]
+    fn iter_includes_records_that_were_not_flushed() {
+        let (mut lm, _) = memory_log_manager(4096);
+        lm.append(&mk_record("unflushed", 7)).unwrap();
+
+        let got: Vec<_> = lm.iter().unwrap().map(|e| parse_entry(&e)).collect();
+
+        assert_eq!(got, vec![("unflushed".to_string(), 7)]);
+    }
+
+    #[test]
+    fn torn_last_page_is_discarded_on_open() {
+        // setup
+        let (mut lm, backend) = memory_log_manager(4096);
+        let durable = lm.append(&mk_record("durable", 1)).unwrap();
+        lm.flush(durable).unwrap();
+        let fm = Arc::new(FileManager::with_backend(
+            backend.clone(),
+            4096,
+            FileOptions::default(),
+        ));
+        let slot = fm.slot_size();
+        let mut before = vec![0; slot];
+        backend.read(Path::new("logfile"), 0, &mut before).unwrap();
+        let torn = lm.append(&mk_record("torn", 2)).unwrap();
+        lm.flush(torn).unwrap();
+        // Put back the second half of the page, as if the last write had been torn
+        backend
+            .write(Path::new("logfile"), (slot / 2) as u64, &before[slot / 2..])
+            .unwrap();
+
+        // test
+        let mut reopened = LogManager::new(fm, "logfile").unwrap();
+
+        // verify
+        let got: Vec<_> = reopened.iter().unwrap().map(|e| parse_entry(&e)).collect();
+        assert_eq!(got, vec![("durable".to_string(), 1)]);
+        assert_eq!(reopened.latest_lsn(), durable);
+    }
+
+    #[test]
+    fn records_flushed_separately_share_a_page() {
+        // setup
+        let (mut lm, backend) = memory_log_manager(4096);
+        let first = lm.append(&mk_record("first", 1)).unwrap();
+        lm.flush(first).unwrap();
+
+        // test
+        let second = lm.append(&mk_record("second", 2)).unwrap();
+        lm.flush(second).unwrap();
+
+        // verify
+        let fm = Arc::new(FileManager::with_backend(
+            backend,
+            4096,
+            FileOptions::default(),
+        ));
+        assert_eq!(fm.size(Path::new("logfile")).unwrap(), 1);
+        let mut reopened = LogManager::new(fm, "logfile").unwrap();
+        let got: Vec<_> = reopened.iter().unwrap().map(|e| parse_entry(&e)).collect();
+        assert_eq!(
+            got,
+            vec![("second".to_string(), 2), ("first".to_string(), 1)]
+        );
+    }
+
     #[test]
     fn append_across_pages_iterates_newest_page_first() {
-        let (mut lm, _) = temp_log_manager(128);
+        let (mut lm, _) = memory_log_manager(128);
 
         // Each record ~18 bytes in page; 6 fit in 128 -> force 2 pages with 12 records
         let mut last_lsn = 0;
@@ -174,11 +376,7 @@ mod test {
         }
         lm.flush(last_lsn).unwrap();
 
-        let got: Vec<_> = lm
-            .iter()
-            .unwrap()
-            .map(|e| parse_entry(&e).0)
-            .collect();
+        let got: Vec<_> = lm.iter().unwrap().map(|e| parse_entry(&e).0).collect();
 
         // Expect reverse chronological: 12..7 then 6..1
         let mut exp: Vec<String> = (7..=12).rev().map(|i| format!("rec{:03}", i)).collect();
@@ -188,7 +386,7 @@ mod test {
 
     #[test]
     fn flush_persists_records_across_reopen() {
-        let (mut lm, tmp) = temp_log_manager(4096);
+        let (mut lm, backend) = memory_log_manager(4096);
         let mut last_lsn = 0;
         for i in 1..=3 {
             last_lsn = lm
@@ -197,16 +395,55 @@ mod test {
         }
         lm.flush(last_lsn).unwrap();
 
-        let lm2 = LogManager::new(Arc::new(FileManager::new(tmp.path(), 4096).unwrap()), tmp.path().join("logfile")).unwrap();
-        let got: Vec<_> = lm2
-            .iter()
-            .unwrap()
-            .map(|e| parse_entry(&e))
-            .collect();
+        let fm = FileManager::with_backend(backend, 4096, FileOptions::default());
+        let mut lm2 = LogManager::new(Arc::new(fm), "logfile").unwrap();
+        let got: Vec<_> = lm2.iter().unwrap().map(|e| parse_entry(&e)).collect();
         let exp: Vec<_> = (1..=3)
             .rev()
             .map(|i| (format!("rec{:03}", i), 3000 + i))
             .collect();
         assert_eq!(got, exp);
     }
+
+    #[test]
+    fn lsns_keep_growing_across_reopen_and_pages() {
+        // setup
+        let (mut lm, backend) = memory_log_manager(128);
+        let mut lsns = vec![];
+        for i in 1..=8 {
+            lsns.push(lm.append(&mk_record(&format!("rec{:03}", i), i)).unwrap());
+        }
+        lm.flush_all().unwrap();
+
+        // test
+        let fm = FileManager::with_backend(backend, 128, FileOptions::default());
+        let mut reopened = LogManager::new(Arc::new(fm), "logfile").unwrap();
+        let latest_after_reopen = reopened.latest_lsn();
+        lsns.push(reopened.append(&mk_record("rec009", 9)).unwrap());
+
+        // verify
+        assert_eq!(latest_after_reopen, lsns[7]);
+        assert!(lsns.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn iterator_reports_the_lsn_append_returned() {
+        // setup
+        let (mut lm, _) = memory_log_manager(128);
+        let mut lsns = vec![];
+        for i in 1..=8 {
+            lsns.push(lm.append(&mk_record(&format!("rec{:03}", i), i)).unwrap());
+        }
+
+        // test
+        let mut iter = lm.iter().unwrap();
+        let mut iterated = vec![];
+        while iter.next().is_some() {
+            iterated.push(iter.lsn());
+        }
+
+        // verify
+        lsns.reverse();
+        assert_eq!(iterated, lsns);
+    }
 }
@@ -1,16 +1,12 @@
-
 use ::log::info;
 
-mod buffer;
-mod db;
-mod file;
-mod log;
+use rimple::config::SimpleDBConfig;
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     info!("Starting Simple DB");
-    let _ = db::SimpleDB::new("mydb", 4096)?;
+    let _ = SimpleDBConfig::new("mydb").load_env()?.open()?;
 
     info!("Listening to requests");
     Ok(())
@@ -19,7 +15,7 @@ fn main() -> anyhow::Result<()> {
 #[cfg(test)]
 mod test {
 
-    use crate::db::SimpleDB;
+    use rimple::db::SimpleDB;
 
     fn new_test_db(page_size: usize) -> (tempfile::TempDir, SimpleDB) {
         let tmp = tempfile::tempdir().expect("tempdir");
@@ -1,61 +1,105 @@
-use std::{
-    collections::HashMap,
-    sync::{Arc, Mutex},
-};
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
-    buffer::{buffer::Buffer, manager::BufferManager},
+    buffer::{manager::BufferManager, pinned::PinnedBuffer},
     file::PageId,
 };
 
+/// The pins held by a transaction.
+///
+/// Every pin is kept until it is unpinned, so pinning a page twice takes two
+/// unpins to release it. Whatever is still pinned is released when the list is
+/// dropped.
 pub struct BufferList {
-    buffers: HashMap<PageId, Arc<Mutex<Buffer>>>,
-    pins: Vec<PageId>,
-    buffer_manager: Arc<Mutex<BufferManager>>,
+    buffers: HashMap<PageId, Vec<PinnedBuffer>>,
+    buffer_manager: Arc<BufferManager>,
 }
 
 impl BufferList {
-    pub fn new(buffer_manager: Arc<Mutex<BufferManager>>) -> Self {
+    pub fn new(buffer_manager: Arc<BufferManager>) -> Self {
         BufferList {
             buffers: HashMap::new(),
-            pins: Vec::new(),
             buffer_manager,
         }
     }
 
-    pub fn get_buffer(&self, page_id: &PageId) -> Option<&Arc<Mutex<Buffer>>> {
-        self.buffers.get(page_id)
+    pub fn get_buffer(&self, page_id: &PageId) -> Option<&PinnedBuffer> {
+        self.buffers.get(page_id).and_then(|pins| pins.first())
     }
 
     pub fn pin(&mut self, page_id: &PageId) -> anyhow::Result<()> {
-        let buffer = self.buffer_manager.lock().unwrap().pin(page_id)?;
-        self.buffers.insert(page_id.clone(), buffer);
-        self.pins.push(page_id.clone());
+        let buffer = self.buffer_manager.pin(page_id)?;
+        self.buffers
+            .entry(page_id.clone())
+            .or_default()
+            .push(buffer);
 
         Ok(())
     }
 
-    pub fn unpin(&mut self, page_id: &PageId) -> anyhow::Result<()> {
-        if let Some(buffer) = self.buffers.get(page_id) {
-            self.buffer_manager.lock().unwrap().unpin(buffer.clone())?;
-            self.pins.retain(|b| b != page_id);
-            if self.pins.iter().filter(|b| *b == page_id).count() == 0 {
+    /// Releases one pin of the page, if the transaction holds any.
+    pub fn unpin(&mut self, page_id: &PageId) {
+        if let Some(pins) = self.buffers.get_mut(page_id) {
+            pins.pop();
+            if pins.is_empty() {
                 self.buffers.remove(page_id);
             }
         }
+    }
 
-        Ok(())
+    pub fn unpin_all(&mut self) {
+        self.buffers.clear();
     }
+}
 
-    pub fn unpin_all(&mut self) -> anyhow::Result<()> {
-        for page_id in &self.pins {
-            if let Some(buffer) = self.buffers.get(page_id) {
-                self.buffer_manager.lock().unwrap().unpin(buffer.clone())?;
-            }
+#[cfg(test)]
+mod test {
+    use crate::db::SimpleDB;
+
+    use super::*;
+
+    #[test]
+    fn pinning_a_page_twice_takes_two_unpins() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path(), 400).unwrap();
+        let page_id = PageId::new(tmp.path().join("data"), 0);
+        db.file_manager().append_page(page_id.path()).unwrap();
+        let available = db.buffer_manager().available();
+        let mut list = BufferList::new(db.buffer_manager().clone());
+
+        // test
+        list.pin(&page_id).unwrap();
+        list.pin(&page_id).unwrap();
+        list.unpin(&page_id);
+        let still_pinned = list.get_buffer(&page_id).is_some();
+        let available_after_one_unpin = db.buffer_manager().available();
+        list.unpin(&page_id);
+
+        // verify
+        assert!(still_pinned);
+        assert_eq!(available_after_one_unpin, available - 1);
+        assert!(list.get_buffer(&page_id).is_none());
+        assert_eq!(db.buffer_manager().available(), available);
+    }
+
+    #[test]
+    fn dropping_the_list_releases_its_pins() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDB::new(tmp.path(), 400).unwrap();
+        let available = db.buffer_manager().available();
+        let mut list = BufferList::new(db.buffer_manager().clone());
+        for block_no in 0..3 {
+            let page_id = PageId::new(tmp.path().join("data"), block_no);
+            db.file_manager().append_page(page_id.path()).unwrap();
+            list.pin(&page_id).unwrap();
         }
-        self.buffers.clear();
-        self.pins.clear();
 
-        Ok(())
+        // test
+        drop(list);
+
+        // verify
+        assert_eq!(db.buffer_manager().available(), available);
     }
 }
@@ -1,82 +1,134 @@
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
 };
 
 use crate::file::PageId;
 
+/// How long a transaction waits for a lock, unless configured otherwise.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, thiserror::Error)]
 enum LockTableError {
     #[error("Lock table is currently unavailable")]
     LockAbort,
 }
 
-#[derive(Default)]
-pub struct LockTable {
-    locks: Arc<Mutex<HashMap<PageId, i32>>>,
-}
-
 /// Handles locking for transactions.
 /// Each page can have multiple shared locks (indicated by a positive integer value in the locks map),
 /// or a single exclusive lock (indicated by a negative integer value in the locks map).
 /// If the page is not locked, it will not be present in the locks map.
+///
+/// A lock that conflicts with the locks of other transactions is waited for. If
+/// it is not granted within the lock timeout, the request is aborted, which also
+/// breaks deadlocks between transactions.
+pub struct LockTable {
+    locks: Mutex<HashMap<PageId, i32>>,
+    /// Signalled whenever a lock is released.
+    released: Condvar,
+    max_wait: Duration,
+}
+
+impl Default for LockTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LockTable {
     pub fn new() -> Self {
+        Self::with_timeout(DEFAULT_LOCK_TIMEOUT)
+    }
+
+    /// Creates a lock table whose lock requests give up after `max_wait`.
+    pub fn with_timeout(max_wait: Duration) -> Self {
         LockTable {
-            locks: Arc::new(Mutex::new(HashMap::new())),
+            locks: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+            max_wait,
         }
     }
 
     /// Acquires a shared lock on the specified page.
-    pub fn s_lock(&mut self, page_id: &PageId) -> anyhow::Result<()> {
-        if let Ok(mut locks) = self.locks.try_lock()
-            && !has_x_lock(&locks, page_id)
-        {
-            *locks.entry(page_id.clone()).or_insert(0) += 1; // will not be negative
-            return Ok(());
-        }
-
-        Err(From::from(LockTableError::LockAbort))
+    pub fn s_lock(&self, page_id: &PageId) -> anyhow::Result<()> {
+        let mut locks = self.wait_while(|locks| has_x_lock(locks, page_id))?;
+        *locks.entry(page_id.clone()).or_insert(0) += 1; // will not be negative
+        Ok(())
     }
 
     /// Acquires an exclusive lock on the specified page.
-    pub fn x_lock(&mut self, page_id: &PageId) -> anyhow::Result<()> {
-        if let Ok(mut locks) = self.locks.try_lock()
-            && !has_other_s_locks(&locks, page_id)
-        {
-            *locks.entry(page_id.clone()).or_insert(-1) = -1; // means eXclusive lock
-            return Ok(());
-        }
+    ///
+    /// The caller is expected to hold a shared lock on the page already.
+    pub fn x_lock(&self, page_id: &PageId) -> anyhow::Result<()> {
+        let mut locks = self.wait_while(|locks| has_other_s_locks(locks, page_id))?;
+        *locks.entry(page_id.clone()).or_insert(-1) = -1; // means eXclusive lock
+        Ok(())
+    }
 
-        Err(From::from(LockTableError::LockAbort))
+    /// Acquires an exclusive lock on a page the caller holds no lock on yet.
+    ///
+    /// Unlike upgrading a shared lock, this cannot deadlock with another
+    /// transaction upgrading its own shared lock on the same page.
+    pub fn x_lock_unshared(&self, page_id: &PageId) -> anyhow::Result<()> {
+        let mut locks = self.wait_while(|locks| get_lock_val(locks, page_id) != 0)?;
+        locks.insert(page_id.clone(), -1);
+        Ok(())
     }
 
     /// Releases the lock on the specified page.
     /// If there are multiple shared locks, it will decrement the count.
     /// If there is only one lock (either shared or exclusive), it will remove the entry from the locks map.
-    pub fn unlock(&mut self, page_id: &PageId) -> anyhow::Result<()> {
-        if let Ok(mut locks) = self.locks.try_lock()
-            && let Some(count) = locks.get_mut(page_id)
-        {
-            if *count > 1 {
-                *count -= 1;
-            } else {
-                locks.remove(page_id);
+    ///
+    /// Waiters are woken either way: a transaction upgrading its shared lock waits
+    /// for the other shared locks to drop to its own.
+    pub fn unlock(&self, page_id: &PageId) -> anyhow::Result<()> {
+        let mut locks = self.lock_locks()?;
+        let Some(count) = locks.get_mut(page_id) else {
+            return Err(From::from(LockTableError::LockAbort));
+        };
+        if *count > 1 {
+            *count -= 1;
+        } else {
+            locks.remove(page_id);
+        }
+        self.released.notify_all();
+        Ok(())
+    }
+
+    /// Waits until `conflicts` no longer holds, for at most the lock timeout.
+    fn wait_while(
+        &self,
+        conflicts: impl Fn(&HashMap<PageId, i32>) -> bool,
+    ) -> anyhow::Result<MutexGuard<'_, HashMap<PageId, i32>>> {
+        let deadline = Instant::now() + self.max_wait;
+        let mut locks = self.lock_locks()?;
+        while conflicts(&locks) {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(From::from(LockTableError::LockAbort));
             }
-            return Ok(());
+            locks = self
+                .released
+                .wait_timeout(locks, deadline - now)
+                .map_err(|_| LockTableError::LockAbort)?
+                .0;
         }
+        Ok(locks)
+    }
 
-        Err(From::from(LockTableError::LockAbort))
+    fn lock_locks(&self) -> Result<MutexGuard<'_, HashMap<PageId, i32>>, LockTableError> {
+        self.locks.lock().map_err(|_| LockTableError::LockAbort)
     }
 }
 
-fn has_x_lock(locks: &MutexGuard<HashMap<PageId, i32>>, blk: &PageId) -> bool {
+fn has_x_lock(locks: &HashMap<PageId, i32>, blk: &PageId) -> bool {
     get_lock_val(locks, blk) < 0
 }
-fn has_other_s_locks(locks: &MutexGuard<HashMap<PageId, i32>>, blk: &PageId) -> bool {
+fn has_other_s_locks(locks: &HashMap<PageId, i32>, blk: &PageId) -> bool {
     get_lock_val(locks, blk) > 1
 }
-fn get_lock_val(locks: &MutexGuard<HashMap<PageId, i32>>, blk: &PageId) -> i32 {
+fn get_lock_val(locks: &HashMap<PageId, i32>, blk: &PageId) -> i32 {
     match locks.get(blk) {
         Some(&ival) => ival,
         None => 0,
@@ -90,14 +142,14 @@ mod test {
 
     #[test]
     fn multiple_shared_locks_are_allowed() {
-        let mut table = LockTable::new();
+        let table = LockTable::new();
         table.s_lock(&PageId::new("testfile".into(), 1)).unwrap();
         table.s_lock(&PageId::new("testfile".into(), 2)).unwrap();
     }
 
     #[test]
     fn exclusive_lock_blocks_other_locks() {
-        let mut table = LockTable::new();
+        let table = LockTable::with_timeout(Duration::from_millis(10));
         table.x_lock(&PageId::new("testfile".into(), 1)).unwrap();
         table
             .s_lock(&PageId::new("testfile".into(), 1))
@@ -105,4 +157,54 @@ mod test {
         table.unlock(&PageId::new("testfile".into(), 1)).unwrap();
         table.s_lock(&PageId::new("testfile".into(), 1)).unwrap();
     }
+
+    #[test]
+    fn waiting_lock_is_granted_once_released() {
+        // setup
+        let table = LockTable::with_timeout(Duration::from_secs(10));
+        let page_id = PageId::new("testfile".into(), 1);
+        table.s_lock(&page_id).unwrap();
+        table.x_lock(&page_id).unwrap();
+
+        // test
+        let waited = std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                let start = Instant::now();
+                table.s_lock(&page_id).unwrap();
+                start.elapsed()
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            table.unlock(&page_id).unwrap();
+            waiter.join().unwrap()
+        });
+
+        // verify
+        assert!(waited >= Duration::from_millis(50));
+        assert!(waited < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn upgrade_is_granted_once_other_shared_lock_is_released() {
+        // setup
+        let table = LockTable::with_timeout(Duration::from_secs(10));
+        let page_id = PageId::new("testfile".into(), 1);
+        table.s_lock(&page_id).unwrap();
+        table.s_lock(&page_id).unwrap();
+
+        // test
+        let waited = std::thread::scope(|scope| {
+            let upgrader = scope.spawn(|| {
+                let start = Instant::now();
+                table.x_lock(&page_id).unwrap();
+                start.elapsed()
+            });
+            std::thread::sleep(Duration::from_millis(50));
+            table.unlock(&page_id).unwrap();
+            upgrader.join().unwrap()
+        });
+
+        // verify
+        assert!(waited >= Duration::from_millis(50));
+        assert!(waited < Duration::from_secs(1));
+    }
 }
@@ -9,7 +9,7 @@ use crate::{file::PageId, tx::concurrency::lock_table::LockTable};
 /// The lock table is shared across all transactions, and the ConcurrencyManager interacts with it to acquire and release locks.
 pub struct ConcurrencyManager {
     // static member, there should only be one lock table for the entire system
-    lock_tbl: Arc<Mutex<LockTable>>,
+    lock_tbl: Arc<LockTable>,
 
     // TODO: Refactor string here.
     // It should be an enum with variants SharedLock and ExclusiveLock
@@ -20,7 +20,7 @@ pub struct ConcurrencyManager {
 // https://github.com/cutsea110/simpledb/blob/master/src/tx/concurrency/manager.rs#L77 to see if
 // the locking has to be reworked
 impl ConcurrencyManager {
-    pub fn new(lock_tbl: Arc<Mutex<LockTable>>) -> Self {
+    pub fn new(lock_tbl: Arc<LockTable>) -> Self {
         Self {
             lock_tbl,
             locks: Arc::new(Mutex::new(HashMap::new())),
@@ -31,17 +31,21 @@ impl ConcurrencyManager {
         if let Ok(mut locks) = self.locks.try_lock()
             && !locks.contains_key(page_id)
         {
-            self.lock_tbl.lock().unwrap().s_lock(page_id)?;
+            self.lock_tbl.s_lock(page_id)?;
             locks.insert(page_id.clone(), "S".to_string());
         }
 
         Ok(())
     }
 
+    /// Acquires an exclusive lock, upgrading the shared lock on the page if there is one.
     pub fn x_lock(&mut self, page_id: &PageId) -> anyhow::Result<()> {
         if !self.has_x_lock(page_id) {
-            self.s_lock(page_id)?;
-            self.lock_tbl.lock().unwrap().x_lock(page_id)?;
+            if self.locks.lock().unwrap().contains_key(page_id) {
+                self.lock_tbl.x_lock(page_id)?;
+            } else {
+                self.lock_tbl.x_lock_unshared(page_id)?;
+            }
             self.locks
                 .lock()
                 .unwrap()
@@ -53,7 +57,7 @@ impl ConcurrencyManager {
 
     pub fn release(&mut self) -> anyhow::Result<()> {
         for blk in self.locks.lock().unwrap().keys() {
-            self.lock_tbl.lock().unwrap().unlock(blk)?;
+            self.lock_tbl.unlock(blk)?;
         }
         self.locks.lock().unwrap().clear();
         Ok(())
@@ -0,0 +1,286 @@
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::bail;
+
+use crate::{file::PageId, tx::transaction::Transaction};
+
+/// Tracks which pages of a data file are free so they can be reused.
+///
+/// The map lives in a sidecar file next to the data file (`data` → `data.fsm`)
+/// and holds one bit per data page; a set bit marks a free page. Pages beyond
+/// the end of the map are in use, so a file without a map has no free pages.
+///
+/// All updates of the map go through the owning [`Transaction`] as logged byte
+/// writes. Rolling back or recovering a transaction therefore restores the map
+/// and undoes its allocations and frees without any extra bookkeeping.
+///
+/// Allocations and frees of a file are serialized by an exclusive lock on the size
+/// of its map, taken before the map is first read. Reading first and upgrading to an
+/// exclusive lock for the update would deadlock two concurrent allocators.
+pub(crate) struct FreeSpaceMap {
+    data_file: PathBuf,
+    map_file: PathBuf,
+}
+
+impl FreeSpaceMap {
+    pub(crate) fn new(data_file: &Path) -> Self {
+        let mut map_file = OsString::from(data_file.as_os_str());
+        map_file.push(".fsm");
+        Self {
+            data_file: data_file.to_path_buf(),
+            map_file: map_file.into(),
+        }
+    }
+
+    /// Hands out a free page of the data file, or appends a new one if none is free.
+    pub(crate) fn allocate(&self, tx: &mut Transaction) -> anyhow::Result<PageId> {
+        let block_no = match self.find_free(tx)? {
+            Some(block_no) => block_no,
+            None => {
+                // A fresh page starts out free without logging, so that undoing the logged
+                // allocation below returns it to the free list instead of leaking it.
+                let block_no = tx.append(&self.data_file)?.block_no();
+                self.set_free(tx, block_no, true, false)?;
+                block_no
+            }
+        };
+        self.set_free(tx, block_no, false, true)?;
+
+        Ok(PageId::new(self.data_file.clone(), block_no))
+    }
+
+    /// Returns a page of the data file to the free list.
+    pub(crate) fn free(&self, tx: &mut Transaction, block_no: u64) -> anyhow::Result<()> {
+        if block_no >= tx.size(&self.data_file)? {
+            bail!(
+                "Cannot free page {} beyond the end of {:?}",
+                block_no,
+                self.data_file
+            );
+        }
+        if self.is_free(tx, block_no)? {
+            bail!("Page {} of {:?} is already free", block_no, self.data_file);
+        }
+        self.set_free(tx, block_no, true, true)
+    }
+
+    /// Scans the map for the first free page.
+    fn find_free(&self, tx: &mut Transaction) -> anyhow::Result<Option<u64>> {
+        tx.x_lock_size(&self.map_file)?;
+        let page_size = tx.page_size();
+        for map_block in 0..tx.size(&self.map_file)? {
+            let page_id = PageId::new(self.map_file.clone(), map_block);
+            tx.pin(&page_id)?;
+            let found = first_set_bit(tx, &page_id, page_size);
+            tx.unpin(&page_id)?;
+
+            if let Some(bit) = found? {
+                return Ok(Some(map_block * bits_per_page(page_size) + bit));
+            }
+        }
+        Ok(None)
+    }
+
+    fn is_free(&self, tx: &mut Transaction, block_no: u64) -> anyhow::Result<bool> {
+        tx.x_lock_size(&self.map_file)?;
+        let (page_id, offset, mask) = self.locate(tx, block_no);
+        if page_id.block_no() >= tx.size(&self.map_file)? {
+            return Ok(false);
+        }
+        tx.pin(&page_id)?;
+        let byte = tx.get_u8(&page_id, offset);
+        tx.unpin(&page_id)?;
+        Ok(byte? & mask != 0)
+    }
+
+    fn set_free(
+        &self,
+        tx: &mut Transaction,
+        block_no: u64,
+        free: bool,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        let (page_id, offset, mask) = self.locate(tx, block_no);
+        while tx.size(&self.map_file)? <= page_id.block_no() {
+            tx.append(&self.map_file)?;
+        }
+
+        tx.pin(&page_id)?;
+        let result = tx.get_u8(&page_id, offset).and_then(|byte| {
+            let byte = if free { byte | mask } else { byte & !mask };
+            tx.set_u8(&page_id, offset, byte, log)
+        });
+        tx.unpin(&page_id)?;
+        result
+    }
+
+    /// Returns the map page, byte offset and bit mask describing `block_no`.
+    fn locate(&self, tx: &Transaction, block_no: u64) -> (PageId, usize, u8) {
+        let bits = bits_per_page(tx.page_size());
+        let bit = block_no % bits;
+        (
+            PageId::new(self.map_file.clone(), block_no / bits),
+            (bit / 8) as usize,
+            1 << (bit % 8),
+        )
+    }
+}
+
+fn bits_per_page(page_size: usize) -> u64 {
+    page_size as u64 * 8
+}
+
+/// Finds the index of the first set bit in a pinned map page.
+fn first_set_bit(
+    tx: &mut Transaction,
+    page_id: &PageId,
+    page_size: usize,
+) -> anyhow::Result<Option<u64>> {
+    let mut offset = 0;
+    // Skip over fully allocated stretches eight bytes at a time
+    while offset + 8 <= page_size && tx.get_u64(page_id, offset)? == 0 {
+        offset += 8;
+    }
+    while offset < page_size {
+        let byte = tx.get_u8(page_id, offset)?;
+        if byte != 0 {
+            return Ok(Some(offset as u64 * 8 + byte.trailing_zeros() as u64));
+        }
+        offset += 1;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use crate::{
+        config::SimpleDBConfig,
+        db::SimpleDB,
+        file::{FileOptions, backend::MemoryBackend},
+    };
+
+    use super::*;
+
+    fn memory_db() -> SimpleDB {
+        let backend = Arc::new(MemoryBackend::new());
+        SimpleDB::with_backend("db", 128, FileOptions::default(), backend).unwrap()
+    }
+
+    #[test]
+    fn freed_pages_are_reused_before_the_file_grows() {
+        // setup
+        let db = memory_db();
+        let data = Path::new("db/data");
+        let mut tx = db.new_transaction().unwrap();
+        let first = tx.allocate_page(data).unwrap();
+        let second = tx.allocate_page(data).unwrap();
+        tx.free_page(&first).unwrap();
+        tx.commit().unwrap();
+
+        // test
+        let mut tx = db.new_transaction().unwrap();
+        let reused = tx.allocate_page(data).unwrap();
+        let fresh = tx.allocate_page(data).unwrap();
+
+        // verify
+        assert_eq!(second.block_no(), 1);
+        assert_eq!(reused, first);
+        assert_eq!(fresh.block_no(), 2);
+        assert_eq!(tx.size(data).unwrap(), 3);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn rolling_back_a_free_keeps_the_page_allocated() {
+        // setup
+        let db = memory_db();
+        let data = Path::new("db/data");
+        let mut tx = db.new_transaction().unwrap();
+        let page = tx.allocate_page(data).unwrap();
+        tx.commit().unwrap();
+
+        let mut tx = db.new_transaction().unwrap();
+        tx.free_page(&page).unwrap();
+
+        // test
+        tx.rollback().unwrap();
+
+        // verify
+        let mut tx = db.new_transaction().unwrap();
+        assert_eq!(tx.allocate_page(data).unwrap().block_no(), 1);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn rolling_back_an_allocation_returns_the_page_to_the_free_list() {
+        // setup
+        let db = memory_db();
+        let data = Path::new("db/data");
+        let mut tx = db.new_transaction().unwrap();
+        let page = tx.allocate_page(data).unwrap();
+
+        // test
+        tx.rollback().unwrap();
+
+        // verify
+        let mut tx = db.new_transaction().unwrap();
+        assert_eq!(tx.allocate_page(data).unwrap(), page);
+        assert_eq!(tx.size(data).unwrap(), 1);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn concurrent_allocations_do_not_deadlock() {
+        // setup
+        let tmp = tempfile::tempdir().unwrap();
+        let db = SimpleDBConfig::new(tmp.path())
+            .page_size(128)
+            .lock_timeout(Duration::from_secs(5))
+            .open()
+            .unwrap();
+        let data = tmp.path().join("data");
+        let map = FreeSpaceMap::new(&data);
+        let mut tx = db.new_transaction().unwrap();
+        let first = tx.allocate_page(&data).unwrap();
+        tx.free_page(&first).unwrap();
+        tx.commit().unwrap();
+
+        // test
+        let second = thread::scope(|scope| {
+            // The first allocator has found the free page but not yet taken it
+            let mut tx1 = db.new_transaction().unwrap();
+            let block_no = map.find_free(&mut tx1).unwrap().unwrap();
+            let allocator = scope.spawn(|| {
+                let mut tx2 = db.new_transaction().unwrap();
+                let page = tx2.allocate_page(&data).unwrap();
+                tx2.commit().unwrap();
+                page
+            });
+            thread::sleep(Duration::from_millis(50));
+            map.set_free(&mut tx1, block_no, false, true).unwrap();
+            tx1.commit().unwrap();
+            allocator.join().unwrap()
+        });
+
+        // verify
+        assert_eq!(first.block_no(), 0);
+        assert_eq!(second.block_no(), 1);
+    }
+
+    #[test]
+    fn freeing_a_page_twice_fails() {
+        let db = memory_db();
+        let data = Path::new("db/data");
+        let mut tx = db.new_transaction().unwrap();
+        let page = tx.allocate_page(data).unwrap();
+        tx.free_page(&page).unwrap();
+
+        assert!(tx.free_page(&page).is_err());
+        tx.commit().unwrap();
+    }
+}
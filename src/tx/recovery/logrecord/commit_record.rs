@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex};
 
 use crate::{
-    file::Page,
+    file::{Lsn, Page},
     log::manager::LogManager,
     tx::recovery::logrecord::{LogRecord, TxOp, UndoContext},
 };
@@ -19,12 +19,12 @@ impl CommitRecord {
     pub(crate) fn write_to_log(
         log_manager: Arc<Mutex<LogManager>>,
         tx_num: i32,
-    ) -> anyhow::Result<usize> {
+    ) -> anyhow::Result<Lsn> {
         let mut page = Page::with_size(std::mem::size_of::<i32>() * 2);
         page.set_integer(0, TxOp::Commit as i32)?;
         page.set_integer(std::mem::size_of::<i32>(), tx_num)?;
 
-        log_manager.lock().unwrap().append(&page.content())
+        log_manager.lock().unwrap().append(page.content())
     }
 }
 
@@ -1,19 +1,22 @@
 pub mod checkpoint_record;
 pub mod commit_record;
 pub mod rollback_record;
-pub mod set_i32_record;
-pub mod set_string_record;
+pub mod set_value_record;
 pub mod start_record;
 
-use self::set_string_record::SetStringRecord;
+use self::set_value_record::{
+    SetBoolRecord, SetF64Record, SetI32Record, SetI64Record, SetStringRecord, SetTimestampRecord,
+    SetU8Record, SetU64Record,
+};
 use anyhow::bail;
+use log::warn;
 
-use crate::buffer::manager::BufferManager;
+use crate::buffer::{manager::BufferManager, pinned::PinnedBuffer};
 use crate::{
-    file::Page,
+    file::{FileError, Page, PageId},
     tx::recovery::logrecord::{checkpoint_record::CheckpointRecord, start_record::StartRecord},
 };
-use std::sync::{Arc, Mutex};
+use std::{path::PathBuf, sync::Arc};
 
 #[derive(Debug, PartialEq)]
 pub enum TxOp {
@@ -23,6 +26,12 @@ pub enum TxOp {
     Rollback,
     SetI32,
     SetString,
+    SetI64,
+    SetU64,
+    SetF64,
+    SetBool,
+    SetU8,
+    SetTimestamp,
 }
 
 pub trait LogRecord {
@@ -45,6 +54,12 @@ pub fn from_page(bytes: &[u8]) -> anyhow::Result<Box<dyn LogRecord>> {
         3 => TxOp::Rollback,
         4 => TxOp::SetI32,
         5 => TxOp::SetString,
+        6 => TxOp::SetI64,
+        7 => TxOp::SetU64,
+        8 => TxOp::SetF64,
+        9 => TxOp::SetBool,
+        10 => TxOp::SetU8,
+        11 => TxOp::SetTimestamp,
         _ => bail!("Unknown log record type: {}", op_code),
     };
     match op {
@@ -52,11 +67,54 @@ pub fn from_page(bytes: &[u8]) -> anyhow::Result<Box<dyn LogRecord>> {
         TxOp::Start => Ok(Box::new(StartRecord::new(page)?)),
         TxOp::Commit => Ok(Box::new(commit_record::CommitRecord::new(page)?)),
         TxOp::Rollback => Ok(Box::new(rollback_record::RollbackRecord::new(page)?)),
-        TxOp::SetI32 => Ok(Box::new(set_i32_record::SetI32Record::new(page)?)),
+        TxOp::SetI32 => Ok(Box::new(SetI32Record::new(page)?)),
         TxOp::SetString => Ok(Box::new(SetStringRecord::new(page)?)),
+        TxOp::SetI64 => Ok(Box::new(SetI64Record::new(page)?)),
+        TxOp::SetU64 => Ok(Box::new(SetU64Record::new(page)?)),
+        TxOp::SetF64 => Ok(Box::new(SetF64Record::new(page)?)),
+        TxOp::SetBool => Ok(Box::new(SetBoolRecord::new(page)?)),
+        TxOp::SetU8 => Ok(Box::new(SetU8Record::new(page)?)),
+        TxOp::SetTimestamp => Ok(Box::new(SetTimestampRecord::new(page)?)),
     }
 }
 
 pub struct UndoContext {
-    pub buffer_manager: Arc<Mutex<BufferManager>>,
+    pub buffer_manager: Arc<BufferManager>,
+    /// The directory the logged pages were in and the directory they are in now,
+    /// if the database was moved since, e.g. by restoring it from a backup.
+    pub relocation: Option<(PathBuf, PathBuf)>,
+}
+
+impl UndoContext {
+    /// Pins the page a log record changed, or returns `None` if the page is not in its file.
+    ///
+    /// A crash can lose the append of a page after changes to it were already logged.
+    /// Such a page never reached storage, so there is nothing to undo.
+    pub fn pin(&self, page_id: &PageId) -> anyhow::Result<Option<PinnedBuffer>> {
+        let relocated;
+        let page_id = match &self.relocation {
+            Some((from, to)) => match page_id.path().strip_prefix(from) {
+                Ok(rest) => {
+                    relocated = PageId::new(to.join(rest), page_id.block_no());
+                    &relocated
+                }
+                Err(_) => page_id,
+            },
+            None => page_id,
+        };
+        let file_manager = self.buffer_manager.file_manager();
+        if page_id.block_no() >= file_manager.size(page_id.path())? {
+            return Ok(None);
+        }
+        match self.buffer_manager.pin(page_id) {
+            // A torn page still holds its durable contents wherever the crash did not reach,
+            // and undo restores the fields the unfinished transaction changed
+            Err(e) if matches!(e.downcast_ref::<FileError>(), Some(FileError::ChecksumMismatch { .. })) => {
+                warn!("Repairing torn page {}: {}", page_id, e);
+                file_manager.repair(page_id)?;
+                Ok(Some(self.buffer_manager.pin(page_id)?))
+            }
+            result => result.map(Some),
+        }
+    }
 }
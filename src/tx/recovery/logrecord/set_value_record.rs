@@ -0,0 +1,210 @@
+use std::{
+    fmt, mem,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use crate::{
+    file::{Lsn, Page, PageId},
+    log::manager::LogManager,
+    tx::recovery::logrecord::{LogRecord, TxOp, UndoContext},
+};
+
+/// A type of field whose old value can be logged by a [`SetValueRecord`].
+pub trait LoggedValue: Sized + fmt::Debug {
+    /// The log record type of changes to fields of this type.
+    const OP: TxOp;
+
+    /// Reads the value at `offset` of `page`.
+    fn read(page: &Page, offset: usize) -> anyhow::Result<Self>;
+
+    /// Writes the value to `offset` of `page`.
+    fn write(&self, page: &mut Page, offset: usize) -> anyhow::Result<()>;
+
+    /// Returns the number of bytes the value takes in a page.
+    fn size(&self) -> usize;
+}
+
+/// Implements [`LoggedValue`] for a type with a fixed size and a pair of page accessors.
+macro_rules! fixed_size_value {
+    ($type:ty, $op:ident, $get:ident, $set:ident) => {
+        impl LoggedValue for $type {
+            const OP: TxOp = TxOp::$op;
+
+            fn read(page: &Page, offset: usize) -> anyhow::Result<Self> {
+                page.$get(offset)
+            }
+
+            fn write(&self, page: &mut Page, offset: usize) -> anyhow::Result<()> {
+                page.$set(offset, *self)
+            }
+
+            fn size(&self) -> usize {
+                mem::size_of::<$type>()
+            }
+        }
+    };
+}
+
+fixed_size_value!(i32, SetI32, get_integer, set_integer);
+fixed_size_value!(i64, SetI64, get_i64, set_i64);
+fixed_size_value!(u64, SetU64, get_u64, set_u64);
+fixed_size_value!(f64, SetF64, get_f64, set_f64);
+fixed_size_value!(u8, SetU8, get_u8, set_u8);
+
+/// The raw byte of a bool field.
+///
+/// Undo restores the byte as it was, so a bool can be written over a field that
+/// held something other than a bool before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoolByte(pub u8);
+
+impl LoggedValue for BoolByte {
+    const OP: TxOp = TxOp::SetBool;
+
+    fn read(page: &Page, offset: usize) -> anyhow::Result<Self> {
+        page.get_u8(offset).map(BoolByte)
+    }
+
+    fn write(&self, page: &mut Page, offset: usize) -> anyhow::Result<()> {
+        page.set_u8(offset, self.0)
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<u8>()
+    }
+}
+
+impl LoggedValue for SystemTime {
+    const OP: TxOp = TxOp::SetTimestamp;
+
+    fn read(page: &Page, offset: usize) -> anyhow::Result<Self> {
+        page.get_timestamp(offset)
+    }
+
+    fn write(&self, page: &mut Page, offset: usize) -> anyhow::Result<()> {
+        page.set_timestamp(offset, *self)
+    }
+
+    fn size(&self) -> usize {
+        mem::size_of::<i64>()
+    }
+}
+
+impl LoggedValue for String {
+    const OP: TxOp = TxOp::SetString;
+
+    fn read(page: &Page, offset: usize) -> anyhow::Result<Self> {
+        page.get_string(offset)
+    }
+
+    fn write(&self, page: &mut Page, offset: usize) -> anyhow::Result<()> {
+        page.set_string(offset, self)
+    }
+
+    fn size(&self) -> usize {
+        Page::max_length(self)
+    }
+}
+
+pub type SetI32Record = SetValueRecord<i32>;
+pub type SetStringRecord = SetValueRecord<String>;
+pub type SetI64Record = SetValueRecord<i64>;
+pub type SetU64Record = SetValueRecord<u64>;
+pub type SetF64Record = SetValueRecord<f64>;
+pub type SetBoolRecord = SetValueRecord<BoolByte>;
+pub type SetU8Record = SetValueRecord<u8>;
+pub type SetTimestampRecord = SetValueRecord<SystemTime>;
+
+/// Holds the value a field of a page had before a transaction changed it.
+///
+/// Every record type stores the transaction number, the file name, the block number
+/// and the offset of the field, followed by the old value.
+pub struct SetValueRecord<T> {
+    tx_num: i32,
+    page_id: PageId,
+    offset: usize,
+    value: T,
+}
+
+impl<T: LoggedValue> SetValueRecord<T> {
+    //TODO: I really don't like that we are passing a page here.
+    // The real interface is: "Restore it from a byte slice".
+    pub fn new(page: Page) -> anyhow::Result<Self> {
+        let tpos = mem::size_of::<i32>();
+        let tx_num = page.get_integer(tpos)?;
+        let fpos = tpos + mem::size_of::<i32>();
+        let file_name = page.get_string(fpos)?;
+        let bpos = fpos + Page::max_length(&file_name);
+        let block_num = page.get_integer(bpos)? as u64;
+        let opos = bpos + mem::size_of::<i32>();
+        let offset = page.get_integer(opos)? as usize;
+        let vpos = opos + mem::size_of::<i32>();
+        let value = T::read(&page, vpos)?;
+        Ok(SetValueRecord {
+            tx_num,
+            page_id: PageId::new(file_name.into(), block_num),
+            offset,
+            value,
+        })
+    }
+
+    pub(crate) fn write_to_log(
+        log_manager: Arc<Mutex<LogManager>>,
+        tx_num: i32,
+        page_id: &PageId,
+        offset: usize,
+        value: &T,
+    ) -> anyhow::Result<Lsn> {
+        let tpos = mem::size_of::<i32>();
+        let fpos = tpos + mem::size_of::<i32>();
+        let bpos = fpos + Page::max_length(page_id.path().to_str().unwrap()); // the unwrap seams odd
+        let opos = bpos + mem::size_of::<i32>();
+        let vpos = opos + mem::size_of::<i32>();
+        let record_size = vpos + value.size();
+
+        let mut page = Page::with_size(record_size);
+        page.set_integer(0, T::OP as i32)?;
+        page.set_integer(tpos, tx_num)?;
+        page.set_string(fpos, page_id.path().to_str().unwrap())?;
+        page.set_integer(bpos, page_id.block_no() as i32)?;
+        page.set_integer(opos, offset as i32)?;
+        value.write(&mut page, vpos)?;
+
+        log_manager.lock().unwrap().append(page.content())
+    }
+}
+
+impl<T: LoggedValue> LogRecord for SetValueRecord<T> {
+    fn op(&self) -> TxOp {
+        T::OP
+    }
+
+    fn tx_num(&self) -> i32 {
+        self.tx_num
+    }
+
+    fn undo(&self, ctx: &mut UndoContext) -> anyhow::Result<()> {
+        let Some(buffer) = ctx.pin(&self.page_id)? else {
+            return Ok(());
+        };
+        let mut page = buffer.lock()?;
+        self.value.write(&mut page, self.offset)?;
+        page.set_modified(self.tx_num, None)
+    }
+}
+
+impl<T: LoggedValue> fmt::Display for SetValueRecord<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<{:?} {} {:?} {} {} {:?}>",
+            T::OP,
+            self.tx_num,
+            self.page_id.path(),
+            self.page_id.block_no(),
+            self.offset,
+            self.value
+        )
+    }
+}
@@ -1,7 +1,10 @@
-use std::{mem, sync::{Arc, Mutex}};
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
-    file::Page,
+    file::{Lsn, Page},
     log::manager::LogManager,
     tx::recovery::logrecord::{LogRecord, TxOp, UndoContext},
 };
@@ -17,7 +20,10 @@ impl StartRecord {
         Ok(StartRecord { tx_num })
     }
 
-    pub(crate) fn write_to_log(log_manager: Arc<Mutex<LogManager>>, tx_num: i32) -> anyhow::Result<usize> {
+    pub(crate) fn write_to_log(
+        log_manager: Arc<Mutex<LogManager>>,
+        tx_num: i32,
+    ) -> anyhow::Result<Lsn> {
         let mut page = Page::with_size(mem::size_of::<i32>() * 2);
         page.set_integer(0, TxOp::Start as i32)?;
         page.set_integer(mem::size_of::<i32>(), tx_num)?;
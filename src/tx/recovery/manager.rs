@@ -1,91 +1,106 @@
 use anyhow::anyhow;
-use std::sync::{Arc, Mutex};
+use std::{
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 use crate::{
     buffer::{buffer::Buffer, manager::BufferManager},
+    file::{Lsn, PageId},
     log::manager::LogManager,
     tx::recovery::logrecord::{
-        checkpoint_record::CheckpointRecord, commit_record::CommitRecord, from_page, rollback_record::RollbackRecord, set_i32_record::SetI32Record, set_string_record::SetStringRecord, start_record::StartRecord, TxOp, UndoContext
+        TxOp, UndoContext,
+        checkpoint_record::CheckpointRecord,
+        commit_record::CommitRecord,
+        from_page,
+        rollback_record::RollbackRecord,
+        set_value_record::{LoggedValue, SetValueRecord},
+        start_record::StartRecord,
     },
 };
 
 pub struct RecoveryManager {
     log_manager: Arc<Mutex<LogManager>>,
-    buffer_manager: Arc<Mutex<BufferManager>>,
+    buffer_manager: Arc<BufferManager>,
     tx_num: i32,
+    /// Whether the log is read-only, so the transaction can neither change nor log anything.
+    read_only: bool,
 }
 
 impl RecoveryManager {
     pub fn new(
         tx_num: i32,
         log_manager: Arc<Mutex<LogManager>>,
-        buffer_manager: Arc<Mutex<BufferManager>>,
-    ) -> Self {
-        StartRecord::write_to_log(log_manager.clone(), tx_num).unwrap();
-        RecoveryManager {
+        buffer_manager: Arc<BufferManager>,
+    ) -> anyhow::Result<Self> {
+        let read_only = log_manager.lock().unwrap().is_read_only();
+        if !read_only {
+            StartRecord::write_to_log(log_manager.clone(), tx_num)?;
+        }
+        Ok(RecoveryManager {
             log_manager,
             buffer_manager,
             tx_num,
-        }
+            read_only,
+        })
     }
 
     pub fn commit(&mut self) -> anyhow::Result<()> {
-        self.buffer_manager.lock().unwrap().flush_all(self.tx_num)?;
+        if self.read_only {
+            return Ok(());
+        }
+        self.buffer_manager.flush_all(self.tx_num)?;
         let lsn = CommitRecord::write_to_log(self.log_manager.clone(), self.tx_num)?;
         self.log_manager.lock().unwrap().flush(lsn)
     }
 
     pub fn rollback(&mut self) -> anyhow::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
         self.do_rollback()?;
-        self.buffer_manager.lock().unwrap().flush_all(self.tx_num)?;
+        self.buffer_manager.flush_all(self.tx_num)?;
         let lsn = RollbackRecord::write_to_log(self.log_manager.clone(), self.tx_num)?;
         self.log_manager.lock().unwrap().flush(lsn)
     }
 
     pub fn recover(&mut self) -> anyhow::Result<()> {
-        self.do_recover()?;
-        self.buffer_manager
-            .lock()
-            .unwrap()
-            .flush_all(self.tx_num)
-            .unwrap();
-        let lsn = CheckpointRecord::write_to_log(self.log_manager.clone(), self.tx_num).unwrap();
-        self.log_manager.lock().unwrap().flush(lsn)
+        self.recover_backup(None, 0..0)
     }
 
-    pub fn set_int(
-        &self,
-        buffer: &mut Buffer,
-        offset: usize,
-        _new_value: i32,
-    ) -> anyhow::Result<usize> {
-        let old_val = buffer.contents().get_integer(offset)?;
-        let page_id = buffer
-            .page_id()
-            .ok_or_else(|| anyhow!("Buffer is not assigned to a page"))?;
-        SetI32Record::write_to_log(
-            self.log_manager.clone(),
-            self.tx_num,
-            page_id,
-            offset,
-            old_val,
-        )
+    /// Recovers a database restored from a backup.
+    ///
+    /// `relocation` holds the directory the log's files were backed up from and the
+    /// one they live in now, if they moved. Commit and rollback records whose LSN is
+    /// in `late` were written after the backup started, which did not wait for the
+    /// pages of their transactions, so those transactions are rolled back.
+    pub fn recover_backup(
+        &mut self,
+        relocation: Option<(PathBuf, PathBuf)>,
+        late: Range<Lsn>,
+    ) -> anyhow::Result<()> {
+        self.do_recover(relocation, late)?;
+        // The undone changes are marked with the numbers of the transactions that
+        // made them, so every dirty page has to be written before the checkpoint
+        self.buffer_manager.flush_every_dirty_page()?;
+        let lsn = CheckpointRecord::write_to_log(self.log_manager.clone(), self.tx_num)?;
+        self.log_manager.lock().unwrap().flush(lsn)
     }
 
-    pub fn set_string(
+    /// Logs the value of type `T` at `offset` of `buffer` so it can be restored on undo.
+    ///
+    /// Returns the LSN of the log record.
+    pub fn log_old_value<T: LoggedValue>(
         &self,
-        buffer: &mut Buffer,
+        buffer: &Buffer,
         offset: usize,
-        _new_value: &str,
-    ) -> anyhow::Result<usize> {
-        let old_val = buffer.contents().get_string(offset)?;
-        let page_id = buffer
-            .page_id()
-            .ok_or_else(|| anyhow!("Buffer is not assigned to a page"))?;
-        SetStringRecord::write_to_log(
+    ) -> anyhow::Result<Lsn> {
+        let old_val = T::read(buffer.contents(), offset)?;
+        SetValueRecord::write_to_log(
             self.log_manager.clone(),
             self.tx_num,
-            page_id,
+            assigned_page(buffer)?,
             offset,
             &old_val,
         )
@@ -94,31 +109,46 @@ impl RecoveryManager {
     fn do_rollback(&mut self) -> anyhow::Result<()> {
         let mut ctx = UndoContext {
             buffer_manager: self.buffer_manager.clone(),
+            relocation: None,
         };
-        for entry in self.log_manager.lock().unwrap().iter()? {
+        // Bind the iterator first so the log manager lock is released before undoing:
+        // pinning a buffer may evict a dirty page, which needs to flush the log.
+        let entries = self.log_manager.lock().unwrap().iter()?;
+        for entry in entries {
             let log_record = from_page(&entry)?;
+            if log_record.tx_num() != self.tx_num {
+                continue;
+            }
             if log_record.op() == TxOp::Start {
                 return Ok(());
             }
 
             log_record.undo(&mut ctx)?;
         }
-        return Ok(());
+        Ok(())
     }
 
-    fn do_recover(&mut self) -> anyhow::Result<()> {
+    fn do_recover(
+        &mut self,
+        relocation: Option<(PathBuf, PathBuf)>,
+        late: Range<Lsn>,
+    ) -> anyhow::Result<()> {
         let mut finished_txs = vec![];
         let mut ctx = UndoContext {
             buffer_manager: self.buffer_manager.clone(),
+            relocation,
         };
-        for entry in self.log_manager.lock().unwrap().iter().unwrap() {
-            let log_record = from_page(&entry).unwrap();
+        let mut entries = self.log_manager.lock().unwrap().iter()?;
+        while let Some(entry) = entries.next() {
+            let log_record = from_page(&entry)?;
             // TODO: maybe a match statement would be better here
             if log_record.op() == TxOp::Checkpoint {
                 return Ok(());
             }
             if log_record.op() == TxOp::Commit || log_record.op() == TxOp::Rollback {
-                finished_txs.push(log_record.tx_num());
+                if !late.contains(&entries.lsn()) {
+                    finished_txs.push(log_record.tx_num());
+                }
             } else if !finished_txs.contains(&log_record.tx_num()) {
                 log_record.undo(&mut ctx)?;
             }
@@ -126,3 +156,253 @@ impl RecoveryManager {
         Ok(())
     }
 }
+
+fn assigned_page(buffer: &Buffer) -> anyhow::Result<&PageId> {
+    buffer
+        .page_id()
+        .ok_or_else(|| anyhow!("Buffer is not assigned to a page"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io, path::Path, sync::Arc};
+
+    use crate::{
+        db::SimpleDB,
+        file::{
+            DurabilityMode, FileManager, FileOptions, PageId,
+            backend::{FaultyBackend, MemoryBackend},
+        },
+        tx::transaction::Transaction,
+    };
+
+    const PAGE_SIZE: usize = 128;
+    /// More blocks than the buffer pool holds, so uncommitted pages get evicted to storage.
+    const BLOCKS: u64 = 10;
+
+    /// What the workload saw commit before the storage crashed.
+    ///
+    /// A crash during a commit leaves it in doubt: once its record is in the log,
+    /// the commit survives even though it failed.
+    #[derive(Debug, Default)]
+    struct Committed {
+        value: Option<i32>,
+        other: bool,
+        /// The value of the commit the crash cut short, if any.
+        committing: Option<i32>,
+        committing_other: bool,
+    }
+
+    fn options() -> FileOptions {
+        FileOptions {
+            durability: DurabilityMode::FsyncOnFlush,
+            ..FileOptions::default()
+        }
+    }
+
+    fn set(tx: &mut Transaction, page_id: &PageId, value: i32) -> anyhow::Result<()> {
+        tx.pin(page_id)?;
+        tx.set_int(page_id, 0, value, true)?;
+        tx.unpin(page_id)
+    }
+
+    /// Commits two versions of the data blocks, leaves a third one uncommitted and
+    /// then commits an unrelated transaction, which syncs the uncommitted pages.
+    fn workload(db: &SimpleDB, committed: &mut Committed) -> anyhow::Result<()> {
+        let data = Path::new("db/data");
+        let mut tx = db.new_transaction()?;
+        let mut blocks = vec![];
+        for _ in 0..BLOCKS {
+            let page_id = tx.append(data)?;
+            set(&mut tx, &page_id, 1)?;
+            blocks.push(page_id);
+        }
+        committed.committing = Some(1);
+        tx.commit()?;
+        committed.value = Some(1);
+        committed.committing = None;
+
+        let mut tx = db.new_transaction()?;
+        for page_id in &blocks {
+            set(&mut tx, page_id, 2)?;
+        }
+        committed.committing = Some(2);
+        tx.commit()?;
+        committed.value = Some(2);
+        committed.committing = None;
+
+        let mut uncommitted = db.new_transaction()?;
+        for page_id in &blocks {
+            set(&mut uncommitted, page_id, 3)?;
+        }
+
+        let mut tx = db.new_transaction()?;
+        let other = tx.append(Path::new("db/other"))?;
+        set(&mut tx, &other, 4)?;
+        committed.committing_other = true;
+        tx.commit()?;
+        committed.other = true;
+        Ok(())
+    }
+
+    /// Runs the workload on top of `faulty` and crashes the storage afterwards,
+    /// unless the workload already ran into an injected crash.
+    fn run_until_crash(faulty: Arc<FaultyBackend>) -> Committed {
+        let mut committed = Committed::default();
+        if let Ok(db) = SimpleDB::with_backend("db", PAGE_SIZE, options(), faulty.clone()) {
+            let _ = workload(&db, &mut committed);
+            faulty.crash();
+        }
+        committed
+    }
+
+    fn verify_after_restart(disk: Arc<MemoryBackend>, committed: &Committed, crash_point: u64) {
+        let db = SimpleDB::with_backend("db", PAGE_SIZE, options(), disk)
+            .unwrap_or_else(|e| panic!("Reopen after crash at write {crash_point} failed: {e:?}"));
+        let mut tx = db.new_transaction().unwrap();
+
+        let data = Path::new("db/data");
+        let size = tx.size(data).unwrap();
+        if committed.value.is_some() {
+            assert_eq!(size, BLOCKS, "crash at write {crash_point}");
+        }
+        let mut values = vec![];
+        for block_no in 0..size {
+            let page_id = PageId::new(data.to_path_buf(), block_no);
+            tx.pin(&page_id).unwrap();
+            values.push(tx.get_int(&page_id, 0).unwrap());
+            tx.unpin(&page_id).unwrap();
+        }
+        let value = committed.value.unwrap_or(0);
+        let expected = vec![value; size as usize];
+        let in_doubt = committed
+            .committing
+            .map(|committing| vec![committing; size as usize]);
+        assert!(
+            values == expected || Some(&values) == in_doubt.as_ref(),
+            "blocks hold {values:?} after crash at write {crash_point}"
+        );
+
+        let other = Path::new("db/other");
+        if tx.size(other).unwrap() > 0 {
+            let page_id = PageId::new(other.to_path_buf(), 0);
+            tx.pin(&page_id).unwrap();
+            let value = tx.get_int(&page_id, 0).unwrap();
+            if committed.committing_other && !committed.other {
+                assert!(value == 0 || value == 4, "crash at write {crash_point}");
+            } else {
+                let expected = if committed.other { 4 } else { 0 };
+                assert_eq!(value, expected, "crash at write {crash_point}");
+            }
+        } else {
+            assert!(!committed.other, "crash at write {crash_point}");
+        }
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn committed_data_survives_a_crash_at_every_write() {
+        // setup
+        let disk = Arc::new(MemoryBackend::new());
+        let slot_size = FileManager::with_backend(disk.clone(), PAGE_SIZE, options()).slot_size();
+        let faulty = Arc::new(FaultyBackend::new(disk.clone(), slot_size));
+        let committed = run_until_crash(faulty.clone());
+        assert!(committed.other, "workload should succeed without faults");
+        let writes = faulty.writes();
+
+        for crash_point in 1..=writes {
+            // test
+            let disk = Arc::new(MemoryBackend::new());
+            let faulty = Arc::new(FaultyBackend::new(disk.clone(), slot_size));
+            faulty.crash_at_write(crash_point);
+            let committed = run_until_crash(faulty);
+
+            // verify
+            verify_after_restart(disk, &committed, crash_point);
+        }
+    }
+
+    #[test]
+    fn committed_data_survives_a_torn_write_at_every_write() {
+        // setup
+        let disk = Arc::new(MemoryBackend::new());
+        let slot_size = FileManager::with_backend(disk.clone(), PAGE_SIZE, options()).slot_size();
+        let faulty = Arc::new(FaultyBackend::new(disk.clone(), slot_size));
+        run_until_crash(faulty.clone());
+        let writes = faulty.writes();
+
+        // Tears keep part of the header, part of the page or all but the checksum
+        for keep in [4, slot_size / 2, slot_size - 1] {
+            for tear_point in 1..=writes {
+                // test
+                let disk = Arc::new(MemoryBackend::new());
+                let faulty = Arc::new(FaultyBackend::new(disk.clone(), slot_size));
+                faulty.tear_write(tear_point, keep);
+                let committed = run_until_crash(faulty);
+
+                // verify
+                verify_after_restart(disk, &committed, tear_point);
+            }
+        }
+    }
+
+    #[test]
+    fn undone_changes_are_written_before_the_checkpoint() {
+        // setup
+        let disk = Arc::new(MemoryBackend::new());
+        let slot_size = FileManager::with_backend(disk.clone(), PAGE_SIZE, options()).slot_size();
+        let page_id = PageId::new("db/data".into(), 0);
+        let faulty = Arc::new(FaultyBackend::new(disk.clone(), slot_size));
+        let db = SimpleDB::with_backend("db", PAGE_SIZE, options(), faulty.clone()).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        tx.append(page_id.path()).unwrap();
+        set(&mut tx, &page_id, 1).unwrap();
+        tx.commit().unwrap();
+        let mut uncommitted = db.new_transaction().unwrap();
+        set(&mut uncommitted, &page_id, 2).unwrap();
+        db.buffer_manager().flush_every_dirty_page().unwrap();
+        faulty.crash();
+        drop(db);
+
+        // test
+        // Recovery undoes the uncommitted change, then storage crashes again
+        let faulty = Arc::new(FaultyBackend::new(disk.clone(), slot_size));
+        let db = SimpleDB::with_backend("db", PAGE_SIZE, options(), faulty.clone()).unwrap();
+        faulty.crash();
+        drop(db);
+
+        // verify
+        let db = SimpleDB::with_backend("db", PAGE_SIZE, options(), disk).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        tx.pin(&page_id).unwrap();
+        assert_eq!(tx.get_int(&page_id, 0).unwrap(), 1);
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn io_errors_on_a_page_fail_the_transaction_touching_it() {
+        // setup
+        let disk = Arc::new(MemoryBackend::new());
+        let slot_size = FileManager::with_backend(disk.clone(), PAGE_SIZE, options()).slot_size();
+        let faulty = Arc::new(FaultyBackend::new(disk, slot_size));
+        let db = SimpleDB::with_backend("db", PAGE_SIZE, options(), faulty.clone()).unwrap();
+        let mut tx = db.new_transaction().unwrap();
+        let broken = tx.append(Path::new("db/data")).unwrap();
+        let healthy = tx.append(Path::new("db/data")).unwrap();
+        tx.commit().unwrap();
+        faulty.fail_page(&broken);
+
+        // test
+        let mut tx = db.new_transaction().unwrap();
+        let err = tx.pin(&broken).unwrap_err();
+
+        // verify
+        let io_err = err
+            .downcast_ref::<io::Error>()
+            .expect("expected an I/O error");
+        assert_eq!(io_err.raw_os_error(), Some(libc::EIO));
+        tx.pin(&healthy).unwrap();
+        tx.set_int(&healthy, 0, 7, true).unwrap();
+        tx.commit().unwrap();
+    }
+}
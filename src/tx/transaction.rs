@@ -1,93 +1,133 @@
 use std::{
-    path::Path,
-    sync::{Arc, Mutex},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::SystemTime,
 };
 
+use anyhow::anyhow;
+
 use crate::{
     buffer::manager::BufferManager,
-    file::{PageId, FileManager},
+    file::{FileManager, Lsn, Page, PageId},
     log::manager::LogManager,
     tx::{
         bufferlist::BufferList,
         concurrency::{lock_table::LockTable, manager::ConcurrencyManager},
-        recovery::manager::RecoveryManager,
+        free_space::FreeSpaceMap,
+        recovery::{
+            logrecord::set_value_record::{BoolByte, LoggedValue},
+            manager::RecoveryManager,
+        },
     },
 };
 
 static END_OF_FILE: u64 = 42;
 
+/// Errors raised by a [`Transaction`] itself rather than by the layers below it.
+#[derive(thiserror::Error, Debug)]
+pub enum TransactionError {
+    /// The transaction tried to modify a database opened with
+    /// [`SimpleDB::open_read_only`](crate::db::SimpleDB::open_read_only).
+    #[error("Transaction {tx_num} cannot modify {path:?}: the database is read-only")]
+    ReadOnly { tx_num: i32, path: PathBuf },
+}
+
 pub struct Transaction {
     file_manager: Arc<FileManager>,
-    buffer_manager: Arc<Mutex<BufferManager>>,
+    buffer_manager: Arc<BufferManager>,
     recovery_manager: Arc<Mutex<RecoveryManager>>,
     concurrency_manager: ConcurrencyManager,
     tx_num: i32,
     buffer_list: BufferList,
+    /// Held shared while committing or rolling back, so a backup can hold transactions
+    /// from finishing while it copies the database.
+    commit_latch: Arc<RwLock<()>>,
 }
 
 impl Transaction {
     pub fn new(
         file_manager: Arc<FileManager>,
         log_manager: Arc<Mutex<LogManager>>,
-        buffer_manager: Arc<Mutex<BufferManager>>,
+        buffer_manager: Arc<BufferManager>,
         tx_num: Arc<Mutex<i32>>,
-        lock_table: Arc<Mutex<LockTable>>,
-    ) -> Self {
+        lock_table: Arc<LockTable>,
+        commit_latch: Arc<RwLock<()>>,
+    ) -> anyhow::Result<Self> {
         let tx_num = next_tx_num(tx_num);
         let recovery_manager = Arc::new(Mutex::new(RecoveryManager::new(
             tx_num,
             log_manager.clone(),
             buffer_manager.clone(),
-        )));
-        Self {
+        )?));
+        Ok(Self {
             file_manager,
             buffer_manager: buffer_manager.clone(),
             recovery_manager,
             concurrency_manager: ConcurrencyManager::new(lock_table),
             tx_num,
             buffer_list: BufferList::new(buffer_manager),
-        }
+            commit_latch,
+        })
     }
 
     pub fn commit(&mut self) -> anyhow::Result<()> {
-        let mut recovery_manager = self
-            .recovery_manager
-            .lock()
-            .expect("Mutex of recovery manager poisoned");
-        recovery_manager.commit()?;
+        {
+            let _latch = self.commit_latch.read().unwrap();
+            let mut recovery_manager = self
+                .recovery_manager
+                .lock()
+                .expect("Mutex of recovery manager poisoned");
+            recovery_manager.commit()?;
+        }
+        self.concurrency_manager.release()?;
+        self.buffer_list.unpin_all();
 
         Ok(())
     }
 
     pub fn rollback(&mut self) -> anyhow::Result<()> {
-        self.recovery_manager.lock().unwrap().rollback()?;
+        {
+            let _latch = self.commit_latch.read().unwrap();
+            self.recovery_manager.lock().unwrap().rollback()?;
+        }
         self.concurrency_manager.release()?;
-        self.buffer_list.unpin_all()?;
+        self.buffer_list.unpin_all();
 
         Ok(())
     }
 
     pub fn recover(&mut self) -> anyhow::Result<()> {
-        self.buffer_manager.lock().unwrap().flush_all(self.tx_num)?;
+        self.buffer_manager.flush_all(self.tx_num)?;
         self.recovery_manager.lock().unwrap().recover()
     }
 
+    /// Like [`recover`](Self::recover), for a database restored from a backup, see
+    /// [`RecoveryManager::recover_backup`].
+    pub fn recover_backup(
+        &mut self,
+        relocation: Option<(PathBuf, PathBuf)>,
+        late: Range<Lsn>,
+    ) -> anyhow::Result<()> {
+        self.buffer_manager.flush_all(self.tx_num)?;
+        self.recovery_manager
+            .lock()
+            .unwrap()
+            .recover_backup(relocation, late)
+    }
+
     pub fn pin(&mut self, page_id: &PageId) -> anyhow::Result<()> {
         self.buffer_list.pin(page_id)
     }
 
     pub fn unpin(&mut self, page_id: &PageId) -> anyhow::Result<()> {
-        self.buffer_list.unpin(page_id)
+        self.buffer_list.unpin(page_id);
+        Ok(())
     }
 
     pub fn get_int(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<i32> {
-        let buff = self
-            .buffer_list
-            .get_buffer(page_id)
-            .unwrap()
-            .lock()
-            .unwrap();
-        buff.contents().get_integer(offset)
+        let buff = self.buffer_list.get_buffer(page_id).unwrap().lock()?;
+        buff.get_integer(offset)
     }
 
     pub fn set_int(
@@ -97,34 +137,13 @@ impl Transaction {
         value: i32,
         log: bool,
     ) -> anyhow::Result<()> {
-        self.concurrency_manager.x_lock(page_id)?;
-        let mut buff = self
-            .buffer_list
-            .get_buffer(page_id)
-            .unwrap()
-            .lock()
-            .unwrap();
-        let mut lsn: i32 = -1;
-        if log {
-            let rm = self.recovery_manager.lock().unwrap();
-            lsn = rm.set_int(&mut buff, offset, value)?.try_into().unwrap();
-        }
-        let p = buff.contents_mut();
-        p.set_integer(offset, value)?;
-        buff.set_modified(self.tx_num, lsn);
-
-        Ok(())
+        self.write_value::<i32>(page_id, offset, log, |page| page.set_integer(offset, value))
     }
 
     pub fn get_string(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<String> {
         self.concurrency_manager.s_lock(page_id)?;
-        let buff = self
-            .buffer_list
-            .get_buffer(page_id)
-            .unwrap()
-            .lock()
-            .unwrap();
-        buff.contents().get_string(offset)
+        let buff = self.buffer_list.get_buffer(page_id).unwrap().lock()?;
+        buff.get_string(offset)
     }
 
     pub fn set_string(
@@ -134,27 +153,97 @@ impl Transaction {
         value: &str,
         log: bool,
     ) -> anyhow::Result<()> {
-        self.concurrency_manager.x_lock(page_id)?;
-        let mut buff = self
-            .buffer_list
-            .get_buffer(page_id)
-            .unwrap()
-            .lock()
-            .unwrap();
-        let mut lsn: i32 = -1;
-        if log {
-            let rm = self.recovery_manager.lock().unwrap();
-            lsn = rm.set_string(&mut buff, offset, value)?.try_into().unwrap();
-        }
-        let p = buff.contents_mut();
-        p.set_string(offset , value)?;
-        buff.set_modified(self.tx_num, lsn);
+        self.write_value::<String>(page_id, offset, log, |page| page.set_string(offset, value))
+    }
 
-        Ok(())
+    pub fn get_i64(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<i64> {
+        self.read_value(page_id, |page| page.get_i64(offset))
+    }
+
+    pub fn set_i64(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        value: i64,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        self.write_value::<i64>(page_id, offset, log, |page| page.set_i64(offset, value))
+    }
+
+    pub fn get_u64(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<u64> {
+        self.read_value(page_id, |page| page.get_u64(offset))
+    }
+
+    pub fn set_u64(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        value: u64,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        self.write_value::<u64>(page_id, offset, log, |page| page.set_u64(offset, value))
+    }
+
+    pub fn get_f64(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<f64> {
+        self.read_value(page_id, |page| page.get_f64(offset))
+    }
+
+    pub fn set_f64(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        value: f64,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        self.write_value::<f64>(page_id, offset, log, |page| page.set_f64(offset, value))
+    }
+
+    pub fn get_bool(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<bool> {
+        self.read_value(page_id, |page| page.get_bool(offset))
+    }
+
+    pub fn set_bool(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        value: bool,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        self.write_value::<BoolByte>(page_id, offset, log, |page| page.set_bool(offset, value))
+    }
+
+    pub fn get_u8(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<u8> {
+        self.read_value(page_id, |page| page.get_u8(offset))
+    }
+
+    pub fn set_u8(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        value: u8,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        self.write_value::<u8>(page_id, offset, log, |page| page.set_u8(offset, value))
+    }
+
+    pub fn get_timestamp(&mut self, page_id: &PageId, offset: usize) -> anyhow::Result<SystemTime> {
+        self.read_value(page_id, |page| page.get_timestamp(offset))
+    }
+
+    pub fn set_timestamp(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        value: SystemTime,
+        log: bool,
+    ) -> anyhow::Result<()> {
+        self.write_value::<SystemTime>(page_id, offset, log, |page| {
+            page.set_timestamp(offset, value)
+        })
     }
 
     pub fn available_buffers(&self) -> usize {
-        self.buffer_manager.lock().unwrap().available()
+        self.buffer_manager.available()
     }
 
     pub fn size(&mut self, path: &Path) -> anyhow::Result<u64> {
@@ -163,15 +252,100 @@ impl Transaction {
         self.file_manager.size(path)
     }
 
+    /// Locks the size of the specified file exclusively until the transaction ends.
+    ///
+    /// Taking the lock before reading the size avoids upgrading a shared lock,
+    /// which deadlocks when two transactions upgrade at the same time.
+    pub(crate) fn x_lock_size(&mut self, path: &Path) -> anyhow::Result<()> {
+        let dummyblk = PageId::new(path.to_path_buf(), END_OF_FILE);
+        self.concurrency_manager.x_lock(&dummyblk)
+    }
+
     pub fn append(&mut self, path: &Path) -> anyhow::Result<PageId> {
+        self.check_writable(path)?;
         let dummyblk = PageId::new(path.to_path_buf(), END_OF_FILE);
         self.concurrency_manager.x_lock(&dummyblk)?;
         self.file_manager.append_page(path)
     }
 
+    /// Allocates a page of the specified file, reusing a freed page if there is one.
+    ///
+    /// The allocation is logged, so rolling back or recovering the transaction
+    /// returns the page to the free list.
+    pub fn allocate_page(&mut self, path: &Path) -> anyhow::Result<PageId> {
+        self.check_writable(path)?;
+        FreeSpaceMap::new(path).allocate(self)
+    }
+
+    /// Returns a page to the free list of its file so later allocations can reuse it.
+    ///
+    /// The free is logged, so rolling back or recovering the transaction keeps
+    /// the page allocated.
+    pub fn free_page(&mut self, page_id: &PageId) -> anyhow::Result<()> {
+        self.check_writable(page_id.path())?;
+        FreeSpaceMap::new(page_id.path()).free(self, page_id.block_no())
+    }
+
     pub fn page_size(&self) -> usize {
         self.file_manager.page_size()
     }
+
+    /// Fails with [`TransactionError::ReadOnly`] if the database is read-only.
+    ///
+    /// Temporary files can still be written, they are not part of the database.
+    fn check_writable(&self, path: &Path) -> anyhow::Result<()> {
+        if self.file_manager.options().read_only && !self.file_manager.is_temp_file(path) {
+            return Err(TransactionError::ReadOnly {
+                tx_num: self.tx_num,
+                path: path.to_path_buf(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Reads a value from a pinned page while holding a shared lock on it.
+    fn read_value<T>(
+        &mut self,
+        page_id: &PageId,
+        read: impl FnOnce(&Page) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        self.concurrency_manager.s_lock(page_id)?;
+        let buff = self
+            .buffer_list
+            .get_buffer(page_id)
+            .ok_or_else(|| anyhow!("Page {} is not pinned", page_id))?
+            .lock()?;
+        read(&buff)
+    }
+
+    /// Writes a value to a pinned page while holding an exclusive lock on it.
+    ///
+    /// If `log` is set, the old value of type `T` at `offset` is logged first,
+    /// and the buffer is marked as modified with the record's LSN.
+    /// Changes to temporary files are never logged.
+    fn write_value<T: LoggedValue>(
+        &mut self,
+        page_id: &PageId,
+        offset: usize,
+        log: bool,
+        write: impl FnOnce(&mut Page) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        self.check_writable(page_id.path())?;
+        self.concurrency_manager.x_lock(page_id)?;
+        let mut buff = self
+            .buffer_list
+            .get_buffer(page_id)
+            .ok_or_else(|| anyhow!("Page {} is not pinned", page_id))?
+            .lock()?;
+        let mut lsn = None;
+        if log && !self.file_manager.is_temp_file(page_id.path()) {
+            let rm = self.recovery_manager.lock().unwrap();
+            lsn = Some(rm.log_old_value::<T>(buff.buffer(), offset)?);
+        }
+        write(&mut buff)?;
+        buff.set_modified(self.tx_num, lsn)
+    }
 }
 
 fn next_tx_num(tx_num: Arc<Mutex<i32>>) -> i32 {
@@ -179,3 +353,235 @@ fn next_tx_num(tx_num: Arc<Mutex<i32>>) -> i32 {
     *num += 1;
     *num
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::{
+        db::SimpleDB,
+        file::{
+            FileOptions,
+            backend::{MemoryBackend, StorageBackend},
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn rollback_restores_typed_values() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db = SimpleDB::with_backend("db", 400, FileOptions::default(), backend).unwrap();
+        let moment = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut tx1 = db.new_transaction().unwrap();
+        let page_id = tx1.append(Path::new("typed")).unwrap();
+        tx1.pin(&page_id).unwrap();
+        tx1.set_i64(&page_id, 0, i64::MAX, true).unwrap();
+        tx1.set_u64(&page_id, 8, u64::MAX, true).unwrap();
+        tx1.set_f64(&page_id, 16, 1234.5, true).unwrap();
+        tx1.set_bool(&page_id, 24, true, true).unwrap();
+        tx1.set_u8(&page_id, 25, 7, true).unwrap();
+        tx1.set_timestamp(&page_id, 26, moment, true).unwrap();
+        tx1.commit().unwrap();
+
+        // test
+        let mut tx2 = db.new_transaction().unwrap();
+        tx2.pin(&page_id).unwrap();
+        tx2.set_i64(&page_id, 0, -1, true).unwrap();
+        tx2.set_u64(&page_id, 8, 0, true).unwrap();
+        tx2.set_f64(&page_id, 16, 0.0, true).unwrap();
+        tx2.set_bool(&page_id, 24, false, true).unwrap();
+        tx2.set_u8(&page_id, 25, 0, true).unwrap();
+        tx2.set_timestamp(&page_id, 26, UNIX_EPOCH, true).unwrap();
+        assert_eq!(tx2.get_i64(&page_id, 0).unwrap(), -1);
+        tx2.rollback().unwrap();
+
+        // verify
+        let mut tx3 = db.new_transaction().unwrap();
+        tx3.pin(&page_id).unwrap();
+        assert_eq!(tx3.get_i64(&page_id, 0).unwrap(), i64::MAX);
+        assert_eq!(tx3.get_u64(&page_id, 8).unwrap(), u64::MAX);
+        assert_eq!(tx3.get_f64(&page_id, 16).unwrap(), 1234.5);
+        assert!(tx3.get_bool(&page_id, 24).unwrap());
+        assert_eq!(tx3.get_u8(&page_id, 25).unwrap(), 7);
+        assert_eq!(tx3.get_timestamp(&page_id, 26).unwrap(), moment);
+        tx3.commit().unwrap();
+    }
+
+    #[test]
+    fn rollback_of_a_bool_restores_the_raw_byte_it_overwrote() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db = SimpleDB::with_backend("db", 400, FileOptions::default(), backend).unwrap();
+        let mut tx1 = db.new_transaction().unwrap();
+        let page_id = tx1.append(Path::new("typed")).unwrap();
+        tx1.pin(&page_id).unwrap();
+        tx1.set_u8(&page_id, 0, 0xab, true).unwrap();
+        tx1.commit().unwrap();
+
+        // test
+        let mut tx2 = db.new_transaction().unwrap();
+        tx2.pin(&page_id).unwrap();
+        tx2.set_bool(&page_id, 0, true, true).unwrap();
+        tx2.rollback().unwrap();
+
+        // verify
+        let mut tx3 = db.new_transaction().unwrap();
+        tx3.pin(&page_id).unwrap();
+        assert_eq!(tx3.get_u8(&page_id, 0).unwrap(), 0xab);
+        tx3.commit().unwrap();
+    }
+
+    #[test]
+    fn commit_releases_locks_and_unpins_buffers() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db = SimpleDB::with_backend("db", 400, FileOptions::default(), backend).unwrap();
+        let mut tx1 = db.new_transaction().unwrap();
+        let page_id = tx1.append(Path::new("data")).unwrap();
+        let available = tx1.available_buffers();
+        tx1.pin(&page_id).unwrap();
+        tx1.set_int(&page_id, 0, 42, true).unwrap();
+
+        // test
+        tx1.commit().unwrap();
+
+        // verify
+        let mut tx2 = db.new_transaction().unwrap();
+        assert_eq!(tx2.available_buffers(), available);
+        tx2.pin(&page_id).unwrap();
+        tx2.set_int(&page_id, 0, 43, true).unwrap();
+        tx2.commit().unwrap();
+    }
+
+    #[test]
+    fn rollback_leaves_changes_of_other_transactions_alone() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db = SimpleDB::with_backend("db", 400, FileOptions::default(), backend).unwrap();
+        let mut setup = db.new_transaction().unwrap();
+        let first = setup.append(Path::new("data")).unwrap();
+        let second = setup.append(Path::new("data")).unwrap();
+        setup.commit().unwrap();
+
+        let mut tx1 = db.new_transaction().unwrap();
+        let mut tx2 = db.new_transaction().unwrap();
+        tx2.pin(&second).unwrap();
+        tx2.set_int(&second, 0, 2, true).unwrap();
+        tx1.pin(&first).unwrap();
+        tx1.set_int(&first, 0, 1, true).unwrap();
+
+        // test
+        tx2.rollback().unwrap();
+
+        // verify
+        assert_eq!(tx1.get_int(&first, 0).unwrap(), 1);
+        tx1.commit().unwrap();
+        let mut tx3 = db.new_transaction().unwrap();
+        tx3.pin(&first).unwrap();
+        tx3.pin(&second).unwrap();
+        assert_eq!(tx3.get_int(&first, 0).unwrap(), 1);
+        assert_eq!(tx3.get_int(&second, 0).unwrap(), 0);
+        tx3.commit().unwrap();
+    }
+
+    #[test]
+    fn changes_to_temp_files_are_not_logged() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db = SimpleDB::with_backend("db", 400, FileOptions::default(), backend).unwrap();
+        let temp = db.file_manager().create_temp_file();
+        let mut tx1 = db.new_transaction().unwrap();
+        let page_id = tx1.append(temp.path()).unwrap();
+        tx1.pin(&page_id).unwrap();
+
+        // test
+        tx1.set_int(&page_id, 0, 42, true).unwrap();
+        tx1.rollback().unwrap();
+
+        // verify
+        let mut tx2 = db.new_transaction().unwrap();
+        tx2.pin(&page_id).unwrap();
+        assert_eq!(tx2.get_int(&page_id, 0).unwrap(), 42);
+        tx2.commit().unwrap();
+    }
+
+    #[test]
+    fn dropping_a_temp_file_discards_its_dirty_pages() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db =
+            SimpleDB::with_backend("db", 400, FileOptions::default(), backend.clone()).unwrap();
+        let temp = db.file_manager().create_temp_file();
+        let path = temp.path().to_path_buf();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(&path).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+        tx.unpin(&page_id).unwrap();
+
+        // test
+        drop(temp);
+        db.buffer_manager().flush_every_dirty_page().unwrap();
+        tx.commit().unwrap();
+
+        // verify
+        assert_eq!(backend.size(&path).unwrap(), 0);
+        assert!(!db.buffer_manager().dirty_pages().contains_key(&page_id));
+    }
+
+    #[test]
+    fn dropping_a_temp_file_drops_changes_to_its_pinned_pages() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db =
+            SimpleDB::with_backend("db", 400, FileOptions::default(), backend.clone()).unwrap();
+        let temp = db.file_manager().create_temp_file();
+        let path = temp.path().to_path_buf();
+        let mut tx = db.new_transaction().unwrap();
+        let page_id = tx.append(&path).unwrap();
+        tx.pin(&page_id).unwrap();
+        tx.set_int(&page_id, 0, 42, true).unwrap();
+
+        // test
+        drop(temp);
+        tx.set_int(&page_id, 4, 7, true).unwrap();
+        tx.unpin(&page_id).unwrap();
+        db.buffer_manager().flush_every_dirty_page().unwrap();
+        tx.commit().unwrap();
+
+        // verify
+        assert_eq!(backend.size(&path).unwrap(), 0);
+        assert!(!db.buffer_manager().dirty_pages().contains_key(&page_id));
+    }
+
+    #[test]
+    fn written_pages_carry_the_lsn_of_their_latest_change() {
+        // setup
+        let backend = Arc::new(MemoryBackend::new());
+        let db = SimpleDB::with_backend("db", 400, FileOptions::default(), backend).unwrap();
+        let mut tx1 = db.new_transaction().unwrap();
+        let page_id = tx1.append(Path::new("db/data")).unwrap();
+        tx1.pin(&page_id).unwrap();
+        tx1.set_int(&page_id, 0, 42, true).unwrap();
+        let frames = db.buffer_manager().frames().unwrap();
+        let frame = frames.iter().find(|f| f.page_id.as_ref() == Some(&page_id));
+        let change_lsn = frame.unwrap().lsn.unwrap();
+        let mut tx2 = db.new_transaction().unwrap();
+        let other = tx2.append(Path::new("db/other")).unwrap();
+        tx2.pin(&other).unwrap();
+        tx2.set_int(&other, 0, 7, true).unwrap();
+
+        // test
+        tx1.commit().unwrap();
+
+        // verify
+        let mut page = Page::with_size(400);
+        db.file_manager().read(&page_id, &mut page).unwrap();
+        assert_eq!(page.lsn(), change_lsn);
+        assert!(page.lsn() < db.log_manager().lock().unwrap().latest_lsn());
+        tx2.commit().unwrap();
+    }
+}